@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::checker::is_standard_global;
+use crate::codegen::{quote, INDENT};
+use crate::diagnostics::{CompileError, Diagnostic, Diagnostics};
+use crate::lower::module::{is_relative, require_name, resolve_specifier};
+use crate::parser::ast::{AstNodes, Block, Expressions};
+use crate::parser::errors::{BundleErrors, ErrorKind};
+use crate::parser::tokens::{Span, Token, Tokens};
+use crate::parser::visit::walk_block_mut;
+use crate::parser::{parse_for, Lexer};
+use crate::target::Target;
+
+/// Extensions tried, in order, when resolving a module name to a file.
+const EXTENSIONS: &[&str] = &["tlua", "lua"];
+
+/// Modules of LuaJIT a bundle requires from the host, besides the standard library.
+const HOST_MODULES: &[&str] = &["ffi", "jit"];
+
+/// A source file of the bundle, registered under the name it is required with.
+pub struct Module {
+    /// `util.strings` for `util/strings.tlua`
+    pub name: String,
+    pub path: String,
+    pub source: String,
+    /// Its statements, with `Error` nodes where it does not parse.
+    pub block: Block,
+    /// The parse errors of the file.
+    pub errors: Vec<Diagnostic>,
+    /// The comment tokens of the file, which the parser sets aside.
+    pub comments: Vec<Token>,
+}
+
+/// The modules reachable from the entry files through `require("name")` calls with a literal name and
+/// imports, dependencies before the modules requiring them. Names resolve relative to the directory of the first
+/// entry, the root, `a.b` to `a/b.tlua`, `a/b/init.tlua` or the same with a `.lua` extension. Relative import
+/// specifiers, `./a` and `../a`, are resolved from the directory of their file and rewritten from the root, so
+/// that checking and lowering need not know where the file is. Names that resolve to no file are left to the
+/// host's `require`, unless `bundle`: the bundle then only requires the standard library from the host, and any
+/// other module it cannot find is an error. Files that do not parse are kept with their errors, a circular
+/// require or an import leading out of the root fails with the diagnostics of every file involved. Files are
+/// parsed for `target`.
+pub fn module_graph(entries: &[String], bundle: bool, target: Target) -> Result<Vec<Module>, CompileError> {
+    let root = entries.first()
+        .and_then(|entry| Path::new(entry).parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut graph = Graph { root, bundle, target, modules: Vec::new(), states: HashMap::new(), stack: Vec::new(), failed: Vec::new() };
+    for entry in entries {
+        let path = PathBuf::from(entry);
+        let name = graph.module_name(&path);
+        graph.visit(name, path)?;
+    }
+    if !graph.failed.is_empty() {
+        return Err(CompileError::Failed(graph.failed));
+    }
+    Ok(graph.modules)
+}
+
+/// Whether a module is being loaded, with its dependencies, or done.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Visiting,
+    Done,
+}
+
+struct Graph {
+    root: PathBuf,
+    /// Report the modules not found, which the host cannot provide to a bundle.
+    bundle: bool,
+    target: Target,
+    modules: Vec<Module>,
+    states: HashMap<String, State>,
+    /// Modules being visited and the span of the `require` leading to the next one.
+    stack: Vec<(String, Span)>,
+    failed: Vec<Diagnostics>,
+}
+
+impl Graph {
+    fn module_name(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path).with_extension("");
+        let segments: Vec<String> = relative.components().map(|part| part.as_os_str().to_string_lossy().into_owned()).collect();
+        match segments.split_last() {
+            Some((last, parents)) if last == "init" && !parents.is_empty() => parents.join("."),
+            _ => segments.join("."),
+        }
+    }
+
+    /// The directory of a module file below the root.
+    fn directory(&self, path: &Path) -> Vec<String> {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        relative.parent().map_or_else(Vec::new, |parent| {
+            parent.components().map(|part| part.as_os_str().to_string_lossy().into_owned()).collect()
+        })
+    }
+
+    /// Rewrites the relative import specifiers of a file in `directory` from the root, reporting those leading
+    /// out of it.
+    fn resolve_imports(&self, block: &mut Block, directory: &[String], diagnostics: &mut Diagnostics) {
+        for node in block {
+            let AstNodes::Import(import) = &mut node.kind else {
+                continue;
+            };
+            if !is_relative(&import.module) {
+                continue;
+            }
+            match resolve_specifier(&import.module, directory) {
+                Some(segments) => import.module = format!("./{}", segments.join("/")),
+                None => {
+                    let error = ErrorKind::Bundle(BundleErrors::ModuleOutsideRoot(import.module.clone()));
+                    diagnostics.push(Diagnostic::error(&error, node.span.clone()).label("imported here"));
+                }
+            }
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let base = self.root.join(name.replace('.', "/"));
+        EXTENSIONS.iter()
+            .flat_map(|extension| [base.with_extension(extension), base.join("init").with_extension(extension)])
+            .find(|candidate| candidate.is_file())
+    }
+
+    fn visit(&mut self, name: String, path: PathBuf) -> std::io::Result<()> {
+        if self.states.get(&name) == Some(&State::Done) {
+            return Ok(());
+        }
+        let source = std::fs::read_to_string(&path)?;
+        let directory = self.directory(&path);
+        let path = path.to_string_lossy().into_owned();
+        let tokens: Vec<Token> = Lexer::new(&source).collect();
+        let comments = tokens.iter().filter(|token| matches!(token.kind, Tokens::Comment(_))).cloned().collect();
+        let (mut block, errors) = parse_for(tokens, self.target);
+        let mut diagnostics = Diagnostics::new(&path, &source);
+        self.resolve_imports(&mut block, &directory, &mut diagnostics);
+        self.states.insert(name.clone(), State::Visiting);
+        for required in requires(&mut block) {
+            let Some(required_path) = self.resolve(&required.name) else {
+                // types are erased, importing them requires nothing
+                let from_host = is_standard_global(&required.name) || HOST_MODULES.contains(&required.name.as_str());
+                if self.bundle && !required.type_only && (required.relative || !from_host) {
+                    let error = ErrorKind::Bundle(BundleErrors::ModuleNotFound(required.name));
+                    diagnostics.push(Diagnostic::error(&error, required.span).label("required here"));
+                }
+                continue;
+            };
+            if self.states.get(&required.name) == Some(&State::Visiting) {
+                // types are declared before any code runs, importing them is never circular
+                if required.type_only {
+                    continue;
+                }
+                let mut chain: Vec<String> = self.stack.iter().map(|(module, _)| module.clone())
+                    .skip_while(|module| *module != required.name)
+                    .collect();
+                chain.push(name.clone());
+                chain.push(required.name);
+                diagnostics.push(Diagnostic::error(&ErrorKind::Bundle(BundleErrors::CircularRequire(chain)), required.span).label("required here"));
+                continue;
+            }
+            self.stack.push((name.clone(), required.span));
+            self.visit(required.name, required_path)?;
+            self.stack.pop();
+        }
+        if !diagnostics.is_empty() {
+            self.failed.push(diagnostics);
+        }
+        self.states.insert(name.clone(), State::Done);
+        self.modules.push(Module { name, path, source, block, errors, comments });
+        Ok(())
+    }
+}
+
+/// A module passed as a literal to `require` or imported.
+struct Required {
+    name: String,
+    /// The call or import.
+    span: Span,
+    /// Only types are imported.
+    type_only: bool,
+    /// Imported from the directory of the file, `./a` or `../a`.
+    relative: bool,
+}
+
+fn requires(block: &mut Block) -> Vec<Required> {
+    let mut found: Vec<Required> = block.iter().filter_map(|node| match &node.kind {
+        AstNodes::Import(import) => Some(Required {
+            name: require_name(&import.module),
+            span: node.span.clone(),
+            type_only: import.type_only,
+            relative: is_relative(&import.module),
+        }),
+        _ => None,
+    }).collect();
+    walk_block_mut(block, &mut |expr| {
+        if let Expressions::Call { target, args } = &expr.kind {
+            if let (Expressions::Ident(callee), [arg]) = (&target.kind, args.as_slice()) {
+                if let (true, Expressions::String(name)) = (callee == "require", &arg.kind) {
+                    found.push(Required { name: name.clone(), span: expr.span.clone(), type_only: false, relative: false });
+                }
+            }
+        }
+    });
+    found
+}
+
+/// Joins the generated code of every module, named, into one chunk. Each module becomes a loader function
+/// run on its first `require`, requiring a module that is not bundled falls back to the host's `require`.
+/// The chunk runs the entries in order and returns what the last one returns.
+/// Also returns the line, 0-based, at which the code of each module starts, indented by one level.
+pub fn link(modules: &[(String, String)], entries: &[String]) -> (String, Vec<usize>) {
+    let mut out = String::from(r#"local __require = require
+local __modules, __loaded = {}, {}
+local function require(name)
+    local loaded = __loaded[name]
+    if loaded == nil then
+        local loader = __modules[name]
+        if loader == nil then
+            if __require == nil then
+                error("module '" .. name .. "' not found")
+            end
+            return __require(name)
+        end
+        loaded = loader(name)
+        if loaded == nil then
+            loaded = true
+        end
+        __loaded[name] = loaded
+    end
+    return loaded
+end
+"#);
+    let mut starts = Vec::with_capacity(modules.len());
+    for (name, code) in modules {
+        out.push_str(&format!("__modules[{}] = function(...)\n", quote(name)));
+        starts.push(out.matches('\n').count());
+        for line in code.lines() {
+            if !line.is_empty() {
+                out.push_str(INDENT);
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str("end\n");
+    }
+    if let Some((last, rest)) = entries.split_last() {
+        for entry in rest {
+            out.push_str(&format!("require({})\n", quote(entry)));
+        }
+        out.push_str(&format!("return require({})\n", quote(last)));
+    }
+    (out, starts)
+}
+
+
+#[test]
+fn bundle_modules() {
+    let dir = crate::TestDir::new("bundle", &[
+        ("main.tlua", "local strings = require(\"util.strings\")\nlocal json = require(\"json\")\nreturn strings.shout(\"hi\")"),
+        ("util/strings.tlua", "local M = {}\nM.shout = (s: string) => s:upper()\nreturn M"),
+    ]);
+    let entries = [dir.path("main.tlua")];
+    let modules = module_graph(&entries, false, Target::default()).unwrap();
+    let names: Vec<&str> = modules.iter().map(|module| module.name.as_str()).collect();
+    assert_eq!(names, ["util.strings", "main"]);
+    // a bundle cannot leave `json` to the host
+    let Err(CompileError::Failed(failed)) = module_graph(&entries, true, Target::default()) else {
+        panic!("expected the missing module to fail the bundle");
+    };
+    assert_eq!(failed[0].iter().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<_>>(), ["Cannot find module 'json' to bundle."]);
+    let (linked, starts) = link(&[("util.strings".to_string(), "local M = {}\n\nreturn M\n".to_string())], &["main".to_string()]);
+    assert_eq!(linked.lines().nth(starts[0]), Some("    local M = {}"));
+    assert!(linked.ends_with("__modules[\"util.strings\"] = function(...)\n    local M = {}\n\n    return M\nend\nreturn require(\"main\")\n"));
+}
+
+#[test]
+fn resolve_relative_imports() {
+    let dir = crate::TestDir::new("relative", &[
+        ("main.tlua", "import { page } from \"./app/views/page\"\nprint(page)"),
+        ("app/views/page.tlua", "import { trim } from \"../../lib/util\"\nexport const page = trim(\" x \")"),
+        ("lib/util.tlua", "export function trim(s: string): string return s end"),
+        ("outside.tlua", "import { trim } from \"../lib/util\""),
+    ]);
+    let modules = module_graph(&[dir.path("main.tlua")], true, Target::default()).unwrap();
+    let names: Vec<&str> = modules.iter().map(|module| module.name.as_str()).collect();
+    assert_eq!(names, ["lib.util", "app.views.page", "main"]);
+    assert!(matches!(&modules[1].block[0].kind, AstNodes::Import(import) if import.module == "./lib/util"));
+    let Err(CompileError::Failed(failed)) = module_graph(&[dir.path("outside.tlua")], false, Target::default()) else {
+        panic!("expected the import out of the root to fail");
+    };
+    assert_eq!(failed[0].iter().map(|diagnostic| diagnostic.code).collect::<Vec<_>>(), ["TL3002"]);
+}
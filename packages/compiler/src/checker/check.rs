@@ -0,0 +1,1202 @@
+mod modules;
+mod narrow;
+mod visibility;
+
+use std::collections::{HashMap, HashSet};
+use std::iter;
+pub use crate::checker::check::modules::ModuleExports;
+use crate::checker::check::modules::type_declarations;
+use crate::checker::check::narrow::{reference, Refinements};
+use crate::checker::evaluate::TypeEvaluator;
+use crate::checker::globals::{is_standard_global, standard_globals};
+use crate::checker::interfaces::Nonconformance;
+use crate::checker::types::{FieldTy, FunctionTy, ParamTy, TableTy, Ty};
+use crate::parser::ast::{Attrib, AstNode, AstNodes, BinaryOps, Block, ClassNode, Expression, Expressions, FunctionDecl, LiteralType, TableField, UnaryOps};
+use crate::parser::errors::{TypeError, TypeErrors};
+use crate::parser::tokens::Span;
+use crate::parser::visit::terminates;
+use crate::target::{bit_function, continue_label, Target};
+
+struct Variable {
+    ty: Ty,
+    /// Inferred from a table constructor: assigning a new field adds it, as in `local M = {} function M.f() end`.
+    open: bool,
+    /// Declared `<const>` or `<close>`.
+    constant: bool,
+}
+
+/// The function whose body is being checked.
+struct Frame {
+    /// Annotated return types, `None` when the function is unannotated.
+    returns: Option<Vec<Ty>>,
+    /// Type of the first value of every `return` statement.
+    inferred: Vec<Ty>,
+    /// Index of the first refinement frame of the function, narrowing outside of it does not apply.
+    refinement_base: usize,
+}
+
+/// Checks a file against its type annotations, collecting every error rather than stopping at the first.
+pub struct Checker {
+    pub evaluator: TypeEvaluator,
+    scopes: Vec<HashMap<String, Variable>>,
+    globals: HashMap<String, Variable>,
+    frames: Vec<Frame>,
+    /// Narrowed types of references, one frame per block.
+    refinements: Vec<Refinements>,
+    /// Dialect whose missing features are reported.
+    target: Target,
+    /// Classes whose members are being checked, innermost last.
+    classes: Vec<String>,
+    /// Label ending each enclosing loop body, `None` for function bodies.
+    continue_labels: Vec<Option<String>>,
+    /// Exports of the checked modules this file can import, by their `require` name.
+    modules: HashMap<String, ModuleExports>,
+    /// What the checked file exports.
+    pub exports: ModuleExports,
+    /// The class declaring the member each field access and method call resolves to, by the span of the access.
+    pub members: HashMap<Span, String>,
+    pub errors: Vec<TypeError>,
+}
+
+impl Checker {
+    /// A checker for gradual typing, or for strict nil-safety with `strict`.
+    pub fn new(strict: bool) -> Checker {
+        let mut evaluator = TypeEvaluator::new();
+        evaluator.strict = strict;
+        let globals = standard_globals().iter()
+            .map(|(name, ty)| (name.clone(), Variable { ty: ty.clone(), open: false, constant: false }))
+            .collect();
+        Checker { evaluator, scopes: Vec::new(), globals, frames: Vec::new(), refinements: Vec::new(), target: Target::default(), classes: Vec::new(), continue_labels: Vec::new(), modules: HashMap::new(), exports: ModuleExports::default(), members: HashMap::new(), errors: Vec::new() }
+    }
+
+    /// Reports the features `target` cannot express.
+    pub fn target(mut self, target: Target) -> Checker {
+        self.target = target;
+        self
+    }
+
+    /// Checks a whole file, errors are sorted by their position.
+    pub fn check(&mut self, block: &Block) {
+        self.declare_imported_types(block);
+        self.evaluator.declare_types(block);
+        self.exports.declarations = type_declarations(block);
+        self.check_module_return(block);
+        self.check_block(block);
+        self.errors.append(&mut self.evaluator.errors);
+        // member types are evaluated again on every expansion, keep one error per position and message
+        let mut seen = HashSet::new();
+        self.errors.retain(|error| seen.insert((error.span.start, error.span.end, error.kind.to_string())));
+        self.errors.sort_by_key(|error| (error.span.start, error.span.end));
+    }
+
+    fn error(&mut self, kind: TypeErrors, span: &Span) {
+        self.errors.push(TypeError { kind, span: span.clone() });
+    }
+
+    fn unsupported(&mut self, feature: &str, span: &Span) {
+        self.error(TypeErrors::UnsupportedFeature(feature.to_string(), self.target.to_string()), span);
+    }
+
+    // ---- scopes ----
+
+    fn declare(&mut self, name: &str, ty: Ty, open: bool) {
+        self.invalidate(name);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Variable { ty, open, constant: false });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Variable> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).or_else(|| self.globals.get(name))
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Variable> {
+        match self.scopes.iter_mut().rev().find(|scope| scope.contains_key(name)) {
+            Some(scope) => scope.get_mut(name),
+            None => self.globals.get_mut(name),
+        }
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        self.check_narrowed_block(block, Refinements::new());
+    }
+
+    /// Checks a block with references narrowed, narrowing done inside the block stays inside of it.
+    fn check_narrowed_block(&mut self, block: &Block, refinements: Refinements) {
+        self.scopes.push(HashMap::new());
+        self.refinements.push(refinements);
+        self.check_nodes(block);
+        self.refinements.pop();
+        self.scopes.pop();
+    }
+
+    fn check_nodes(&mut self, block: &Block) {
+        for node in block {
+            self.check_node(node);
+        }
+    }
+
+    // ---- statements ----
+
+    fn check_node(&mut self, node: &AstNode) {
+        match &node.kind {
+            AstNodes::Class(class) => self.check_class(class, &node.span),
+            AstNodes::LocalAssign { names, values } => {
+                let found = self.infer_list(values);
+                for (ix, name) in names.iter().enumerate() {
+                    let value_ty = value_at(&found, ix).unwrap_or(Ty::Nil);
+                    let value = values.get(ix).or(values.last());
+                    let (ty, open) = match &name.typ {
+                        Some(typ) => {
+                            let expected = self.evaluator.evaluate(typ);
+                            if let Some(value) = value {
+                                self.check_value(value, &value_ty, &expected);
+                            }
+                            (expected, false)
+                        }
+                        None => {
+                            let open = matches!(values.get(ix).map(|value| &value.kind), Some(Expressions::Table(_)));
+                            let ty = if name.attrib == Some(Attrib::Const) { value_ty } else { value_ty.widen() };
+                            (if ty == Ty::Nil { Ty::Any } else { ty }, open)
+                        }
+                    };
+                    self.declare(&name.name, ty, open);
+                    if name.attrib == Some(Attrib::Close) && !self.target.has_attributes() {
+                        self.unsupported("Attribute '<close>'", &name.span);
+                    }
+                    if name.attrib.is_some() {
+                        if let Some(var) = self.lookup_mut(&name.name) {
+                            var.constant = true;
+                        }
+                    }
+                }
+            }
+            AstNodes::Assign { targets, values } => {
+                let found = self.infer_list(values);
+                for (ix, target) in targets.iter().enumerate() {
+                    let value_ty = value_at(&found, ix).unwrap_or(Ty::Nil);
+                    let value = values.get(ix).or(values.last()).unwrap_or(target);
+                    self.check_assign(target, value, value_ty);
+                }
+            }
+            AstNodes::Call(expr) => {
+                self.infer_multi(expr);
+            }
+            AstNodes::Return(values) => self.check_return(values, &node.span),
+            AstNodes::Do(body) => self.check_block(body),
+            AstNodes::While { condition, body } => {
+                self.infer(condition);
+                let refinements = self.narrow(condition, true);
+                self.continue_labels.push(continue_label(body).map(str::to_string));
+                self.check_narrowed_block(body, refinements);
+                self.continue_labels.pop();
+            }
+            AstNodes::Repeat { body, condition } => {
+                // the condition can see the locals of the body
+                self.scopes.push(HashMap::new());
+                self.refinements.push(Refinements::new());
+                self.check_nodes(body);
+                self.infer(condition);
+                self.refinements.pop();
+                self.scopes.pop();
+            }
+            AstNodes::If { condition, body, else_ifs, else_body } => {
+                let branches = iter::once((condition, body)).chain(else_ifs.iter().map(|(condition, body)| (condition, body)));
+                let conditions: Vec<&Expression> = branches.clone().map(|(condition, _)| condition).collect();
+                let then_refinements = self.check_branches(branches, else_body.as_ref());
+                // code after the statement is only reached through the branches that do not return
+                let exits = |body: &Block| terminates(body);
+                let all_exit = iter::once(body).chain(else_ifs.iter().map(|(_, body)| body)).all(exits);
+                if all_exit && !else_body.as_ref().is_some_and(exits) {
+                    for condition in conditions {
+                        let refinements = self.narrow(condition, false);
+                        self.refine(refinements);
+                    }
+                } else if else_ifs.is_empty() && else_body.as_ref().is_some_and(exits) && !exits(body) {
+                    self.refine(then_refinements);
+                }
+            }
+            AstNodes::Switch { subject, cases, default } => {
+                let subject_ty = self.infer(subject);
+                let mut conditions = Vec::with_capacity(cases.len());
+                for (values, _) in cases {
+                    for value in values {
+                        let found = self.infer(value);
+                        let comparable = self.evaluator.is_assignable(&found, &subject_ty) || self.evaluator.is_assignable(&subject_ty, &found);
+                        if !comparable {
+                            self.error(TypeErrors::TypeMismatch(found.to_string(), subject_ty.to_string()), &value.span);
+                        }
+                    }
+                    conditions.push(case_condition(subject, values));
+                }
+                let branches = conditions.iter().zip(cases.iter().map(|(_, body)| body));
+                self.check_branches(branches, default.as_ref());
+            }
+            AstNodes::NumericFor { var, start, limit, step, body } => {
+                for expr in [Some(start), Some(limit), step.as_ref()].into_iter().flatten() {
+                    let found = self.infer(expr);
+                    self.check_value(expr, &found, &Ty::Number);
+                }
+                self.scopes.push(HashMap::new());
+                self.declare(&var.name, Ty::Number, false);
+                self.continue_labels.push(continue_label(body).map(str::to_string));
+                self.check_nodes(body);
+                self.continue_labels.pop();
+                self.scopes.pop();
+            }
+            AstNodes::GenericFor { names, exprs, body } => {
+                let types = self.iteration_types(exprs);
+                self.scopes.push(HashMap::new());
+                for (ix, name) in names.iter().enumerate() {
+                    let ty = match &name.typ {
+                        Some(typ) => self.evaluator.evaluate(typ),
+                        None => types.get(ix).cloned().unwrap_or(Ty::Any),
+                    };
+                    self.declare(&name.name, ty, false);
+                }
+                self.continue_labels.push(continue_label(body).map(str::to_string));
+                self.check_nodes(body);
+                self.continue_labels.pop();
+                self.scopes.pop();
+            }
+            AstNodes::Function(func) => {
+                let signature = self.signature(func);
+                let self_ty = func.is_method.then(|| self.path_type(&func.path));
+                match func.path.as_slice() {
+                    [] => match self.lookup_mut(&func.name) {
+                        Some(var) => var.ty = Ty::Function(Box::new(signature.clone())),
+                        None => {
+                            if self.evaluator.strict {
+                                self.error(TypeErrors::UndeclaredGlobal(func.name.clone()), &func.span);
+                            }
+                            let var = Variable { ty: Ty::Function(Box::new(signature.clone())), open: false, constant: false };
+                            self.globals.insert(func.name.clone(), var);
+                        }
+                    },
+                    [table] => self.add_field(table, &func.name, Ty::Function(Box::new(signature.clone()))),
+                    _ => {}
+                }
+                self.check_function(func, &signature, self_ty);
+            }
+            AstNodes::LocalFunction(func) => {
+                let signature = self.signature(func);
+                self.declare(&func.name, Ty::Function(Box::new(signature.clone())), false);
+                self.check_function(func, &signature, None);
+            }
+            AstNodes::Goto(label) | AstNodes::Label(label) => {
+                // Luau continues loops, which is the one jump it can express
+                let continues = self.target == Target::Luau && matches!(self.continue_labels.last(), Some(Some(end)) if end == label);
+                if !self.target.has_goto() && !continues {
+                    self.unsupported("'goto'", &node.span);
+                }
+            }
+            AstNodes::Import(import) => self.check_import(import, &node.span),
+            AstNodes::Export(declaration) => self.check_export(declaration, &node.span),
+            AstNodes::ExportDefault(value) => self.check_export_default(value, &node.span),
+            AstNodes::TypeAlias { name, .. } => self.evaluator.check_alias(name),
+            AstNodes::Break | AstNodes::Interface(_) | AstNodes::Error => {}
+        }
+    }
+
+    /// Checks the branches of an `if` chain or a `switch`, each narrowed by its condition and by the previous
+    /// conditions being false. Returns the narrowing of the first branch.
+    fn check_branches<'a>(&mut self, branches: impl Iterator<Item = (&'a Expression, &'a Block)>, otherwise: Option<&Block>) -> Refinements {
+        let mut first = None;
+        let mut depth = 0;
+        for (condition, body) in branches {
+            self.infer(condition);
+            let (truthy, falsy) = (self.narrow(condition, true), self.narrow(condition, false));
+            first.get_or_insert_with(|| truthy.clone());
+            self.check_narrowed_block(body, truthy);
+            self.refinements.push(falsy);
+            depth += 1;
+        }
+        if let Some(body) = otherwise {
+            self.check_block(body);
+        }
+        self.refinements.truncate(self.refinements.len() - depth);
+        first.unwrap_or_default()
+    }
+
+    fn check_assign(&mut self, target: &Expression, value: &Expression, value_ty: Ty) {
+        if let Some(key) = reference(target) {
+            self.invalidate(&key);
+        }
+        match &target.kind {
+            Expressions::Ident(name) => match self.lookup(name) {
+                Some(var) => {
+                    let expected = var.ty.clone();
+                    if var.constant {
+                        self.error(TypeErrors::ConstantAssignment(name.clone()), &target.span);
+                        return;
+                    }
+                    self.check_value(value, &value_ty, &expected);
+                    // the assigned value narrows a union until the next assignment
+                    if let Ty::Union(members) = &expected {
+                        let assigned: Vec<Ty> = members.iter().filter(|member| self.evaluator.is_assignable(&value_ty, member)).cloned().collect();
+                        if !assigned.is_empty() {
+                            self.refine(Refinements::from([(name.clone(), Ty::union(assigned))]));
+                        }
+                    }
+                }
+                None => {
+                    if self.evaluator.strict {
+                        self.error(TypeErrors::UndeclaredGlobal(name.clone()), &target.span);
+                    }
+                    let ty = if value_ty == Ty::Nil { Ty::Any } else { value_ty.widen() };
+                    let open = matches!(value.kind, Expressions::Table(_));
+                    self.globals.insert(name.clone(), Variable { ty, open, constant: false });
+                }
+            },
+            Expressions::Field { target: object, name } => {
+                if let Expressions::Ident(table) = &object.kind {
+                    if self.is_open_without(table, name) {
+                        self.add_field(table, name, value_ty.widen());
+                        return;
+                    }
+                    if self.is_library_without(table, name) {
+                        return;
+                    }
+                }
+                let object_ty = self.infer(object);
+                self.check_visibility(object, &object_ty, name, &target.span);
+                let expected = self.member_type(&object_ty, name, &target.span);
+                self.check_value(value, &value_ty, &expected);
+            }
+            Expressions::Index { target: object, key } => {
+                let object_ty = self.infer(object);
+                let key_ty = self.infer(key);
+                let expected = self.index_type(&object_ty, &key_ty, key);
+                self.check_value(value, &value_ty, &expected);
+            }
+            _ => {
+                self.infer(target);
+            }
+        }
+    }
+
+    fn is_open_without(&self, table: &str, field: &str) -> bool {
+        match self.lookup(table) {
+            Some(Variable { ty: Ty::Table(members), open: true, .. }) => !members.fields.contains_key(field),
+            _ => false,
+        }
+    }
+
+    /// Whether `table` is a standard library table missing `field`, which only strict mode reports: the
+    /// library differs between Lua versions and hosts extend it.
+    fn is_library_without(&self, table: &str, field: &str) -> bool {
+        if self.evaluator.strict || !is_standard_global(table) || self.scopes.iter().any(|scope| scope.contains_key(table)) {
+            return false;
+        }
+        matches!(self.globals.get(table), Some(Variable { ty: Ty::Table(members), .. }) if !members.fields.contains_key(field))
+    }
+
+    /// Adds a field to a table inferred from a constructor, other variables are left as they are.
+    fn add_field(&mut self, table: &str, field: &str, ty: Ty) {
+        if let Some(Variable { ty: Ty::Table(members), open: true, .. }) = self.lookup_mut(table) {
+            members.fields.insert(field.to_string(), FieldTy { ty, optional: false });
+        }
+    }
+
+    /// The type of `a.b.c`, the table a method is declared on.
+    fn path_type(&mut self, path: &[String]) -> Ty {
+        let Some((first, rest)) = path.split_first() else {
+            return Ty::Any;
+        };
+        let mut ty = self.lookup(first).map_or(Ty::Any, |var| var.ty.clone());
+        for name in rest {
+            ty = match self.evaluator.expand(&ty) {
+                Ty::Table(table) => table.fields.get(name).map_or(Ty::Any, |field| field.ty.clone()),
+                _ => Ty::Any,
+            };
+        }
+        ty
+    }
+
+    fn check_return(&mut self, values: &[Expression], span: &Span) {
+        let found = self.infer_list(values);
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        frame.inferred.push(value_at(&found, 0).unwrap_or(Ty::Nil));
+        let Some(expected) = frame.returns.clone() else {
+            return;
+        };
+        let count = fixed_count(&found);
+        // a function returning `...T` can return any number of values
+        let open = matches!(expected.last(), Some(Ty::Variadic(_)));
+        if count > expected.len() && !open {
+            self.error(TypeErrors::WrongReturnCount(expected.len(), count), span);
+            return;
+        }
+        for ix in 0..expected.len().max(count) {
+            let Some(expected_ty) = value_at(&expected, ix) else {
+                break;
+            };
+            match (value_at(&found, ix), values.get(ix).or(values.last())) {
+                (Some(found), Some(value)) => self.check_value(value, &found, &expected_ty),
+                _ if open && ix + 1 >= expected.len() => {}
+                _ if self.evaluator.is_assignable(&Ty::Nil, &expected_ty) => {}
+                _ => {
+                    self.error(TypeErrors::WrongReturnCount(expected.len(), count), span);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn signature(&mut self, func: &FunctionDecl) -> FunctionTy {
+        match self.evaluator.function_type(func) {
+            Ty::Function(signature) => *signature,
+            _ => unreachable!("function_type always returns a function"),
+        }
+    }
+
+    /// Checks a function body with its parameters in scope, returning the checked frame.
+    fn check_function(&mut self, func: &FunctionDecl, signature: &FunctionTy, self_ty: Option<Ty>) -> Frame {
+        let returns = func.returns.as_ref().map(|_| signature.returns.clone());
+        self.frames.push(Frame { returns, inferred: Vec::new(), refinement_base: self.refinements.len() });
+        self.scopes.push(HashMap::new());
+        self.refinements.push(Refinements::new());
+        if let Some(self_ty) = self_ty {
+            self.declare("self", self_ty, false);
+        }
+        for (arg, param) in func.args.iter().zip(&signature.params) {
+            if self.evaluator.strict && arg.typ.is_none() {
+                self.error(TypeErrors::ImplicitAny(arg.name.clone()), &arg.span);
+            }
+            self.declare(&arg.name, param.ty.clone(), false);
+        }
+        self.continue_labels.push(None);
+        self.check_nodes(&func.body);
+        self.continue_labels.pop();
+        self.refinements.pop();
+        self.scopes.pop();
+        self.frames.pop().unwrap()
+    }
+
+    /// Declares the class object, checks `implements` clauses, field initializers and member bodies.
+    fn check_class(&mut self, class: &ClassNode, span: &Span) {
+        let instance = Ty::Named { name: class.name.clone(), args: Vec::new() };
+        let mut statics = TableTy::default();
+        let constructor = match &class.constructor {
+            Some(constructor) => self.signature(constructor),
+            None => FunctionTy { params: Vec::new(), varargs: class.extends.as_ref().map(|_| Ty::Any), returns: Vec::new() },
+        };
+        let new = FunctionTy { returns: vec![instance.clone()], ..constructor.clone() };
+        statics.fields.insert("new".to_string(), FieldTy { ty: Ty::Function(Box::new(new)), optional: false });
+        for method in &class.methods {
+            let mut signature = self.signature(&method.func);
+            if !method.is_static {
+                signature.params.insert(0, ParamTy { name: Some("self".to_string()), ty: instance.clone(), optional: false });
+            }
+            statics.fields.insert(method.func.name.clone(), FieldTy { ty: Ty::Function(Box::new(signature)), optional: false });
+        }
+        for field in class.fields.iter().filter(|field| field.is_static) {
+            let ty = match (&field.field.typ, &field.value) {
+                (Some(typ), _) => self.evaluator.evaluate(typ),
+                (None, Some(value)) => self.evaluator.literal_type(value).widen(),
+                (None, None) => Ty::Any,
+            };
+            statics.fields.insert(field.field.name.clone(), FieldTy { ty, optional: false });
+        }
+        self.declare(&class.name, Ty::Table(Box::new(statics)), true);
+
+        self.check_extends(class, span);
+        self.check_implements(class, &instance, span);
+        self.classes.push(class.name.clone());
+        // members of a derived class call the base class through `super`
+        self.scopes.push(HashMap::new());
+        if let Some(base) = class.extends.as_ref().and_then(|base| self.lookup(base)).map(|base| base.ty.clone()) {
+            let constructor = match self.evaluator.expand(&base) {
+                Ty::Table(statics) => match statics.fields.get("new").map(|new| &new.ty) {
+                    Some(Ty::Function(new)) => FunctionTy { returns: Vec::new(), ..*new.clone() },
+                    _ => FunctionTy { params: Vec::new(), varargs: Some(Ty::Any), returns: Vec::new() },
+                },
+                _ => FunctionTy { params: Vec::new(), varargs: Some(Ty::Any), returns: Vec::new() },
+            };
+            self.declare("super", Ty::Intersection(vec![base, Ty::Function(Box::new(constructor))]), false);
+        }
+        for field in &class.fields {
+            if let Some(value) = &field.value {
+                let found = self.infer(value);
+                if let Some(typ) = &field.field.typ {
+                    let expected = self.evaluator.evaluate(typ);
+                    self.check_value(value, &found, &expected);
+                }
+            }
+        }
+        if let Some(func) = &class.constructor {
+            self.check_function(func, &constructor, Some(instance.clone()));
+        }
+        for method in &class.methods {
+            let signature = self.signature(&method.func);
+            let self_ty = (!method.is_static).then(|| instance.clone());
+            self.check_function(&method.func, &signature, self_ty);
+        }
+        self.scopes.pop();
+        self.classes.pop();
+    }
+
+    /// Checks that the base of a class is a class declared before it, and that the class is not among its
+    /// own bases, which would leave the class without a metatable or recurse forever on construction.
+    fn check_extends(&mut self, class: &ClassNode, span: &Span) {
+        let Some(base) = &class.extends else {
+            return;
+        };
+        let chain = self.evaluator.inheritance(&class.name);
+        let last = chain.last().and_then(|last| self.evaluator.classes[last].0.extends.as_ref());
+        if last == Some(&class.name) {
+            self.error(TypeErrors::CircularInheritance(class.name.clone()), span);
+        } else if self.lookup(base).is_none() {
+            let kind = if self.evaluator.classes.contains_key(base) {
+                TypeErrors::BaseClassUsedBeforeDeclaration(base.clone())
+            } else {
+                TypeErrors::UnknownBaseClass(base.clone())
+            };
+            self.error(kind, span);
+        }
+    }
+
+    /// Checks that a class provides the members of every interface it implements.
+    fn check_implements(&mut self, class: &ClassNode, instance: &Ty, span: &Span) {
+        for interface in &class.implements {
+            if !self.evaluator.is_interface(interface) {
+                self.error(TypeErrors::NotAnInterface(interface.clone()), span);
+                continue;
+            }
+            let required = Ty::Named { name: interface.clone(), args: Vec::new() };
+            for problem in self.evaluator.conformance(instance, &required) {
+                match problem {
+                    Nonconformance::Missing(member) => {
+                        self.error(TypeErrors::MissingMember(class.name.clone(), member, interface.clone()), span);
+                    }
+                    Nonconformance::Incompatible { member, found, expected } => {
+                        let field_span = class.fields.iter().map(|field| (&field.field.name, &field.field.span))
+                            .chain(class.methods.iter().map(|method| (&method.func.name, &method.func.span)))
+                            .find(|(name, _)| **name == member)
+                            .map_or(span, |(_, span)| span);
+                        let kind = TypeErrors::IncompatibleMember(member, found.to_string(), expected.to_string(), interface.clone());
+                        self.error(kind, &field_span.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Loop variable types of `for k, v in pairs(t)` and `ipairs(t)`, other iterators are `any`.
+    fn iteration_types(&mut self, exprs: &[Expression]) -> Vec<Ty> {
+        if let [Expression { kind: Expressions::Call { target, args }, .. }] = exprs {
+            if let (Expressions::Ident(iterator), [arg]) = (&target.kind, args.as_slice()) {
+                if iterator == "pairs" || iterator == "ipairs" {
+                    let iterated = self.infer(arg);
+                    return match self.evaluator.expand(&iterated) {
+                        Ty::Array(element) => vec![Ty::Number, *element],
+                        Ty::Table(table) if iterator == "pairs" => match table.indexers.first() {
+                            Some((key, value)) => vec![key.clone(), value.clone()],
+                            None if !table.fields.is_empty() => {
+                                let values = table.fields.values().map(|field| field.ty.clone()).collect();
+                                vec![Ty::String, Ty::union(values)]
+                            }
+                            None => Vec::new(),
+                        },
+                        _ => Vec::new(),
+                    };
+                }
+            }
+        }
+        self.infer_list(exprs);
+        Vec::new()
+    }
+
+    // ---- values ----
+
+    /// Checks that a value can be used where `expected` is. Table constructors are compared member by member
+    /// so that each offending member is reported at the value that provides it.
+    fn check_value(&mut self, expr: &Expression, found: &Ty, expected: &Ty) {
+        if let Expressions::Table(fields) = &expr.kind {
+            if matches!(self.evaluator.expand(expected), Ty::Table(_)) {
+                self.check_table_members(expr, fields, found, expected);
+                return;
+            }
+        }
+        if !self.evaluator.is_assignable(found, expected) {
+            self.error(TypeErrors::TypeMismatch(found.to_string(), expected.to_string()), &expr.span);
+        }
+    }
+
+    fn check_table_members(&mut self, expr: &Expression, fields: &[TableField], found: &Ty, expected: &Ty) {
+        let required = expected.to_string();
+        let problems = self.evaluator.conformance(found, expected);
+        if problems.is_empty() {
+            if !self.evaluator.is_assignable(found, expected) {
+                self.error(TypeErrors::TypeMismatch(found.to_string(), required), &expr.span);
+            }
+            return;
+        }
+        for problem in problems {
+            match problem {
+                Nonconformance::Missing(member) => {
+                    self.error(TypeErrors::MissingMember(found.to_string(), member, required.clone()), &expr.span);
+                }
+                Nonconformance::Incompatible { member, found, expected } => {
+                    let value = fields.iter().find_map(|field| match field {
+                        TableField::Named(name, value) if *name == member => Some(value),
+                        _ => None,
+                    });
+                    match value {
+                        Some(value) if matches!(value.kind, Expressions::Table(_)) => self.check_value(value, &found, &expected),
+                        _ => {
+                            let span = value.map_or(&expr.span, |value| &value.span).clone();
+                            self.error(TypeErrors::IncompatibleMember(member, found.to_string(), expected.to_string(), required.clone()), &span);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // ---- expressions ----
+
+    /// Types of an expression list, the last expression contributing all of its values.
+    fn infer_list(&mut self, exprs: &[Expression]) -> Vec<Ty> {
+        let mut types = Vec::with_capacity(exprs.len());
+        for (ix, expr) in exprs.iter().enumerate() {
+            if ix + 1 == exprs.len() {
+                types.extend(self.infer_multi(expr));
+            } else {
+                types.push(self.infer(expr));
+            }
+        }
+        types
+    }
+
+    /// The type of the first value of an expression.
+    fn infer(&mut self, expr: &Expression) -> Ty {
+        value_at(&self.infer_multi(expr), 0).unwrap_or(Ty::Nil)
+    }
+
+    /// Every value an expression evaluates to: calls can return several or none.
+    fn infer_multi(&mut self, expr: &Expression) -> Vec<Ty> {
+        let ty = match &expr.kind {
+            Expressions::Nil => Ty::Nil,
+            Expressions::True => Ty::Literal(LiteralType::Boolean(true)),
+            Expressions::False => Ty::Literal(LiteralType::Boolean(false)),
+            Expressions::Number(n) | Expressions::Float(n) => Ty::Literal(LiteralType::Number(*n)),
+            Expressions::String(s) => Ty::string_literal(s),
+            Expressions::Dots => Ty::Variadic(Box::new(Ty::Any)),
+            Expressions::Error => Ty::Any,
+            Expressions::Ident(name) => match (self.refined(name), self.lookup(name)) {
+                (Some(ty), _) => ty,
+                (None, Some(var)) => var.ty.clone(),
+                (None, None) => {
+                    if self.evaluator.strict {
+                        self.error(TypeErrors::UnknownName(name.clone()), &expr.span);
+                    }
+                    Ty::Any
+                }
+            },
+            Expressions::Paren(inner) => self.infer(inner),
+            Expressions::Function(func) => {
+                let mut signature = self.signature(func);
+                let frame = self.check_function(func, &signature, None);
+                if func.is_arrow && func.returns.is_none() {
+                    signature.returns = frame.inferred.iter().map(Ty::widen).collect();
+                }
+                Ty::Function(Box::new(signature))
+            }
+            Expressions::Field { target, name } => {
+                let object = self.infer(target);
+                self.check_visibility(target, &object, name, &expr.span);
+                if let Some(ty) = reference(expr).and_then(|key| self.refined(&key)) {
+                    return vec![ty];
+                }
+                match &target.kind {
+                    Expressions::Ident(table) if self.is_open_without(table, name) || self.is_library_without(table, name) => Ty::Any,
+                    _ => self.member_type(&object, name, &expr.span),
+                }
+            }
+            Expressions::Index { target, key } => {
+                let object = self.infer(target);
+                let key_ty = self.infer(key);
+                self.index_type(&object, &key_ty, key)
+            }
+            Expressions::Call { target, args } => {
+                let callee = self.infer(target);
+                let arg_types = self.infer_list(args);
+                return self.check_call(&callee, args, arg_types, &expr.span, false);
+            }
+            Expressions::MethodCall { target, method, args } => {
+                let object = self.infer(target);
+                let arg_types = self.infer_list(args);
+                self.check_visibility(target, &object, method, &expr.span);
+                let is_string = matches!(object, Ty::String | Ty::Literal(LiteralType::String(_)));
+                let callee = self.member_type(&object, method, &expr.span);
+                return self.check_call(&callee, args, arg_types, &expr.span, is_string);
+            }
+            Expressions::Binary { op, lhs, rhs } => self.infer_binary(*op, lhs, rhs, &expr.span),
+            Expressions::Unary { op, expr: operand } => {
+                if *op == UnaryOps::BitNot && !self.target.has_bitwise() {
+                    self.unsupported(&format!("Operator '{}'", op), &expr.span);
+                }
+                let found = self.infer(operand);
+                let valid = match op {
+                    UnaryOps::Not => true,
+                    UnaryOps::Neg | UnaryOps::BitNot => self.evaluator.is_assignable(&found, &Ty::Number),
+                    UnaryOps::Len => matches!(self.evaluator.expand(&found), Ty::Any | Ty::Unknown | Ty::String | Ty::Literal(LiteralType::String(_)) | Ty::Array(_) | Ty::Tuple(_) | Ty::Table(_)),
+                };
+                if !valid {
+                    self.error(TypeErrors::InvalidOperand(op.to_string(), found.to_string()), &expr.span);
+                }
+                if *op == UnaryOps::Not { Ty::Boolean } else { Ty::Number }
+            }
+            Expressions::Table(fields) => self.infer_table(fields),
+        };
+        vec![ty]
+    }
+
+    fn infer_table(&mut self, fields: &[TableField]) -> Ty {
+        let mut table = TableTy::default();
+        let mut elements = Vec::new();
+        for field in fields {
+            match field {
+                TableField::Named(name, value) => {
+                    let ty = self.infer(value);
+                    table.fields.insert(name.clone(), FieldTy { ty, optional: false });
+                }
+                TableField::Positional(value) => elements.push(self.infer(value)),
+                TableField::Keyed(key, value) => {
+                    let key_ty = self.infer(key);
+                    let value_ty = self.infer(value);
+                    match key_ty {
+                        Ty::Literal(LiteralType::String(name)) => {
+                            table.fields.insert(name, FieldTy { ty: value_ty, optional: false });
+                        }
+                        key_ty => table.indexers.push((key_ty.widen(), value_ty.widen())),
+                    }
+                }
+            }
+        }
+        if !elements.is_empty() {
+            let element = Ty::union(elements).widen();
+            if table.fields.is_empty() && table.indexers.is_empty() {
+                return Ty::Array(Box::new(element));
+            }
+            table.indexers.push((Ty::Number, element));
+        }
+        Ty::Table(Box::new(table))
+    }
+
+    fn infer_binary(&mut self, op: BinaryOps, lhs: &Expression, rhs: &Expression, span: &Span) -> Ty {
+        if bit_function(op).is_some() && !self.target.has_bitwise() {
+            self.unsupported(&format!("Operator '{}'", op), span);
+        }
+        let left = self.infer(lhs);
+        // the right side of `and` only runs when the left is truthy, the one of `or` when it is falsy
+        let right = match op {
+            BinaryOps::And | BinaryOps::Or => {
+                let refinements = self.narrow(lhs, op == BinaryOps::And);
+                self.refinements.push(refinements);
+                let right = self.infer(rhs);
+                self.refinements.pop();
+                right
+            }
+            _ => self.infer(rhs),
+        };
+        match op {
+            BinaryOps::And => Ty::union(vec![left.falsy(), right]),
+            BinaryOps::Or => Ty::union(vec![left.truthy(), right]),
+            BinaryOps::EQ | BinaryOps::NEQ => Ty::Boolean,
+            BinaryOps::LT | BinaryOps::GT | BinaryOps::LTE | BinaryOps::GTE => {
+                let numbers = self.evaluator.is_assignable(&left, &Ty::Number) && self.evaluator.is_assignable(&right, &Ty::Number);
+                let strings = self.evaluator.is_assignable(&left, &Ty::String) && self.evaluator.is_assignable(&right, &Ty::String);
+                if !numbers && !strings {
+                    self.error(TypeErrors::InvalidOperands(op.to_string(), left.to_string(), right.to_string()), span);
+                }
+                Ty::Boolean
+            }
+            BinaryOps::Concat => {
+                let concatenable = Ty::union(vec![Ty::String, Ty::Number]);
+                for (operand, ty) in [(lhs, &left), (rhs, &right)] {
+                    if !self.evaluator.is_assignable(ty, &concatenable) {
+                        self.error(TypeErrors::InvalidOperand(op.to_string(), ty.to_string()), &operand.span);
+                    }
+                }
+                Ty::String
+            }
+            _ => {
+                for (operand, ty) in [(lhs, &left), (rhs, &right)] {
+                    if !self.evaluator.is_assignable(ty, &Ty::Number) {
+                        self.error(TypeErrors::InvalidOperand(op.to_string(), ty.to_string()), &operand.span);
+                    }
+                }
+                Ty::Number
+            }
+        }
+    }
+
+    /// Checks the arguments of a call against the callee's signature and returns its results.
+    /// `drop_self` skips the first parameter, for `s:method()` calls resolved to the `string` library.
+    fn check_call(&mut self, callee: &Ty, args: &[Expression], arg_types: Vec<Ty>, span: &Span, drop_self: bool) -> Vec<Ty> {
+        let callee = match self.evaluator.expand(callee) {
+            Ty::Union(members) => Ty::union(self.without_nil(members, span)),
+            other => other,
+        };
+        // an intersection with a function is called through it, as `super` is
+        let callee = match self.evaluator.expand(&callee) {
+            Ty::Intersection(members) => members.iter().find(|member| matches!(member, Ty::Function(_))).cloned().unwrap_or(Ty::Intersection(members)),
+            other => other,
+        };
+        let func = match callee {
+            Ty::Function(func) => func,
+            Ty::Any | Ty::Unknown => return vec![Ty::Any],
+            Ty::Never => return vec![Ty::Never],
+            other => {
+                self.error(TypeErrors::NotCallable(other.to_string()), span);
+                return vec![Ty::Any];
+            }
+        };
+        let params = if drop_self { func.params.get(1..).unwrap_or_default() } else { &func.params[..] };
+        let required = params.iter().rposition(|param| !param.optional && !param.ty.is_optional()).map_or(0, |ix| ix + 1);
+        // the values of a trailing `...` or call returning `...T` are not known in number
+        let count = fixed_count(&arg_types);
+        let open = count < arg_types.len();
+        if (count < required && !open) || (count > params.len() && func.varargs.is_none()) {
+            let expected = if count < required { required } else { params.len() };
+            self.error(TypeErrors::WrongArgumentCount(expected, count), span);
+        }
+        let checked = if open { count.max(params.len()) } else { count };
+        for ix in 0..checked {
+            let (Some(found), Some(expected)) = (value_at(&arg_types, ix), params.get(ix).map(|param| param.ty.clone()).or_else(|| func.varargs.clone())) else {
+                continue;
+            };
+            if let Some(arg) = args.get(ix).or(args.last()) {
+                self.check_value(arg, &found, &expected);
+            }
+        }
+        func.returns.clone()
+    }
+
+    /// The members of a union that are not `nil`. Strict mode reports using a value that is possibly `nil`.
+    fn without_nil(&mut self, members: Vec<Ty>, span: &Span) -> Vec<Ty> {
+        if self.evaluator.strict && members.contains(&Ty::Nil) {
+            self.error(TypeErrors::PossiblyNil(Ty::Union(members.clone()).to_string()), span);
+        }
+        members.into_iter().filter(|member| *member != Ty::Nil).collect()
+    }
+
+    /// The type of `object.name`, reporting properties that do not exist on a known type.
+    fn member_type(&mut self, object: &Ty, name: &str, span: &Span) -> Ty {
+        match self.evaluator.expand(object) {
+            Ty::Any | Ty::Unknown => Ty::Any,
+            Ty::Never => Ty::Never,
+            Ty::Union(members) => {
+                let present = self.without_nil(members, span);
+                let types = present.iter().map(|member| self.member_type(member, name, span)).collect();
+                Ty::union(types)
+            }
+            Ty::Table(table) => {
+                if let Some(field) = table.fields.get(name) {
+                    return if field.optional { Ty::optional(field.ty.clone()) } else { field.ty.clone() };
+                }
+                let key = Ty::string_literal(name);
+                let indexer = table.indexers.iter().find(|(indexer_key, _)| self.evaluator.is_assignable(&key, indexer_key));
+                match indexer {
+                    Some((_, value)) => value.clone(),
+                    None => {
+                        self.error(TypeErrors::UnknownProperty(name.to_string(), object.to_string()), span);
+                        Ty::Any
+                    }
+                }
+            }
+            Ty::String | Ty::Literal(LiteralType::String(_)) => {
+                let library = self.globals.get("string").map_or(Ty::Any, |var| var.ty.clone());
+                self.member_type(&library, name, span)
+            }
+            Ty::Intersection(_) => Ty::Any,
+            other => {
+                self.error(TypeErrors::UnknownProperty(name.to_string(), other.to_string()), span);
+                Ty::Any
+            }
+        }
+    }
+
+    /// The type of `object[key]`.
+    fn index_type(&mut self, object: &Ty, key: &Ty, key_expr: &Expression) -> Ty {
+        if let Ty::Literal(LiteralType::String(name)) = key {
+            if !matches!(self.evaluator.expand(object), Ty::Array(_)) {
+                return self.member_type(object, name, &key_expr.span);
+            }
+        }
+        match self.evaluator.expand(object) {
+            Ty::Array(element) => {
+                if !self.evaluator.is_assignable(key, &Ty::Number) {
+                    self.error(TypeErrors::TypeMismatch(key.to_string(), Ty::Number.to_string()), &key_expr.span);
+                }
+                *element
+            }
+            Ty::Table(table) => table.indexers.iter()
+                .find(|(indexer_key, _)| self.evaluator.is_assignable(key, indexer_key))
+                .map_or(Ty::Any, |(_, value)| value.clone()),
+            Ty::Union(members) => {
+                let present = self.without_nil(members, &key_expr.span);
+                let types = present.iter().map(|member| self.index_type(member, key, key_expr)).collect();
+                Ty::union(types)
+            }
+            _ => Ty::Any,
+        }
+    }
+}
+
+
+/// The type of the value at `ix` of a list of values, a trailing `...T` standing for any number of `T`.
+fn value_at(values: &[Ty], ix: usize) -> Option<Ty> {
+    match (values.get(ix), values.last()) {
+        (Some(Ty::Variadic(inner)), _) | (None, Some(Ty::Variadic(inner))) => Some((**inner).clone()),
+        (value, _) => value.cloned(),
+    }
+}
+
+/// The number of values a list of values has at least.
+fn fixed_count(values: &[Ty]) -> usize {
+    values.len() - usize::from(matches!(values.last(), Some(Ty::Variadic(_))))
+}
+
+/// `subject == a or subject == b`, the condition a `case a, b` branch is taken on.
+fn case_condition(subject: &Expression, values: &[Expression]) -> Expression {
+    let equals = |value: &Expression| Expression {
+        kind: Expressions::Binary { op: BinaryOps::EQ, lhs: Box::new(subject.clone()), rhs: Box::new(value.clone()) },
+        span: value.span.clone(),
+    };
+    let mut values = values.iter();
+    let first = equals(values.next().expect("a case has at least one value"));
+    values.fold(first, |condition, value| {
+        let span = Span { start: condition.span.start, end: value.span.end };
+        Expression { kind: Expressions::Binary { op: BinaryOps::Or, lhs: Box::new(condition), rhs: Box::new(equals(value)) }, span }
+    })
+}
+
+#[test]
+fn check_statements() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        local M = {}
+        function M.add(a: number, b: number): number
+            return a + b
+        end
+        local count: number = M.add(1, 2)
+        local name: string = M.add(1, 2)
+        M.add(1)
+        local greeting = "hi " .. name
+        local bad = greeting + 1
+        local list: number[] = { 1, 2, 3 }
+        for i, v in ipairs(list) do
+            local doubled: number = v * 2
+        end
+        local point: { x: number, y: number } = { x = 1, y = 2 }
+        print(point.z)
+        local double = (n: number) => n * 2
+        local s: string = double(2)
+        local function pair(): (number, string)
+            return 1
+        end
+        count = true
+        local up = name:upper()
+        local n = 5
+        n()
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    let mut checker = Checker::new(false);
+    checker.check(&block);
+    let messages: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(messages, [
+        "Type 'number' is not assignable to type 'string'.",
+        "Expected 2 argument(s), but received 1.",
+        "Operator '+' cannot be applied to type 'string'.",
+        "Property 'z' does not exist on type '{ x: number, y: number }'.",
+        "Type 'number' is not assignable to type 'string'.",
+        "Type 'true' is not assignable to type 'number'.",
+        "Type 'number' has no call signatures.",
+    ]);
+}
+
+
+#[test]
+fn check_strict_nil_safety() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        local name: string = nil
+        local maybe: { value: number }? = nil
+        local value = maybe.value
+        counter = 1
+        local function log(message)
+            print(mesage)
+        end
+        if maybe ~= nil then
+            local ok: number = maybe.value
+        end
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    let messages = |strict: bool| {
+        let mut checker = Checker::new(strict);
+        checker.check(&block);
+        checker.errors.iter().map(|error| error.kind.to_string()).collect::<Vec<String>>()
+    };
+    assert_eq!(messages(true), [
+        "Type 'nil' is not assignable to type 'string'.",
+        "Object of type '{ value: number } | nil' is possibly 'nil'.",
+        "Cannot assign to undeclared global 'counter', declare it with 'local'.",
+        "Parameter 'message' implicitly has an 'any' type.",
+        "Cannot find name 'mesage'.",
+    ]);
+    assert!(messages(false).is_empty());
+}
+
+#[test]
+fn check_target_features() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        local limit <const> = 10
+        local flags <close> = nil
+        limit = 11
+        local masked = 6 & 3
+        local halved = 7 // 2
+        goto done
+        ::done::
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    let messages = |target: Target| {
+        let mut checker = Checker::new(false).target(target);
+        checker.check(&block);
+        checker.errors.iter().map(|error| error.kind.to_string()).collect::<Vec<String>>()
+    };
+    assert_eq!(messages(Target::Lua51), [
+        "Attribute '<close>' is not available when targeting Lua 5.1.",
+        "Cannot assign to 'limit' because it is a constant.",
+        "Operator '&' is not available when targeting Lua 5.1.",
+        "'goto' is not available when targeting Lua 5.1.",
+        "'goto' is not available when targeting Lua 5.1.",
+    ]);
+    assert_eq!(messages(Target::LuaJIT), [
+        "Attribute '<close>' is not available when targeting LuaJIT.",
+        "Cannot assign to 'limit' because it is a constant.",
+    ]);
+    assert_eq!(messages(Target::Lua54), ["Cannot assign to 'limit' because it is a constant."]);
+    let src = "for i = 1, 3 do if i == 2 then goto continue end goto skip ::skip:: ::continue:: end";
+    let mut checker = Checker::new(false).target(Target::Luau);
+    checker.check(&parse(Lexer::new(src).collect()).unwrap());
+    let messages: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(messages, ["'goto' is not available when targeting Luau.", "'goto' is not available when targeting Luau."]);
+}
+
+#[test]
+fn check_standard_library() {
+    use crate::parser::{parse, Lexer};
+    let src = r##"
+        io.stderr:write("failed\n")
+        local kind = math.type(1)
+        local moved = table.move({ 1, 2 }, 1, 2, 1)
+        local first, second = unpack({ 1, 2 })
+        local ok, result, extra = pcall(print, "hi")
+        local function forward(...)
+            return select("#", ...), string.format("%s %s", ...)
+        end
+        local mask = bit32.band(6, 3)
+        local length = utf8.len("abc")
+        local found = package.searchpath("mod", package.path)
+        local clock = os.uptime()
+        string.trim = function(s: string) return s end
+    "##;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    let messages = |strict: bool| {
+        let mut checker = Checker::new(strict);
+        checker.check(&block);
+        checker.errors.iter().map(|error| error.kind.to_string()).collect::<Vec<String>>()
+    };
+    assert!(messages(false).is_empty());
+    let strict = messages(true);
+    assert_eq!(strict.len(), 2);
+    assert!(strict[0].starts_with("Property 'uptime' does not exist") && strict[1].starts_with("Property 'trim' does not exist"));
+}
+
+#[test]
+fn check_class_bases() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        class Animal end
+        class Dog extends Animal end
+        class Ghost extends Spirit end
+        class Early extends Late end
+        class Late end
+        class Loop extends Loop end
+        class A extends B end
+        class B extends A end
+    "#;
+    for strict in [false, true] {
+        let mut checker = Checker::new(strict);
+        checker.check(&parse(Lexer::new(src).collect()).unwrap());
+        let errors: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+        assert_eq!(errors, [
+            "Cannot find class 'Spirit' to extend.",
+            "Class 'Late' is extended before its declaration.",
+            "Class 'Loop' extends itself, directly or through its bases.",
+            "Class 'A' extends itself, directly or through its bases.",
+            "Class 'B' extends itself, directly or through its bases.",
+        ]);
+    }
+}
+
+#[test]
+fn check_super_calls() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        class Animal
+            name: string = ""
+            constructor(name: string)
+                self.name = name
+            end
+            speak(): string
+                return self.name
+            end
+        end
+        class Dog extends Animal
+            constructor(name: string)
+                super(name)
+            end
+            speak(): string
+                return super.speak(self) .. super:speak() .. "woof"
+            end
+            fail()
+                super(1)
+            end
+        end
+    "#;
+    let mut checker = Checker::new(true);
+    checker.check(&parse(Lexer::new(src).collect()).unwrap());
+    let errors: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(errors, ["Type '1' is not assignable to type 'string'."]);
+}
+
+#[test]
+fn check_circular_aliases() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        type Same = Same
+        type Ping = Pong
+        type Pong = Ping | nil
+        export type Exported = Exported?
+        type List = { value: number, next: List? }
+        type Tree = Tree[]
+        type Callback = (Callback) -> ()
+        local value: Same = 1
+    "#;
+    let mut checker = Checker::new(true);
+    checker.check(&parse(Lexer::new(src).collect()).unwrap());
+    let errors: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(errors, [
+        "Type alias 'Same' circularly references itself.",
+        "Type alias 'Ping' circularly references itself.",
+        "Type alias 'Pong' circularly references itself.",
+        "Type alias 'Exported' circularly references itself.",
+    ]);
+}
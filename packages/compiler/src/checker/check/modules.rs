@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+use crate::checker::check::Checker;
+use crate::checker::types::{FieldTy, TableTy, Ty};
+use crate::lower::module::{exported_values, is_relative, require_name};
+use crate::parser::ast::{AstNode, AstNodes, Block, Expression, ImportNode, Type, Types};
+use crate::parser::errors::TypeErrors;
+use crate::parser::tokens::Span;
+
+/// What a checked file exports, for the files importing it.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleExports {
+    /// Types of the exported values, `default` for the default export.
+    pub values: HashMap<String, Ty>,
+    /// Every type alias, interface and class declared at the top level, exported or not,
+    /// as exported types can refer to the private ones.
+    pub declarations: Block,
+    /// Names of the exported types.
+    pub types: HashSet<String>,
+}
+
+impl Checker {
+    /// Makes the exports of a checked module available to the imports naming it, `pkg/mod` and
+    /// `pkg.mod` alike.
+    pub fn add_module(&mut self, name: &str, exports: ModuleExports) {
+        self.modules.insert(require_name(name), exports);
+    }
+
+    /// Declares the types imported by a file, before its own type declarations.
+    /// An import renamed with `as` declares an alias of the exported type.
+    pub(super) fn declare_imported_types(&mut self, block: &Block) {
+        for node in block {
+            let AstNodes::Import(import) = &node.kind else {
+                continue;
+            };
+            let Some(module) = self.modules.get(&require_name(&import.module)) else {
+                continue;
+            };
+            if !import.names.iter().any(|name| module.types.contains(&name.name)) {
+                continue;
+            }
+            let declarations = module.declarations.clone();
+            self.evaluator.declare_types(&declarations);
+            for name in import.names.iter().filter(|name| name.local != name.name) {
+                let typ = Type { kind: Types::Named { name: name.name.clone(), args: Vec::new() }, span: name.span.clone() };
+                self.evaluator.declare_alias(&name.local, Vec::new(), typ);
+            }
+        }
+    }
+
+    /// Marks the imported names that only name a type of their module, they have no runtime binding.
+    pub fn mark_type_imports(&self, block: &mut Block) {
+        for node in block {
+            let AstNodes::Import(import) = &mut node.kind else {
+                continue;
+            };
+            let Some(module) = self.modules.get(&require_name(&import.module)) else {
+                continue;
+            };
+            for name in &mut import.names {
+                name.type_only = module.types.contains(&name.name) && !module.values.contains_key(&name.name);
+            }
+        }
+    }
+
+    /// Reports a top-level `return` in a file with exports, the exports table is what the file returns.
+    pub(super) fn check_module_return(&mut self, block: &Block) {
+        if !block.iter().any(|node| matches!(node.kind, AstNodes::Export(_) | AstNodes::ExportDefault(_))) {
+            return;
+        }
+        for node in block.iter().filter(|node| matches!(node.kind, AstNodes::Return(_))) {
+            self.error(TypeErrors::ReturnWithExports, &node.span);
+        }
+    }
+
+    fn at_top_level(&mut self, span: &Span) -> bool {
+        let top = self.frames.is_empty() && self.scopes.len() == 1;
+        if !top {
+            self.error(TypeErrors::NestedModuleDeclaration, span);
+        }
+        top
+    }
+
+    pub(super) fn check_import(&mut self, import: &ImportNode, span: &Span) {
+        if !self.at_top_level(span) {
+            return;
+        }
+        let module = self.modules.get(&require_name(&import.module)).cloned();
+        // other modules may be plain Lua ones provided by the host, a relative one is a file of the project
+        if module.is_none() && is_relative(&import.module) {
+            self.error(TypeErrors::UnresolvedModule(import.module.clone()), span);
+        }
+        for name in &import.names {
+            let value = match &module {
+                // a module that was not checked, such as a plain Lua one, is untyped
+                None => Some(Ty::Any),
+                Some(module) => {
+                    let value = module.values.get(&name.name).cloned();
+                    if value.is_none() && !module.types.contains(&name.name) {
+                        self.error(TypeErrors::NotExported(name.name.clone(), import.module.clone()), &name.span);
+                    }
+                    value
+                }
+            };
+            if let (Some(ty), false) = (value, import.type_only) {
+                self.declare(&name.local, ty, false);
+            }
+        }
+        if let Some(namespace) = &import.namespace {
+            let ty = match &module {
+                Some(module) => {
+                    let fields = module.values.iter().map(|(name, ty)| (name.clone(), FieldTy { ty: ty.clone(), optional: false })).collect();
+                    Ty::Table(Box::new(TableTy { fields, indexers: Vec::new() }))
+                }
+                None => Ty::Any,
+            };
+            self.declare(namespace, ty, false);
+        }
+    }
+
+    pub(super) fn check_export(&mut self, declaration: &AstNode, span: &Span) {
+        if !self.at_top_level(span) {
+            return;
+        }
+        self.check_node(declaration);
+        for name in exported_values(declaration) {
+            let ty = self.lookup(&name).map_or(Ty::Any, |var| var.ty.clone());
+            self.exports.values.insert(name, ty);
+        }
+        match &declaration.kind {
+            AstNodes::TypeAlias { name, .. } => {
+                self.exports.types.insert(name.clone());
+            }
+            AstNodes::Interface(interface) => {
+                self.exports.types.insert(interface.name.clone());
+            }
+            AstNodes::Class(class) => {
+                self.exports.types.insert(class.name.clone());
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn check_export_default(&mut self, value: &Expression, span: &Span) {
+        if !self.at_top_level(span) {
+            return;
+        }
+        let ty = self.infer(value).widen();
+        self.exports.values.insert("default".to_string(), ty);
+    }
+}
+
+/// The type declarations of a file, unwrapped from their `export`.
+pub(super) fn type_declarations(block: &Block) -> Block {
+    block.iter().map(|node| match &node.kind {
+        AstNodes::Export(declaration) => declaration.as_ref(),
+        _ => node,
+    }).filter(|node| matches!(node.kind, AstNodes::TypeAlias { .. } | AstNodes::Interface(_) | AstNodes::Class(_)))
+        .cloned()
+        .collect()
+}
+
+
+#[test]
+fn check_imports_across_files() {
+    use crate::parser::{parse, Lexer};
+    let shapes = r#"
+        type Side = number
+        export interface Shape
+            area(): number
+        end
+        export function square(side: Side): Shape
+            return { area = () => side * side }
+        end
+        export default 2
+    "#;
+    let mut checker = Checker::new(false);
+    checker.check(&parse(Lexer::new(shapes).collect()).unwrap());
+    assert!(checker.errors.is_empty());
+    let exports = checker.exports;
+    assert_eq!(exports.values.get("default"), Some(&Ty::Number));
+
+    let main = r#"
+        import scale, { square as sq, Shape, circle } from "./shapes"
+        import * as shapes from "shapes"
+        local s: Shape = sq("big")
+        local n: string = scale
+        local t: number = shapes.square(1):area()
+        import { trim } from "../strings"
+    "#;
+    let mut checker = Checker::new(false);
+    checker.add_module("shapes", exports);
+    checker.check(&parse(Lexer::new(main).collect()).unwrap());
+    let errors: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(errors, [
+        "Module './shapes' has no exported member 'circle'.",
+        "Type '\"big\"' is not assignable to type 'number'.",
+        "Type 'number' is not assignable to type 'string'.",
+        "Cannot find module '../strings'.",
+    ]);
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use crate::checker::check::Checker;
+use crate::checker::types::{FunctionTy, TableTy, Ty};
+use crate::parser::ast::{BinaryOps, Expression, Expressions, LiteralType, UnaryOps};
+
+/// Narrowed types of references, keyed by their path.
+pub(super) type Refinements = HashMap<String, Ty>;
+
+/// The path of an expression that narrowing can refer to: `x` or `x.y.z`.
+pub(super) fn reference(expr: &Expression) -> Option<String> {
+    match &expr.kind {
+        Expressions::Ident(name) => Some(name.clone()),
+        Expressions::Field { target, name } => reference(target).map(|path| format!("{}.{}", path, name)),
+        Expressions::Paren(inner) => reference(inner),
+        _ => None,
+    }
+}
+
+fn literal_of(expr: &Expression) -> Option<Ty> {
+    Some(match &expr.kind {
+        Expressions::Nil => Ty::Nil,
+        Expressions::True => Ty::Literal(LiteralType::Boolean(true)),
+        Expressions::False => Ty::Literal(LiteralType::Boolean(false)),
+        Expressions::Number(n) => Ty::Literal(LiteralType::Number(*n)),
+        Expressions::String(s) => Ty::string_literal(s),
+        _ => return None,
+    })
+}
+
+/// The type `type(x) == name` narrows an `any` value to.
+fn type_of_name(name: &str) -> Ty {
+    match name {
+        "nil" => Ty::Nil,
+        "number" => Ty::Number,
+        "string" => Ty::String,
+        "boolean" => Ty::Boolean,
+        "table" => Ty::Table(Box::new(TableTy { fields: Default::default(), indexers: vec![(Ty::Any, Ty::Any)] })),
+        "function" => Ty::Function(Box::new(FunctionTy { params: Vec::new(), varargs: Some(Ty::Any), returns: vec![Ty::Any] })),
+        _ => Ty::Any,
+    }
+}
+
+/// Keeps the references narrowed by both refinements, as the union of both types.
+fn merge(a: Refinements, mut b: Refinements) -> Refinements {
+    a.into_iter().filter_map(|(key, ty)| b.remove(&key).map(|other| (key, Ty::union(vec![ty, other])))).collect()
+}
+
+impl Checker {
+    /// The narrowed type of a reference, refinements of enclosing functions do not apply.
+    pub(super) fn refined(&self, key: &str) -> Option<Ty> {
+        let base = self.frames.last().map_or(0, |frame| frame.refinement_base);
+        self.refinements.get(base..)?.iter().rev().find_map(|refinements| refinements.get(key).cloned())
+    }
+
+    /// Forgets what is known about a reassigned or redeclared reference and the paths below it.
+    pub(super) fn invalidate(&mut self, key: &str) {
+        let prefix = format!("{}.", key);
+        for refinements in &mut self.refinements {
+            refinements.retain(|path, _| path != key && !path.starts_with(&prefix));
+        }
+    }
+
+    /// Narrows every reference of the current refinement frame.
+    pub(super) fn refine(&mut self, refinements: Refinements) {
+        if let Some(current) = self.refinements.last_mut() {
+            current.extend(refinements);
+        }
+    }
+
+    /// The types references have when `cond` evaluates to a truthy (`assume`) or falsy value.
+    pub(super) fn narrow(&mut self, cond: &Expression, assume: bool) -> Refinements {
+        match &cond.kind {
+            Expressions::Paren(inner) => self.narrow(inner, assume),
+            Expressions::Unary { op: UnaryOps::Not, expr } => self.narrow(expr, !assume),
+            Expressions::Binary { op: BinaryOps::And, lhs, rhs } if assume => self.narrow_sequence(lhs, true, rhs, true),
+            Expressions::Binary { op: BinaryOps::And, lhs, rhs } => {
+                let left = self.narrow(lhs, false);
+                let right = self.narrow_sequence(lhs, true, rhs, false);
+                merge(left, right)
+            }
+            Expressions::Binary { op: BinaryOps::Or, lhs, rhs } if assume => {
+                let left = self.narrow(lhs, true);
+                let right = self.narrow_sequence(lhs, false, rhs, true);
+                merge(left, right)
+            }
+            Expressions::Binary { op: BinaryOps::Or, lhs, rhs } => self.narrow_sequence(lhs, false, rhs, false),
+            Expressions::Binary { op: BinaryOps::EQ, lhs, rhs } => self.narrow_equality(lhs, rhs, assume),
+            Expressions::Binary { op: BinaryOps::NEQ, lhs, rhs } => self.narrow_equality(lhs, rhs, !assume),
+            _ => match reference(cond) {
+                Some(key) => {
+                    let ty = self.infer(cond);
+                    HashMap::from([(key, if assume { ty.truthy() } else { ty.falsy() })])
+                }
+                None => HashMap::new(),
+            },
+        }
+    }
+
+    /// Narrows by `first`, then by `second` with the narrowing of `first` in effect.
+    fn narrow_sequence(&mut self, first: &Expression, first_assume: bool, second: &Expression, second_assume: bool) -> Refinements {
+        let mut refinements = self.narrow(first, first_assume);
+        self.refinements.push(refinements.clone());
+        let second = self.narrow(second, second_assume);
+        self.refinements.pop();
+        refinements.extend(second);
+        refinements
+    }
+
+    /// `x == nil`, `type(x) == "string"`, `x.tag == "circle"` and `x == "literal"`, in either order.
+    fn narrow_equality(&mut self, lhs: &Expression, rhs: &Expression, assume: bool) -> Refinements {
+        for (a, b) in [(lhs, rhs), (rhs, lhs)] {
+            if let (Expressions::Call { target, args }, Expressions::String(name)) = (&a.kind, &b.kind) {
+                if matches!(&target.kind, Expressions::Ident(function) if function == "type") && args.len() == 1 {
+                    let Some(key) = reference(&args[0]) else {
+                        return HashMap::new();
+                    };
+                    let ty = self.infer(&args[0]);
+                    return HashMap::from([(key, self.narrow_type_name(&ty, name, assume))]);
+                }
+            }
+            let Some(literal) = literal_of(b) else {
+                continue;
+            };
+            let mut refinements = HashMap::new();
+            if let Expressions::Field { target, name } = &a.kind {
+                if let Some(key) = reference(target) {
+                    let ty = self.infer(target);
+                    refinements.insert(key, self.narrow_discriminant(&ty, name, &literal, assume));
+                }
+            }
+            if let Some(key) = reference(a) {
+                let ty = self.infer(a);
+                refinements.insert(key, narrow_literal(&ty, &literal, assume));
+            }
+            return refinements;
+        }
+        HashMap::new()
+    }
+
+    fn narrow_type_name(&mut self, ty: &Ty, name: &str, assume: bool) -> Ty {
+        if matches!(ty, Ty::Any | Ty::Unknown) {
+            return if assume { type_of_name(name) } else { ty.clone() };
+        }
+        let members: Vec<Ty> = ty.members().into_iter().cloned().collect();
+        Ty::union(members.into_iter().filter(|member| self.type_name_matches(member, name) == assume).collect())
+    }
+
+    fn type_name_matches(&mut self, ty: &Ty, name: &str) -> bool {
+        match self.evaluator.expand(ty) {
+            Ty::Nil => name == "nil",
+            Ty::Boolean | Ty::Literal(LiteralType::Boolean(_)) => name == "boolean",
+            Ty::Number | Ty::Literal(LiteralType::Number(_)) => name == "number",
+            Ty::String | Ty::Literal(LiteralType::String(_)) => name == "string",
+            Ty::Table(_) | Ty::Array(_) | Ty::Tuple(_) => name == "table",
+            Ty::Function(_) => name == "function",
+            Ty::Named { name: opaque, .. } => opaque == name,
+            _ => true,
+        }
+    }
+
+    /// Keeps the members of a union whose `tag` field can hold `literal`, or drops the ones where it must.
+    fn narrow_discriminant(&mut self, ty: &Ty, tag: &str, literal: &Ty, assume: bool) -> Ty {
+        let members: Vec<Ty> = ty.members().into_iter().cloned().collect();
+        let mut kept = Vec::new();
+        for member in members {
+            let tag_ty = match self.evaluator.expand(&member) {
+                Ty::Table(table) => table.fields.get(tag).map(|field| field.ty.clone()),
+                _ => None,
+            };
+            let keep = match tag_ty {
+                Some(tag_ty) if assume => self.evaluator.is_assignable(literal, &tag_ty),
+                Some(tag_ty) => tag_ty != *literal,
+                None => true,
+            };
+            if keep {
+                kept.push(member);
+            }
+        }
+        Ty::union(kept)
+    }
+}
+
+/// The type of a reference known to be (`assume`) or not to be equal to a literal.
+fn narrow_literal(ty: &Ty, literal: &Ty, assume: bool) -> Ty {
+    if matches!(ty, Ty::Any | Ty::Unknown) {
+        return if assume { literal.clone() } else { ty.clone() };
+    }
+    let members = ty.members().into_iter();
+    if assume {
+        let can_equal = |member: &Ty| match literal {
+            Ty::Literal(lit) => member == literal || *member == lit.base(),
+            _ => member == literal,
+        };
+        return if members.clone().any(can_equal) { literal.clone() } else { Ty::Never };
+    }
+    Ty::union(members.filter_map(|member| match (member, literal) {
+        (Ty::Boolean, Ty::Literal(LiteralType::Boolean(b))) => Some(Ty::Literal(LiteralType::Boolean(!b))),
+        _ if member == literal => None,
+        _ => Some(member.clone()),
+    }).collect())
+}
+
+
+#[test]
+fn narrow_references() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        type Shape = { kind: "circle", radius: number } | { kind: "square", side: number }
+        local function area(shape: Shape): number
+            if shape.kind == "circle" then
+                return shape.radius * shape.radius
+            end
+            return shape.side * shape.side
+        end
+        local function describe(shape: Shape): number
+            switch shape.kind
+                case "circle" then
+                    return shape.radius
+                case "square" then
+                    return shape.side
+                case "triangle" then
+                    return 0
+            end
+            return 0
+        end
+        local function len(value: string | number[] | nil): number
+            if value == nil then
+                return 0
+            end
+            if type(value) == "string" then
+                return value:len()
+            end
+            return #value
+        end
+        local function first(list: number[]?): number
+            local n = list and list[1] or 0
+            if not list then
+                error("missing")
+            end
+            return list[1] + n
+        end
+        local function wrong(value: string?): string
+            if value ~= nil then
+                local ok: string = value
+            end
+            return value
+        end
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    let mut checker = Checker::new(true);
+    checker.check(&block);
+    let messages: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(messages, [
+        r#"Type '"triangle"' is not assignable to type '"circle" | "square"'."#,
+        "Type 'string | nil' is not assignable to type 'string'.",
+    ]);
+}
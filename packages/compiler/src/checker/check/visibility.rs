@@ -0,0 +1,96 @@
+use crate::checker::check::Checker;
+use crate::checker::types::Ty;
+use crate::parser::ast::{ClassNode, Expression, Expressions, Visibility};
+use crate::parser::errors::TypeErrors;
+use crate::parser::tokens::Span;
+
+impl Checker {
+    /// Reports accessing a private member outside of its class, or a protected one outside of its class and
+    /// the classes deriving from it. `object` is the type of `target`, the instance or the class the member is
+    /// looked up on. The declaring class is recorded in `members` for the access at `span`.
+    pub(super) fn check_visibility(&mut self, target: &Expression, object: &Ty, name: &str, span: &Span) {
+        let class = match &target.kind {
+            Expressions::Ident(class) if self.evaluator.classes.contains_key(class) && !self.shadows_class(class) => Some(class.clone()),
+            _ => self.instance_class(object),
+        };
+        let Some((declaring, visibility)) = class.and_then(|class| self.declaring_class(&class, name)) else {
+            return;
+        };
+        self.members.insert(span.clone(), declaring.clone());
+        let allowed = match visibility {
+            Visibility::Public => true,
+            Visibility::Private => self.classes.last() == Some(&declaring),
+            Visibility::Protected => self.classes.last().is_some_and(|current| self.derives_from(current, &declaring)),
+        };
+        if !allowed {
+            self.error(TypeErrors::InaccessibleMember(name.to_string(), declaring, visibility), span);
+        }
+    }
+
+    /// The class of an instance type, possibly `nil`.
+    fn instance_class(&self, object: &Ty) -> Option<String> {
+        match object {
+            Ty::Named { name, .. } if self.evaluator.classes.contains_key(name) => Some(name.clone()),
+            Ty::Union(members) => members.iter().find_map(|member| self.instance_class(member)),
+            _ => None,
+        }
+    }
+
+    /// Whether a class name is shadowed by a variable holding something else than the class, such as a
+    /// parameter of the class type.
+    fn shadows_class(&self, name: &str) -> bool {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).is_some_and(|var| !matches!(var.ty, Ty::Table(_)))
+    }
+
+    /// The class declaring a member, the class itself or one of its bases, with the visibility of the member.
+    fn declaring_class(&self, class: &str, name: &str) -> Option<(String, Visibility)> {
+        self.evaluator.inheritance(class).into_iter().find_map(|class| {
+            let visibility = member_visibility(&self.evaluator.classes[&class].0, name)?;
+            Some((class, visibility))
+        })
+    }
+
+    fn derives_from(&self, class: &str, base: &str) -> bool {
+        self.evaluator.inheritance(class).iter().any(|class| class == base)
+    }
+}
+
+fn member_visibility(class: &ClassNode, name: &str) -> Option<Visibility> {
+    let field = class.fields.iter().find(|field| field.field.name == name).map(|field| field.visibility);
+    field.or_else(|| class.methods.iter().find(|method| method.func.name == name).map(|method| method.visibility))
+}
+
+
+#[test]
+fn check_member_visibility() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        class Base
+            private secret: number = 1
+            protected shared: number = 2
+            private static count: number = 0
+            private hidden(): number
+                return self.secret + Base.count
+            end
+        end
+        class Derived extends Base
+            same(other: Derived): boolean
+                return self.shared == other.shared and other.secret == 1
+            end
+        end
+        local d = Derived.new()
+        print(d.secret, d.shared, d:hidden(), Base.count)
+        d.secret = 2
+    "#;
+    let mut checker = Checker::new(true);
+    checker.check(&parse(Lexer::new(src).collect()).unwrap());
+    let errors: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(errors, [
+        "Property 'secret' is private and only accessible within class 'Base'.",
+        "Property 'secret' is private and only accessible within class 'Base'.",
+        "Property 'shared' is protected and only accessible within class 'Base' and its subclasses.",
+        "Property 'hidden' is private and only accessible within class 'Base'.",
+        "Property 'count' is private and only accessible within class 'Base'.",
+        "Property 'secret' is private and only accessible within class 'Base'.",
+    ]);
+}
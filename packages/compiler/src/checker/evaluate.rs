@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::checker::types::{ConditionalTy, FieldTy, FunctionTy, ParamTy, TableTy, Ty};
+use crate::parser::ast::{AstNodes, Block, ClassNode, GenericParam, InterfaceNode, Type, Types};
+use crate::parser::errors::{TypeError, TypeErrors};
+use crate::parser::tokens::Span;
+use crate::parser::{parse, Lexer};
+
+/// Utility types available in every file.
+const PRELUDE: &str = r#"
+type NonNullable<T> = T extends nil ? never : T
+type Exclude<T, U> = T extends U ? never : T
+type Extract<T, U> = T extends U ? T : never
+"#;
+
+/// Aliases nested deeper than this are assumed to be infinitely recursive.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    pub generics: Vec<GenericParam>,
+    pub typ: Type,
+}
+
+/// Resolves type annotations to [`Ty`], expanding aliases and reducing conditional types.
+pub struct TypeEvaluator {
+    aliases: HashMap<String, TypeAlias>,
+    /// Interfaces and classes are nominal [`Ty::Named`] references, expanded to their members on demand.
+    pub(super) interfaces: HashMap<String, (InterfaceNode, Span)>,
+    pub(super) classes: HashMap<String, (ClassNode, Span)>,
+    /// Aliases currently being expanded, references to these stay lazy [`Ty::Named`] values.
+    pub(super) expanding: Vec<String>,
+    /// The aliases of `expanding` reached without a table, function or array type in between, which a reference
+    /// back to them would make circular.
+    bare: Vec<String>,
+    /// The alias [`TypeEvaluator::check_alias`] checks, the one a circular reference is reported for.
+    declaring: Option<String>,
+    /// Strict nil-safety: `nil` is only assignable to optional types.
+    pub strict: bool,
+    pub errors: Vec<TypeError>,
+}
+
+impl Default for TypeEvaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeEvaluator {
+    pub fn new() -> TypeEvaluator {
+        let mut evaluator = TypeEvaluator {
+            aliases: HashMap::new(),
+            interfaces: HashMap::new(),
+            classes: HashMap::new(),
+            expanding: Vec::new(),
+            bare: Vec::new(),
+            declaring: None,
+            strict: false,
+            errors: Vec::new(),
+        };
+        static PARSED: OnceLock<Block> = OnceLock::new();
+        let prelude = PARSED.get_or_init(|| parse(Lexer::new(PRELUDE).collect()).expect("the prelude parses"));
+        evaluator.declare_types(prelude);
+        evaluator
+    }
+
+    pub fn declare_alias(&mut self, name: &str, generics: Vec<GenericParam>, typ: Type) {
+        self.aliases.insert(name.to_string(), TypeAlias { generics, typ });
+    }
+
+    /// Reports an alias that expands to itself without a table, function or array type in between, such as
+    /// `type T = T | nil`, at its declaration.
+    pub fn check_alias(&mut self, name: &str) {
+        let Some(alias) = self.aliases.get(name).cloned() else {
+            return;
+        };
+        let params: Vec<String> = alias.generics.iter().map(|param| param.name.clone()).collect();
+        self.declaring = Some(name.to_string());
+        self.expanding.push(name.to_string());
+        self.bare.push(name.to_string());
+        self.eval(&alias.typ, &params);
+        self.bare.pop();
+        self.expanding.pop();
+        self.declaring = None;
+    }
+
+    /// Declares every `type` alias, interface and class found at the top level of a block, exported or not.
+    pub fn declare_types(&mut self, block: &Block) {
+        for node in block {
+            let node = match &node.kind {
+                AstNodes::Export(declaration) => declaration,
+                _ => node,
+            };
+            match &node.kind {
+                AstNodes::TypeAlias { name, generics, typ } => self.declare_alias(name, generics.clone(), typ.clone()),
+                AstNodes::Interface(interface) => {
+                    self.interfaces.insert(interface.name.clone(), (interface.clone(), node.span.clone()));
+                }
+                AstNodes::Class(class) => {
+                    self.classes.insert(class.name.clone(), (class.clone(), node.span.clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// A class and its bases in order, up to a base that is not a known class or that would repeat.
+    pub(super) fn inheritance(&self, class: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        let mut current = Some(class.to_string());
+        while let Some(class) = current.filter(|class| self.classes.contains_key(class) && !chain.contains(class)) {
+            current = self.classes[&class].0.extends.clone();
+            chain.push(class);
+        }
+        chain
+    }
+
+    pub fn is_interface(&self, name: &str) -> bool {
+        self.interfaces.contains_key(name)
+    }
+
+    pub(super) fn error(&mut self, kind: TypeErrors, span: &Span) {
+        self.errors.push(TypeError { kind, span: span.clone() });
+    }
+
+    /// Evaluates an annotation with no generic parameters in scope.
+    pub fn evaluate(&mut self, typ: &Type) -> Ty {
+        self.evaluate_with(typ, &[])
+    }
+
+    /// Evaluates an annotation, treating `params` as generic parameters rather than type names.
+    pub fn evaluate_with(&mut self, typ: &Type, params: &[String]) -> Ty {
+        let ty = self.eval(typ, params);
+        self.reduce(&ty)
+    }
+
+    pub(super) fn eval(&mut self, typ: &Type, params: &[String]) -> Ty {
+        match &typ.kind {
+            Types::Named { name, args } => {
+                let args: Vec<Ty> = self.guarded(|evaluator| args.iter().map(|arg| evaluator.eval(arg, params)).collect());
+                if params.contains(name) {
+                    return Ty::Param(name.clone());
+                }
+                self.resolve_name(name, args, &typ.span)
+            }
+            Types::Nil => Ty::Nil,
+            Types::Literal(lit) => Ty::Literal(lit.clone()),
+            Types::Union(types) => Ty::union(types.iter().map(|ty| self.eval(ty, params)).collect()),
+            Types::Intersection(types) => Ty::Intersection(types.iter().map(|ty| self.eval(ty, params)).collect()),
+            Types::Optional(inner) => Ty::optional(self.eval(inner, params)),
+            Types::Array(inner) => Ty::Array(Box::new(self.guarded(|evaluator| evaluator.eval(inner, params)))),
+            Types::Tuple(types) => Ty::Tuple(types.iter().map(|ty| self.eval(ty, params)).collect()),
+            Types::Variadic(inner) => Ty::Variadic(Box::new(self.eval(inner, params))),
+            Types::Function(func) => self.guarded(|evaluator| {
+                let returns = match evaluator.eval(&func.returns, params) {
+                    Ty::Tuple(types) => types,
+                    single => vec![single],
+                };
+                Ty::Function(Box::new(FunctionTy {
+                    params: func.params.iter().map(|param| ParamTy {
+                        name: param.name.clone(),
+                        ty: evaluator.eval(&param.typ, params),
+                        optional: param.optional,
+                    }).collect(),
+                    varargs: func.varargs.as_ref().map(|ty| evaluator.eval(ty, params)),
+                    returns,
+                }))
+            }),
+            Types::Table(table) => self.guarded(|evaluator| Ty::Table(Box::new(TableTy {
+                fields: table.fields.iter().map(|field| (field.name.clone(), FieldTy {
+                    ty: evaluator.eval(&field.typ, params),
+                    optional: field.optional,
+                })).collect(),
+                indexers: table.indexers.iter().map(|(key, value)| (evaluator.eval(key, params), evaluator.eval(value, params))).collect(),
+            }))),
+            Types::Conditional { check, extends, then, otherwise } => Ty::Conditional(Box::new(ConditionalTy {
+                check: self.eval(check, params),
+                extends: self.eval(extends, params),
+                then: self.eval(then, params),
+                otherwise: self.eval(otherwise, params),
+            })),
+        }
+    }
+
+    /// Evaluates the members of a table, function or array type, or type arguments, which references to the
+    /// aliases being expanded are allowed in.
+    fn guarded<T>(&mut self, eval: impl FnOnce(&mut TypeEvaluator) -> T) -> T {
+        let bare = std::mem::take(&mut self.bare);
+        let evaluated = eval(self);
+        self.bare = bare;
+        evaluated
+    }
+
+    fn resolve_name(&mut self, name: &str, args: Vec<Ty>, span: &Span) -> Ty {
+        let builtin = match name {
+            "any" => Some(Ty::Any),
+            "unknown" => Some(Ty::Unknown),
+            "never" => Some(Ty::Never),
+            "number" => Some(Ty::Number),
+            "string" => Some(Ty::String),
+            "boolean" => Some(Ty::Boolean),
+            "table" => Some(Ty::Table(Box::new(TableTy { fields: Default::default(), indexers: vec![(Ty::Any, Ty::Any)] }))),
+            "function" => Some(Ty::Function(Box::new(FunctionTy { params: Vec::new(), varargs: Some(Ty::Any), returns: vec![Ty::Any] }))),
+            "thread" | "userdata" => Some(Ty::Named { name: name.to_string(), args: Vec::new() }),
+            _ => None,
+        };
+        if let Some(ty) = builtin {
+            if !args.is_empty() {
+                self.error(TypeErrors::WrongTypeArgumentCount(name.to_string(), 0, args.len()), span);
+            }
+            return ty;
+        }
+        if self.aliases.contains_key(name) {
+            return self.instantiate(name, args, span);
+        }
+        let generics = match (self.interfaces.get(name), self.classes.contains_key(name)) {
+            (Some((interface, _)), _) => Some(interface.generics.clone()),
+            (None, true) => Some(Vec::new()),
+            (None, false) => None,
+        };
+        if let Some(generics) = generics {
+            let required = generics.iter().filter(|param| param.default.is_none()).count();
+            if args.len() < required || args.len() > generics.len() {
+                self.error(TypeErrors::WrongTypeArgumentCount(name.to_string(), generics.len(), args.len()), span);
+                return Ty::Any;
+            }
+            return Ty::Named { name: name.to_string(), args };
+        }
+        self.error(TypeErrors::UnknownType(name.to_string()), span);
+        Ty::Any
+    }
+
+    /// Applies type arguments to a generic alias, filling in defaults and checking constraints.
+    pub fn instantiate(&mut self, name: &str, args: Vec<Ty>, span: &Span) -> Ty {
+        if self.bare.iter().any(|bare| bare == name) {
+            if self.declaring.as_deref() == Some(name) {
+                let declaration = self.aliases[name].typ.span.clone();
+                self.error(TypeErrors::CircularAlias(name.to_string()), &declaration);
+            }
+            return Ty::Any;
+        }
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            return Ty::Named { name: name.to_string(), args };
+        }
+        if self.expanding.len() >= MAX_EXPANSION_DEPTH {
+            self.error(TypeErrors::RecursiveAlias(name.to_string()), span);
+            return Ty::Any;
+        }
+        let alias = self.aliases[name].clone();
+        let param_names: Vec<String> = alias.generics.iter().map(|param| param.name.clone()).collect();
+        self.expanding.push(name.to_string());
+        let Some(bindings) = self.bind_generics(name, &alias.generics, &args, span) else {
+            self.expanding.pop();
+            return Ty::Any;
+        };
+        self.bare.push(name.to_string());
+        let template = self.eval(&alias.typ, &param_names);
+        self.bare.pop();
+        self.expanding.pop();
+        self.reduce(&template.substitute(&bindings))
+    }
+
+    /// Binds type arguments to generic parameters, filling in defaults and checking constraints.
+    pub(super) fn bind_generics(&mut self, name: &str, generics: &[GenericParam], args: &[Ty], span: &Span) -> Option<HashMap<String, Ty>> {
+        let required = generics.iter().filter(|param| param.default.is_none()).count();
+        if args.len() < required || args.len() > generics.len() {
+            self.error(TypeErrors::WrongTypeArgumentCount(name.to_string(), generics.len(), args.len()), span);
+            return None;
+        }
+        let param_names: Vec<String> = generics.iter().map(|param| param.name.clone()).collect();
+        let mut bindings = HashMap::new();
+        for (ix, param) in generics.iter().enumerate() {
+            let arg = match args.get(ix) {
+                Some(arg) => arg.clone(),
+                None => {
+                    let default = self.eval(param.default.as_ref().unwrap(), &param_names);
+                    default.substitute(&bindings)
+                }
+            };
+            if let Some(constraint) = &param.constraint {
+                let constraint = self.eval(constraint, &param_names).substitute(&bindings);
+                let constraint = self.reduce(&constraint);
+                if !arg.has_params() && !self.is_assignable(&arg, &constraint) {
+                    self.error(TypeErrors::ConstraintNotSatisfied(arg.to_string(), param.name.clone(), constraint.to_string()), span);
+                }
+            }
+            bindings.insert(param.name.clone(), arg);
+        }
+        Some(bindings)
+    }
+
+    /// Resolves conditional types whose operands no longer depend on generic parameters.
+    pub fn reduce(&mut self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Conditional(cond) if !cond.check.has_params() && !cond.extends.has_params() => {
+                let check = self.reduce(&cond.check);
+                let extends = self.reduce(&cond.extends);
+                if check == Ty::Any {
+                    let (then, otherwise) = (self.reduce(&cond.then), self.reduce(&cond.otherwise));
+                    return Ty::union(vec![then, otherwise]);
+                }
+                if self.is_assignable(&check, &extends) {
+                    self.reduce(&cond.then)
+                } else {
+                    self.reduce(&cond.otherwise)
+                }
+            }
+            Ty::Union(types) => Ty::union(types.iter().map(|ty| self.reduce(ty)).collect()),
+            Ty::Intersection(types) => Ty::Intersection(types.iter().map(|ty| self.reduce(ty)).collect()),
+            Ty::Tuple(types) => Ty::Tuple(types.iter().map(|ty| self.reduce(ty)).collect()),
+            Ty::Array(inner) => Ty::Array(Box::new(self.reduce(inner))),
+            Ty::Variadic(inner) => Ty::Variadic(Box::new(self.reduce(inner))),
+            Ty::Function(func) => Ty::Function(Box::new(FunctionTy {
+                params: func.params.iter().map(|param| ParamTy { ty: self.reduce(&param.ty), ..param.clone() }).collect(),
+                varargs: func.varargs.as_ref().map(|ty| self.reduce(ty)),
+                returns: func.returns.iter().map(|ty| self.reduce(ty)).collect(),
+            })),
+            Ty::Table(table) => Ty::Table(Box::new(TableTy {
+                fields: table.fields.iter().map(|(name, field)| (name.clone(), FieldTy { ty: self.reduce(&field.ty), optional: field.optional })).collect(),
+                indexers: table.indexers.iter().map(|(key, value)| (self.reduce(key), self.reduce(value))).collect(),
+            })),
+            other => other.clone(),
+        }
+    }
+
+    /// Expands a lazy reference to a recursive alias one level and interfaces or classes to their members,
+    /// other types are returned as is.
+    pub fn expand(&mut self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Named { name, args } if self.aliases.contains_key(name) => {
+                let span = self.aliases[name].typ.span.clone();
+                self.instantiate(name, args.clone(), &span)
+            }
+            Ty::Named { name, args } if self.interfaces.contains_key(name) => self.interface_members(name, args),
+            Ty::Named { name, .. } if self.classes.contains_key(name) => self.instance_members(name),
+            other => other.clone(),
+        }
+    }
+}
+
+
+#[test]
+fn evaluate_conditional_aliases() {
+    let src = r#"
+        type myType<T> = T extends boolean ? "yes" : "no"
+        type Box<T extends string = "x"> = { value: T }
+        type List<T> = { value: T, next: List<T>? }
+        local a: myType<boolean>
+        local b: myType<number | boolean>
+        local c: Exclude<"a" | "b" | "c", "a">
+        local d: NonNullable<string?>
+        local e: Box
+        local f: List<number>
+        local g: Box<number>
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    let mut evaluator = TypeEvaluator::new();
+    evaluator.declare_types(&block);
+    let shown: Vec<String> = block.iter().filter_map(|node| match &node.kind {
+        AstNodes::LocalAssign { names, .. } => Some(evaluator.evaluate(names[0].typ.as_ref().unwrap()).to_string()),
+        _ => None
+    }).collect();
+    assert_eq!(shown, [
+        r#""yes""#,
+        r#""no" | "yes""#,
+        r#""b" | "c""#,
+        "string",
+        r#"{ value: "x" }"#,
+        "{ next: List<number> | nil, value: number }",
+        "{ value: number }",
+    ]);
+    assert_eq!(evaluator.errors.len(), 1);
+    assert!(matches!(evaluator.errors[0].kind, TypeErrors::ConstraintNotSatisfied(..)));
+}
@@ -0,0 +1,150 @@
+use std::sync::OnceLock;
+use crate::checker::evaluate::TypeEvaluator;
+use crate::checker::types::Ty;
+use crate::parser::ast::AstNodes;
+use crate::parser::{parse, Lexer};
+
+/// The type of the files of the `io` library, their methods taking the file first.
+macro_rules! file_type {
+    () => {
+        "{
+            write: (any, ...any) -> (any, string?), read: (any, ...any) -> ...any, lines: (any, ...any) -> (any, any, any),
+            close: (any) -> (boolean?, string?), flush: (any) -> any, seek: (any, string?, number?) -> (number?, string?),
+            setvbuf: (any, string, number?) -> any
+        }"
+    };
+}
+
+/// Signatures of the standard library, the members of every Lua version tlua targets together, LuaJIT's `bit`
+/// included.
+const GLOBALS: &[(&str, &str)] = &[
+    ("print", "(...any) -> ()"),
+    ("type", r#"(any) -> "nil" | "number" | "string" | "boolean" | "table" | "function" | "thread" | "userdata""#),
+    ("tostring", "(any) -> string"),
+    ("tonumber", "(any, number?) -> number?"),
+    ("pairs", "(any) -> (any, any, any)"),
+    ("ipairs", "(any) -> (any, any, any)"),
+    ("next", "(any, any?) -> (any, any)"),
+    ("select", "(any, ...any) -> ...any"),
+    ("error", "(any, number?) -> never"),
+    ("assert", "(any, ...any) -> ...any"),
+    ("pcall", "(any, ...any) -> (boolean, ...any)"),
+    ("xpcall", "(any, any, ...any) -> (boolean, ...any)"),
+    ("setmetatable", "(any, any) -> any"),
+    ("getmetatable", "(any) -> any"),
+    ("rawget", "(any, any) -> any"),
+    ("rawset", "(any, any, any) -> any"),
+    ("rawequal", "(any, any) -> boolean"),
+    ("rawlen", "(any) -> number"),
+    ("require", "(string) -> any"),
+    ("unpack", "(any, number?, number?) -> ...any"),
+    ("load", "(any, ...any) -> (any, string?)"),
+    ("loadstring", "(string, string?) -> (any, string?)"),
+    ("loadfile", "(string?, ...any) -> (any, string?)"),
+    ("dofile", "(string?) -> ...any"),
+    ("collectgarbage", "(string?, ...any) -> any"),
+    ("setfenv", "(any, any) -> any"),
+    ("getfenv", "(any?) -> any"),
+    ("_G", "table"),
+    ("_ENV", "table"),
+    ("_VERSION", "string"),
+    ("string", "{
+        format: (string, ...any) -> string, rep: (string, number, string?) -> string,
+        sub: (string, number, number?) -> string, len: (string) -> number,
+        upper: (string) -> string, lower: (string) -> string, reverse: (string) -> string,
+        byte: (string, number?, number?) -> ...number, char: (...number) -> string,
+        find: (string, string, number?, boolean?) -> (number?, number?, ...any),
+        match: (string, string, number?) -> ...any, gmatch: (string, string) -> () -> ...any,
+        gsub: (string, string, any, number?) -> (string, number), dump: (any, boolean?) -> string,
+        pack: (string, ...any) -> string, unpack: (string, string, number?) -> ...any, packsize: (string) -> number
+    }"),
+    ("table", "{
+        insert: (any, any, any?) -> (), remove: (any, number?) -> any,
+        concat: (any, string?, number?, number?) -> string, sort: (any, any?) -> (),
+        unpack: (any, number?, number?) -> ...any, pack: (...any) -> any,
+        move: (any, number, number, number, any?) -> any, maxn: (any) -> number
+    }"),
+    ("math", r#"{
+        floor: (number) -> number, ceil: (number) -> number, abs: (number) -> number,
+        max: (number, ...number) -> number, min: (number, ...number) -> number,
+        sqrt: (number) -> number, exp: (number) -> number, log: (number, number?) -> number, log10: (number) -> number,
+        sin: (number) -> number, cos: (number) -> number, tan: (number) -> number,
+        asin: (number) -> number, acos: (number) -> number, atan: (number, number?) -> number, atan2: (number, number) -> number,
+        sinh: (number) -> number, cosh: (number) -> number, tanh: (number) -> number,
+        deg: (number) -> number, rad: (number) -> number, pow: (number, number) -> number,
+        fmod: (number, number) -> number, modf: (number) -> (number, number),
+        frexp: (number) -> (number, number), ldexp: (number, number) -> number,
+        random: (number?, number?) -> number, randomseed: (number?, number?) -> (),
+        tointeger: (any) -> number?, type: (any) -> "integer" | "float" | nil, ult: (number, number) -> boolean,
+        huge: number, pi: number, maxinteger: number, mininteger: number
+    }"#),
+    ("os", "{
+        time: (any?) -> number, clock: () -> number, date: (string?, number?) -> any, difftime: (number, number?) -> number,
+        getenv: (string) -> string?, exit: (any?, boolean?) -> never, remove: (string) -> (boolean?, string?),
+        rename: (string, string) -> (boolean?, string?), tmpname: () -> string, execute: (string?) -> ...any,
+        setlocale: (string?, string?) -> string?
+    }"),
+    ("io", concat!("{
+        write: (...any) -> any, read: (...any) -> ...any, lines: (...any) -> (any, any, any),
+        open: (string, string?) -> (", file_type!(), "?, string?), close: (any?) -> (boolean?, string?),
+        input: (any?) -> any, output: (any?) -> any, popen: (string, string?) -> (any, string?),
+        tmpfile: () -> any, type: (any) -> string?, flush: () -> any,
+        stdin: ", file_type!(), ", stdout: ", file_type!(), ", stderr: ", file_type!(), "
+    }")),
+    ("coroutine", "{
+        create: (any) -> thread, resume: (thread, ...any) -> (boolean, ...any), yield: (...any) -> ...any,
+        status: (thread) -> string, wrap: (any) -> any, running: () -> (thread?, boolean?),
+        isyieldable: () -> boolean, close: (thread) -> (boolean, any)
+    }"),
+    ("debug", "{
+        traceback: (...any) -> string, getinfo: (...any) -> any, getlocal: (...any) -> (string?, any),
+        setlocal: (...any) -> string?, getupvalue: (any, number) -> (string?, any), setupvalue: (any, number, any) -> string?,
+        upvalueid: (any, number) -> any, upvaluejoin: (any, number, any, number) -> (),
+        sethook: (...any) -> (), gethook: (any?) -> ...any, getmetatable: (any) -> any, setmetatable: (any, any) -> any,
+        getregistry: () -> any, debug: () -> ()
+    }"),
+    ("utf8", "{
+        char: (...number) -> string, charpattern: string, codes: (string, boolean?) -> (any, any, any),
+        codepoint: (string, number?, number?, boolean?) -> ...number, len: (string, number?, number?, boolean?) -> (number?, number?),
+        offset: (string, number, number?) -> number?
+    }"),
+    ("package", "{
+        path: string, cpath: string, config: string, loaded: { [string]: any }, preload: { [string]: any },
+        searchers: any[], loaders: any[], searchpath: (string, string, string?, string?) -> (string?, string?),
+        loadlib: (string, string) -> (any, string?)
+    }"),
+    ("bit32", "{
+        band: (...number) -> number, bor: (...number) -> number, bxor: (...number) -> number, bnot: (number) -> number,
+        btest: (...number) -> boolean, lshift: (number, number) -> number, rshift: (number, number) -> number,
+        arshift: (number, number) -> number, lrotate: (number, number) -> number, rrotate: (number, number) -> number,
+        extract: (number, number, number?) -> number, replace: (number, number, number, number?) -> number
+    }"),
+    ("bit", "{
+        tobit: (number) -> number, tohex: (number, number?) -> string, bnot: (number) -> number,
+        band: (number, ...number) -> number, bor: (number, ...number) -> number, bxor: (number, ...number) -> number,
+        lshift: (number, number) -> number, rshift: (number, number) -> number, arshift: (number, number) -> number,
+        rol: (number, number) -> number, ror: (number, number) -> number, bswap: (number) -> number
+    }"),
+];
+
+/// Whether the standard library defines a global of that name.
+pub fn is_standard_global(name: &str) -> bool {
+    GLOBALS.iter().any(|(global, _)| *global == name)
+}
+
+/// The types of the standard library, evaluated once and shared by every checker.
+pub fn standard_globals() -> &'static [(String, Ty)] {
+    static EVALUATED: OnceLock<Vec<(String, Ty)>> = OnceLock::new();
+    EVALUATED.get_or_init(|| {
+        let mut evaluator = TypeEvaluator::new();
+        GLOBALS.iter().map(|(name, signature)| {
+            let src = format!("local _: {}", signature);
+            let block = parse(Lexer::new(&src).collect()).expect("a standard library signature parses");
+            let ty = match block.first().map(|node| &node.kind) {
+                Some(AstNodes::LocalAssign { names, .. }) => names[0].typ.as_ref().map_or(Ty::Any, |typ| evaluator.evaluate(typ)),
+                _ => Ty::Any,
+            };
+            (name.to_string(), ty)
+        }).collect()
+    })
+}
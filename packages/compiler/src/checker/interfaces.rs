@@ -0,0 +1,195 @@
+use crate::checker::evaluate::TypeEvaluator;
+use crate::checker::types::{FieldTy, FunctionTy, ParamTy, TableTy, Ty};
+use crate::parser::ast::{Expression, Expressions, FunctionDecl, LiteralType, TableField, Type, Types};
+use crate::parser::errors::TypeErrors;
+
+/// A member of an interface that a type does not provide.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nonconformance {
+    Missing(String),
+    Incompatible { member: String, found: Ty, expected: Ty },
+}
+
+impl TypeEvaluator {
+    /// The members of an interface, including the ones inherited through `extends`.
+    pub(super) fn interface_members(&mut self, name: &str, args: &[Ty]) -> Ty {
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            return Ty::Any;
+        }
+        let (interface, span) = self.interfaces[name].clone();
+        let params: Vec<String> = interface.generics.iter().map(|param| param.name.clone()).collect();
+        self.expanding.push(name.to_string());
+        let Some(bindings) = self.bind_generics(name, &interface.generics, args, &span) else {
+            self.expanding.pop();
+            return Ty::Any;
+        };
+        let mut members = TableTy::default();
+        for base in &interface.extends {
+            let base_ty = self.eval(base, &params).substitute(&bindings);
+            match self.expand(&base_ty) {
+                Ty::Table(table) => {
+                    members.fields.extend(table.fields);
+                    members.indexers.extend(table.indexers);
+                }
+                Ty::Any => {}
+                _ => self.error(TypeErrors::NotAnInterface(base.to_string()), &base.span),
+            }
+        }
+        let body = Type { kind: Types::Table(interface.body.clone()), span };
+        if let Ty::Table(own) = self.eval(&body, &params).substitute(&bindings) {
+            members.fields.extend(own.fields);
+            members.indexers.extend(own.indexers);
+        }
+        self.expanding.pop();
+        self.reduce(&Ty::Table(Box::new(members)))
+    }
+
+    /// The type of a class instance: inherited members, then instance fields and methods.
+    pub(super) fn instance_members(&mut self, name: &str) -> Ty {
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            return Ty::Any;
+        }
+        let (class, _) = self.classes[name].clone();
+        self.expanding.push(name.to_string());
+        let mut members = match &class.extends {
+            Some(base) if self.classes.contains_key(base) => match self.instance_members(base) {
+                Ty::Table(table) => *table,
+                _ => TableTy::default(),
+            },
+            _ => TableTy::default(),
+        };
+        for field in class.fields.iter().filter(|field| !field.is_static) {
+            let ty = match (&field.field.typ, &field.value) {
+                (Some(typ), _) => self.evaluate(typ),
+                (None, Some(value)) => self.literal_type(value).widen(),
+                (None, None) => Ty::Any,
+            };
+            members.fields.insert(field.field.name.clone(), FieldTy { ty, optional: false });
+        }
+        for method in class.methods.iter().filter(|method| !method.is_static) {
+            let ty = self.function_type(&method.func);
+            members.fields.insert(method.func.name.clone(), FieldTy { ty, optional: false });
+        }
+        self.expanding.pop();
+        Ty::Table(Box::new(members))
+    }
+
+    /// The signature of a function declaration, unannotated parameters and returns being `any`.
+    /// The implicit `self` of methods is not part of the signature.
+    pub fn function_type(&mut self, func: &FunctionDecl) -> Ty {
+        let params = func.args.iter().map(|arg| ParamTy {
+            name: Some(arg.name.clone()),
+            ty: arg.typ.as_ref().map_or(Ty::Any, |typ| self.evaluate(typ)),
+            optional: false,
+        }).collect();
+        let varargs = match (&func.varargs_typ, func.varargs) {
+            (Some(typ), _) => Some(self.evaluate(typ)),
+            (None, true) => Some(Ty::Any),
+            (None, false) => None,
+        };
+        let returns = match func.returns.as_ref().map(|typ| self.evaluate(typ)) {
+            Some(Ty::Tuple(types)) => types,
+            Some(single) => vec![single],
+            None => vec![Ty::Any],
+        };
+        Ty::Function(Box::new(FunctionTy { params, varargs, returns }))
+    }
+
+    /// The type of a literal expression, keeping literal types so that tags can be matched.
+    /// Expressions that are not literals are `any`.
+    pub fn literal_type(&mut self, expr: &Expression) -> Ty {
+        match &expr.kind {
+            Expressions::Nil => Ty::Nil,
+            Expressions::True => Ty::Literal(LiteralType::Boolean(true)),
+            Expressions::False => Ty::Literal(LiteralType::Boolean(false)),
+            Expressions::Number(n) => Ty::Literal(LiteralType::Number(*n)),
+            Expressions::String(s) => Ty::string_literal(s),
+            Expressions::Paren(inner) => self.literal_type(inner),
+            Expressions::Function(func) => self.function_type(func),
+            Expressions::Table(fields) => {
+                let mut table = TableTy::default();
+                let mut elements = Vec::new();
+                for field in fields {
+                    match field {
+                        TableField::Named(name, value) => {
+                            let ty = self.literal_type(value);
+                            table.fields.insert(name.clone(), FieldTy { ty, optional: false });
+                        }
+                        TableField::Positional(value) => elements.push(self.literal_type(value)),
+                        TableField::Keyed(..) => table.indexers.push((Ty::Any, Ty::Any)),
+                    }
+                }
+                if !elements.is_empty() {
+                    if table.fields.is_empty() && table.indexers.is_empty() {
+                        return Ty::Array(Box::new(Ty::union(elements).widen()));
+                    }
+                    table.indexers.push((Ty::Number, Ty::union(elements).widen()));
+                }
+                Ty::Table(Box::new(table))
+            }
+            _ => Ty::Any,
+        }
+    }
+
+    /// Compares a type against the members an interface requires, returning every member that is missing
+    /// or has an incompatible type. Interfaces that do not expand to a table have no members to compare.
+    pub fn conformance(&mut self, ty: &Ty, interface: &Ty) -> Vec<Nonconformance> {
+        let Ty::Table(required) = self.expand(interface) else {
+            return Vec::new();
+        };
+        let Ty::Table(provided) = self.expand(ty) else {
+            return Vec::new();
+        };
+        let mut problems = Vec::new();
+        for (name, field) in &required.fields {
+            match provided.fields.get(name) {
+                None if field.optional => {}
+                None => problems.push(Nonconformance::Missing(name.clone())),
+                Some(found) => {
+                    let expected = if field.optional { Ty::optional(field.ty.clone()) } else { field.ty.clone() };
+                    if !self.is_assignable(&found.ty, &expected) {
+                        problems.push(Nonconformance::Incompatible { member: name.clone(), found: found.ty.clone(), expected });
+                    }
+                }
+            }
+        }
+        problems
+    }
+}
+
+
+#[test]
+fn check_interface_conformance() {
+    use crate::checker::Checker;
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        interface Named
+            name: string
+        end
+        interface Greeter extends Named
+            greet(msg: string): string
+            volume?: number
+        end
+        class Loud implements Greeter
+            name: string = "loud"
+            greet(msg: string): string
+                return msg
+            end
+        end
+        class Quiet implements Greeter
+            name = 5
+        end
+        local function welcome(g: Greeter) end
+        local ok: Greeter = { name = "a", greet = (msg: string) => msg }
+        welcome({ name = 1, greet = (msg: string) => msg })
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    let mut checker = Checker::new(false);
+    checker.check(&block);
+    let messages: Vec<String> = checker.errors.iter().map(|error| error.kind.to_string()).collect();
+    assert_eq!(messages, [
+        "Property 'greet' is missing in type 'Quiet' but required in type 'Greeter'.",
+        "Property 'name' of type 'number' is not assignable to type 'string' required by 'Greeter'.",
+        "Property 'name' of type '1' is not assignable to type 'string' required by 'Greeter'.",
+    ]);
+}
@@ -0,0 +1,11 @@
+pub mod types;
+pub mod evaluate;
+pub mod interfaces;
+mod relate;
+mod check;
+mod globals;
+
+pub use check::{Checker, ModuleExports};
+pub use evaluate::TypeEvaluator;
+pub use globals::is_standard_global;
+pub use types::Ty;
\ No newline at end of file
@@ -0,0 +1,99 @@
+use crate::checker::evaluate::TypeEvaluator;
+use crate::checker::types::{FunctionTy, TableTy, Ty};
+
+/// Nested comparisons of lazily expanded recursive types stop here and are assumed to succeed.
+const MAX_RELATE_DEPTH: usize = 32;
+
+impl TypeEvaluator {
+    /// Whether a value of type `from` can be used where `to` is expected.
+    pub fn is_assignable(&mut self, from: &Ty, to: &Ty) -> bool {
+        self.relate(from, to, 0)
+    }
+
+    fn relate(&mut self, from: &Ty, to: &Ty, depth: usize) -> bool {
+        if depth > MAX_RELATE_DEPTH || from == to {
+            return true;
+        }
+        match (from, to) {
+            (_, Ty::Any | Ty::Unknown) | (Ty::Any | Ty::Never, _) => true,
+            // gradual typing lets `nil` stand in for any value
+            (Ty::Nil, _) if !self.strict => true,
+            (Ty::Union(members), _) => members.iter().all(|member| self.relate(member, to, depth + 1)),
+            (_, Ty::Union(members)) => members.iter().any(|member| self.relate(from, member, depth + 1)),
+            (_, Ty::Intersection(members)) => members.iter().all(|member| self.relate(from, member, depth + 1)),
+            (Ty::Intersection(members), _) => members.iter().any(|member| self.relate(member, to, depth + 1))
+                || merge_tables(members).is_some_and(|merged| self.relate(&merged, to, depth + 1)),
+            (Ty::Named { name: a, args: a_args }, Ty::Named { name: b, args: b_args }) if a == b => {
+                a_args.len() == b_args.len() && a_args.iter().zip(b_args).all(|(a, b)| self.relate(a, b, depth + 1))
+            }
+            (Ty::Named { .. }, _) | (_, Ty::Named { .. }) => {
+                let (expanded_from, expanded_to) = (self.expand(from), self.expand(to));
+                if &expanded_from == from && &expanded_to == to {
+                    return false;
+                }
+                self.relate(&expanded_from, &expanded_to, depth + 1)
+            }
+            (Ty::Literal(lit), _) => lit.base() == *to,
+            (Ty::Array(a), Ty::Array(b)) => self.relate(a, b, depth + 1),
+            (Ty::Variadic(a), Ty::Variadic(b)) => self.relate(a, b, depth + 1),
+            (Ty::Variadic(inner), _) => self.relate(inner, to, depth + 1),
+            (_, Ty::Variadic(inner)) => self.relate(from, inner, depth + 1),
+            (Ty::Tuple(a), Ty::Tuple(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.relate(a, b, depth + 1)),
+            (Ty::Function(a), Ty::Function(b)) => self.relate_functions(a, b, depth),
+            (Ty::Table(a), Ty::Table(b)) => self.relate_tables(a, b, depth),
+            (Ty::Array(element), Ty::Table(table)) => table.fields.values().all(|field| field.optional)
+                && table.indexers.iter().all(|(key, value)| self.relate(&Ty::Number, key, depth + 1) && self.relate(element, value, depth + 1)),
+            _ => false,
+        }
+    }
+
+    /// Parameters are compared bivariantly, which keeps callbacks typed with wider parameters usable.
+    fn relate_functions(&mut self, from: &FunctionTy, to: &FunctionTy, depth: usize) -> bool {
+        if from.params.iter().skip(to.params.len()).any(|param| !param.optional) && to.varargs.is_none() && from.varargs.is_none() {
+            return false;
+        }
+        for (a, b) in from.params.iter().zip(&to.params) {
+            if !self.relate(&b.ty, &a.ty, depth + 1) && !self.relate(&a.ty, &b.ty, depth + 1) {
+                return false;
+            }
+        }
+        from.returns.iter().zip(&to.returns).all(|(a, b)| self.relate(a, b, depth + 1))
+    }
+
+    fn relate_tables(&mut self, from: &TableTy, to: &TableTy, depth: usize) -> bool {
+        for (name, field) in &to.fields {
+            match from.fields.get(name) {
+                Some(source) => {
+                    if !self.relate(&source.ty, &field.ty, depth + 1) {
+                        return false;
+                    }
+                }
+                None if field.optional => {}
+                None => return false,
+            }
+        }
+        for (key, value) in &to.indexers {
+            for (name, field) in &from.fields {
+                if self.relate(&Ty::string_literal(name), key, depth + 1) && !self.relate(&field.ty, value, depth + 1) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Combines the members of an intersection of table types into one table.
+fn merge_tables(members: &[Ty]) -> Option<Ty> {
+    let mut merged = TableTy::default();
+    for member in members {
+        match member {
+            Ty::Table(table) => {
+                merged.fields.extend(table.fields.clone());
+                merged.indexers.extend(table.indexers.clone());
+            }
+            _ => return None,
+        }
+    }
+    Some(Ty::Table(Box::new(merged)))
+}
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use crate::parser::ast::LiteralType;
+
+/// A resolved type, as seen by the checker.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Any,
+    Unknown,
+    Never,
+    Nil,
+    Boolean,
+    Number,
+    String,
+    Literal(LiteralType),
+    Union(Vec<Ty>),
+    Intersection(Vec<Ty>),
+    Array(Box<Ty>),
+    Tuple(Vec<Ty>),
+    /// Any number of values, ending the returns of a function or a list of values.
+    Variadic(Box<Ty>),
+    Function(Box<FunctionTy>),
+    Table(Box<TableTy>),
+    /// A reference by name: a recursive alias that is expanded lazily, or an opaque type such as `thread`.
+    Named {
+        name: String,
+        args: Vec<Ty>,
+    },
+    /// An unsubstituted generic parameter.
+    Param(String),
+    /// A conditional type that still depends on generic parameters.
+    Conditional(Box<ConditionalTy>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionTy {
+    pub params: Vec<ParamTy>,
+    pub varargs: Option<Ty>,
+    pub returns: Vec<Ty>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamTy {
+    pub name: Option<String>,
+    pub ty: Ty,
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TableTy {
+    pub fields: BTreeMap<String, FieldTy>,
+    pub indexers: Vec<(Ty, Ty)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldTy {
+    pub ty: Ty,
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConditionalTy {
+    pub check: Ty,
+    pub extends: Ty,
+    pub then: Ty,
+    pub otherwise: Ty,
+}
+
+impl Ty {
+    pub fn string_literal(s: &str) -> Ty {
+        Ty::Literal(LiteralType::String(s.to_string()))
+    }
+
+    pub fn optional(ty: Ty) -> Ty {
+        Ty::union(vec![ty, Ty::Nil])
+    }
+
+    /// Builds a flattened union, dropping duplicates, `never` and literals covered by their base type.
+    pub fn union(types: Vec<Ty>) -> Ty {
+        let mut members: Vec<Ty> = Vec::new();
+        for ty in types {
+            let flattened = match ty {
+                Ty::Union(inner) => inner,
+                Ty::Never => continue,
+                other => vec![other],
+            };
+            for ty in flattened {
+                if ty == Ty::Any {
+                    return Ty::Any;
+                }
+                if !members.contains(&ty) {
+                    members.push(ty);
+                }
+            }
+        }
+        let has = |base: &Ty, members: &Vec<Ty>| members.contains(base);
+        let snapshot = members.clone();
+        members.retain(|ty| match ty {
+            Ty::Literal(lit) => !has(&lit.base(), &snapshot),
+            _ => true,
+        });
+        let has_true = members.contains(&Ty::Literal(LiteralType::Boolean(true)));
+        let has_false = members.contains(&Ty::Literal(LiteralType::Boolean(false)));
+        if has_true && has_false {
+            members.retain(|ty| !matches!(ty, Ty::Literal(LiteralType::Boolean(_))));
+            members.push(Ty::Boolean);
+        }
+        match members.len() {
+            0 => Ty::Never,
+            1 => members.remove(0),
+            _ => Ty::Union(members),
+        }
+    }
+
+    /// Replaces literal types by their base, as done for mutable class fields and inferred locals.
+    pub fn widen(&self) -> Ty {
+        match self {
+            Ty::Literal(lit) => lit.base(),
+            Ty::Union(types) => Ty::union(types.iter().map(Ty::widen).collect()),
+            Ty::Array(inner) => Ty::Array(Box::new(inner.widen())),
+            Ty::Table(table) => Ty::Table(Box::new(TableTy {
+                fields: table.fields.iter().map(|(name, field)| (name.clone(), FieldTy { ty: field.ty.widen(), optional: field.optional })).collect(),
+                indexers: table.indexers.clone(),
+            })),
+            other => other.clone(),
+        }
+    }
+
+    /// The members that can make a value truthy, as produced by the left side of `or`.
+    pub fn truthy(&self) -> Ty {
+        Ty::union(self.members().into_iter().filter_map(|ty| match ty {
+            Ty::Nil | Ty::Literal(LiteralType::Boolean(false)) => None,
+            Ty::Boolean => Some(Ty::Literal(LiteralType::Boolean(true))),
+            other => Some(other.clone()),
+        }).collect())
+    }
+
+    /// The members that can make a value falsy, as produced by the left side of `and`.
+    pub fn falsy(&self) -> Ty {
+        Ty::union(self.members().into_iter().filter_map(|ty| match ty {
+            Ty::Nil | Ty::Any | Ty::Unknown | Ty::Literal(LiteralType::Boolean(false)) => Some(ty.clone()),
+            Ty::Boolean => Some(Ty::Literal(LiteralType::Boolean(false))),
+            _ => None,
+        }).collect())
+    }
+
+    pub fn members(&self) -> Vec<&Ty> {
+        match self {
+            Ty::Union(members) => members.iter().collect(),
+            other => vec![other],
+        }
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.members().iter().any(|ty| matches!(ty, Ty::Nil | Ty::Any | Ty::Unknown))
+    }
+
+    /// Whether the type mentions a generic parameter that has not been substituted yet.
+    pub fn has_params(&self) -> bool {
+        match self {
+            Ty::Param(_) => true,
+            Ty::Union(types) | Ty::Intersection(types) | Ty::Tuple(types) => types.iter().any(Ty::has_params),
+            Ty::Named { args, .. } => args.iter().any(Ty::has_params),
+            Ty::Array(inner) | Ty::Variadic(inner) => inner.has_params(),
+            Ty::Function(func) => func.params.iter().any(|param| param.ty.has_params())
+                || func.varargs.as_ref().is_some_and(Ty::has_params)
+                || func.returns.iter().any(Ty::has_params),
+            Ty::Table(table) => table.fields.values().any(|field| field.ty.has_params())
+                || table.indexers.iter().any(|(key, value)| key.has_params() || value.has_params()),
+            Ty::Conditional(cond) => cond.check.has_params() || cond.extends.has_params(),
+            _ => false,
+        }
+    }
+
+    /// Replaces generic parameters by their bindings, leaving conditionals for the evaluator to reduce.
+    pub fn substitute(&self, bindings: &HashMap<String, Ty>) -> Ty {
+        let sub = |ty: &Ty| ty.substitute(bindings);
+        match self {
+            Ty::Param(name) => bindings.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Ty::Union(types) => Ty::union(types.iter().map(sub).collect()),
+            Ty::Intersection(types) => Ty::Intersection(types.iter().map(sub).collect()),
+            Ty::Tuple(types) => Ty::Tuple(types.iter().map(sub).collect()),
+            Ty::Named { name, args } => Ty::Named { name: name.clone(), args: args.iter().map(sub).collect() },
+            Ty::Array(inner) => Ty::Array(Box::new(sub(inner))),
+            Ty::Variadic(inner) => Ty::Variadic(Box::new(sub(inner))),
+            Ty::Function(func) => Ty::Function(Box::new(FunctionTy {
+                params: func.params.iter().map(|param| ParamTy { ty: sub(&param.ty), ..param.clone() }).collect(),
+                varargs: func.varargs.as_ref().map(sub),
+                returns: func.returns.iter().map(sub).collect(),
+            })),
+            Ty::Table(table) => Ty::Table(Box::new(TableTy {
+                fields: table.fields.iter().map(|(name, field)| (name.clone(), FieldTy { ty: sub(&field.ty), optional: field.optional })).collect(),
+                indexers: table.indexers.iter().map(|(key, value)| (sub(key), sub(value))).collect(),
+            })),
+            // conditionals over a bare parameter distribute over unions, as in TypeScript
+            Ty::Conditional(cond) if matches!(&cond.check, Ty::Param(name) if matches!(bindings.get(name), Some(Ty::Union(_) | Ty::Never))) => {
+                let Ty::Param(name) = &cond.check else { unreachable!() };
+                let members = match &bindings[name] {
+                    Ty::Union(members) => members.clone(),
+                    _ => Vec::new(),
+                };
+                Ty::union(members.into_iter().map(|member| {
+                    let mut bindings = bindings.clone();
+                    bindings.insert(name.clone(), member);
+                    self.substitute(&bindings)
+                }).collect())
+            }
+            Ty::Conditional(cond) => Ty::Conditional(Box::new(ConditionalTy {
+                check: sub(&cond.check),
+                extends: sub(&cond.extends),
+                then: sub(&cond.then),
+                otherwise: sub(&cond.otherwise),
+            })),
+            other => other.clone(),
+        }
+    }
+}
+
+impl LiteralType {
+    /// The primitive type a literal belongs to.
+    pub fn base(&self) -> Ty {
+        match self {
+            LiteralType::String(_) => Ty::String,
+            LiteralType::Number(_) => Ty::Number,
+            LiteralType::Boolean(_) => Ty::Boolean,
+        }
+    }
+}
+
+fn join<T: Display>(items: &[T], sep: &str) -> String {
+    items.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(sep)
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ty::Any => write!(f, "any"),
+            Ty::Unknown => write!(f, "unknown"),
+            Ty::Never => write!(f, "never"),
+            Ty::Nil => write!(f, "nil"),
+            Ty::Boolean => write!(f, "boolean"),
+            Ty::Number => write!(f, "number"),
+            Ty::String => write!(f, "string"),
+            Ty::Literal(LiteralType::String(s)) => write!(f, "{:?}", s),
+            Ty::Literal(LiteralType::Number(n)) => write!(f, "{}", n),
+            Ty::Literal(LiteralType::Boolean(b)) => write!(f, "{}", b),
+            Ty::Union(types) => write!(f, "{}", join(types, " | ")),
+            Ty::Intersection(types) => write!(f, "{}", join(types, " & ")),
+            Ty::Array(inner) if matches!(**inner, Ty::Union(_) | Ty::Function(_)) => write!(f, "({})[]", inner),
+            Ty::Array(inner) => write!(f, "{}[]", inner),
+            Ty::Tuple(types) => write!(f, "({})", join(types, ", ")),
+            Ty::Variadic(inner) => write!(f, "...{}", inner),
+            Ty::Function(func) => {
+                let mut params: Vec<String> = func.params.iter().map(|param| match &param.name {
+                    Some(name) => format!("{}{}: {}", name, if param.optional { "?" } else { "" }, param.ty),
+                    None => param.ty.to_string(),
+                }).collect();
+                if let Some(varargs) = &func.varargs {
+                    params.push(format!("...{}", varargs));
+                }
+                match func.returns.as_slice() {
+                    [single] => write!(f, "({}) -> {}", params.join(", "), single),
+                    returns => write!(f, "({}) -> ({})", params.join(", "), join(returns, ", ")),
+                }
+            }
+            Ty::Table(table) => {
+                let mut fields: Vec<String> = table.fields.iter()
+                    .map(|(name, field)| format!("{}{}: {}", name, if field.optional { "?" } else { "" }, field.ty))
+                    .collect();
+                fields.extend(table.indexers.iter().map(|(key, value)| format!("[{}]: {}", key, value)));
+                if fields.is_empty() {
+                    write!(f, "{{}}")
+                } else {
+                    write!(f, "{{ {} }}", fields.join(", "))
+                }
+            }
+            Ty::Named { name, args } if args.is_empty() => write!(f, "{}", name),
+            Ty::Named { name, args } => write!(f, "{}<{}>", name, join(args, ", ")),
+            Ty::Param(name) => write!(f, "{}", name),
+            Ty::Conditional(cond) => write!(f, "{} extends {} ? {} : {}", cond.check, cond.extends, cond.then, cond.otherwise),
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use crate::codegen::{comment, is_prefix_expression, needs_parens, needs_parens_unary, float, number, quote, INDENT};
+use crate::codegen::luau::{concat_parts, generics, interpolated_text, is_plain_reference, luau_type};
+use crate::lower::lower_block;
+use crate::parser::ast::{Attrib, AstNode, AstNodes, BinaryOps, Block, Expression, Expressions, FunctionDecl, NamedTokenWithTypeInfo, TableField, Type, Types, UnaryOps};
+use crate::parser::tokens::Span;
+use crate::sourcemap::Segment;
+use crate::target::{bit_function, bit_unary_function, continue_label, Target};
+
+/// Lowers a checked file and prints it as plain Lua, with every type annotation erased.
+/// Operators the target lacks are replaced with their library equivalents, features it cannot express
+/// are printed as written and reported by the checker.
+pub fn emit_lua(block: Block, target: Target, comments: bool) -> String {
+    emit(block, target, comments)
+}
+
+/// Prints a block for any target, Luau keeping the type annotations.
+pub(super) fn emit(block: Block, target: Target, comments: bool) -> String {
+    emit_mapped(block, target, comments).0
+}
+
+/// Prints a block for any target along with the position every statement was printed at,
+/// to build a source map with.
+pub fn emit_mapped(block: Block, target: Target, comments: bool) -> (String, Vec<Segment>) {
+    let mut emitter = LuaEmitter::new(0, target, comments);
+    emitter.block(&lower_block(block));
+    (emitter.out, emitter.segments)
+}
+
+struct LuaEmitter {
+    out: String,
+    /// Lines printed so far.
+    lines: usize,
+    depth: usize,
+    target: Target,
+    /// Print the comments attached to statements.
+    comments: bool,
+    /// Label ending the innermost loop body, jumping to it is a `continue` in Luau.
+    continue_label: Option<String>,
+    /// Span of the statement being printed.
+    span: Option<Span>,
+    segments: Vec<Segment>,
+    /// Bodies of the function expressions of the statement being printed, with their segments
+    /// relative to the body, placed once the statement is.
+    bodies: Vec<(String, Vec<Segment>)>,
+}
+
+impl LuaEmitter {
+    fn new(depth: usize, target: Target, comments: bool) -> LuaEmitter {
+        LuaEmitter {
+            out: String::new(),
+            lines: 0,
+            depth,
+            target,
+            comments,
+            continue_label: None,
+            span: None,
+            segments: Vec::new(),
+            bodies: Vec::new(),
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        if let Some(span) = &self.span {
+            self.segments.push(Segment { line: self.lines, column: self.depth * INDENT.len(), offset: span.start });
+        }
+        let mut searched = 0;
+        for (body, segments) in std::mem::take(&mut self.bodies) {
+            let Some(found) = text[searched..].find(&body) else {
+                continue;
+            };
+            let start = self.lines + text[..searched + found].matches('\n').count();
+            self.segments.extend(segments.into_iter().map(|segment| Segment { line: segment.line + start, ..segment }));
+            searched += found + body.len();
+        }
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+        self.lines += text.matches('\n').count() + 1;
+    }
+
+    fn indented(&mut self, block: &[AstNode]) {
+        self.depth += 1;
+        self.block(block);
+        self.depth -= 1;
+    }
+
+    fn loop_body(&mut self, body: &Block) {
+        let label = match self.target {
+            Target::Luau => continue_label(body),
+            _ => None,
+        };
+        let saved = std::mem::replace(&mut self.continue_label, label.map(str::to_string));
+        // the label itself is dropped, `continue` jumps past the end of the body
+        self.indented(if label.is_some() { &body[..body.len() - 1] } else { body });
+        self.continue_label = saved;
+    }
+
+    fn block(&mut self, block: &[AstNode]) {
+        for node in block {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &AstNode) {
+        let outer = self.span.replace(node.span.clone());
+        self.statement(node);
+        self.span = outer;
+    }
+
+    fn statement(&mut self, node: &AstNode) {
+        if self.comments {
+            for spanned in &node.comments {
+                self.line(&comment(&spanned.comment));
+            }
+        }
+        match &node.kind {
+            AstNodes::LocalAssign { names, values } => {
+                let names = names.iter().map(|name| {
+                    let annotation = self.annotation(name.typ.as_ref());
+                    format!("{}{}", local_name(name, self.target), annotation)
+                }).collect::<Vec<_>>().join(", ");
+                if values.is_empty() {
+                    self.line(&format!("local {}", names));
+                } else {
+                    let values = self.expression_list(values);
+                    self.line(&format!("local {} = {}", names, values));
+                }
+            }
+            AstNodes::Assign { targets, values } => {
+                if let Some(compound) = self.compound_assignment(targets, values) {
+                    self.line(&compound);
+                    return;
+                }
+                let targets = self.expression_list(targets);
+                let values = self.expression_list(values);
+                self.line(&format!("{} = {}", targets, values));
+            }
+            AstNodes::Call(call) => {
+                let call = self.expression(call);
+                self.line(&call);
+            }
+            AstNodes::Do(body) => {
+                self.line("do");
+                self.indented(body);
+                self.line("end");
+            }
+            AstNodes::While { condition, body } => {
+                let condition = self.expression(condition);
+                self.line(&format!("while {} do", condition));
+                self.loop_body(body);
+                self.line("end");
+            }
+            AstNodes::Repeat { body, condition } => {
+                self.line("repeat");
+                self.indented(body);
+                let condition = self.expression(condition);
+                self.line(&format!("until {}", condition));
+            }
+            AstNodes::If { condition, body, else_ifs, else_body } => {
+                let condition = self.expression(condition);
+                self.line(&format!("if {} then", condition));
+                self.indented(body);
+                for (condition, body) in else_ifs {
+                    let condition = self.expression(condition);
+                    self.line(&format!("elseif {} then", condition));
+                    self.indented(body);
+                }
+                if let Some(body) = else_body {
+                    self.line("else");
+                    self.indented(body);
+                }
+                self.line("end");
+            }
+            AstNodes::NumericFor { var, start, limit, step, body } => {
+                let mut range = vec![self.expression(start), self.expression(limit)];
+                if let Some(step) = step {
+                    range.push(self.expression(step));
+                }
+                self.line(&format!("for {} = {} do", var.name, range.join(", ")));
+                self.loop_body(body);
+                self.line("end");
+            }
+            AstNodes::GenericFor { names, exprs, body } => {
+                let names = names.iter().map(|name| name.name.as_str()).collect::<Vec<_>>().join(", ");
+                let exprs = self.expression_list(exprs);
+                self.line(&format!("for {} in {} do", names, exprs));
+                self.loop_body(body);
+                self.line("end");
+            }
+            AstNodes::Function(func) => {
+                let name = self.function_name(func);
+                self.function(&format!("function {}", name), func);
+            }
+            AstNodes::LocalFunction(func) => self.function(&format!("local function {}", func.name), func),
+            AstNodes::Return(values) if values.is_empty() => self.line("return"),
+            AstNodes::Return(values) => {
+                let values = self.expression_list(values);
+                self.line(&format!("return {}", values));
+            }
+            AstNodes::Break => self.line("break"),
+            AstNodes::Goto(label) if self.continue_label.as_ref() == Some(label) => self.line("continue"),
+            AstNodes::Goto(label) => self.line(&format!("goto {}", label)),
+            AstNodes::Label(label) => self.line(&format!("::{}::", label)),
+            AstNodes::TypeAlias { name, generics: params, typ } if self.target == Target::Luau => {
+                let alias = format!("type {}{} = {}", name, generics(params, name), luau_type(typ, name));
+                self.line(&alias);
+            }
+            AstNodes::Interface(interface) if self.target == Target::Luau => {
+                let name = &interface.name;
+                let body = Type { kind: Types::Table(interface.body.clone()), span: node.span.clone() };
+                let mut parts: Vec<String> = interface.extends.iter().map(|base| luau_type(base, name)).collect();
+                parts.push(luau_type(&body, name));
+                self.line(&format!("type {}{} = {}", name, generics(&interface.generics, name), parts.join(" & ")));
+            }
+            // only exported types are left after lowering, Luau declares them with `export type`
+            AstNodes::Export(declaration) if self.target == Target::Luau => {
+                let start = self.out.len() + self.depth * INDENT.len();
+                self.node(declaration);
+                if self.out.len() > start {
+                    self.out.insert_str(start, "export ");
+                }
+            }
+            // types are erased, classes, switches, imports and exports are lowered before printing
+            AstNodes::TypeAlias { .. } | AstNodes::Interface(_) | AstNodes::Class(_) | AstNodes::Switch { .. }
+                | AstNodes::Import(_) | AstNodes::Export(_) | AstNodes::ExportDefault(_) => {}
+            // a file that did not parse is never compiled, a printed one runs as far as it can
+            AstNodes::Error => {}
+        }
+    }
+
+    fn function(&mut self, header: &str, func: &FunctionDecl) {
+        self.line(&format!("{}{}", header, self.signature(func)));
+        let saved = self.continue_label.take();
+        self.indented(&func.body);
+        self.continue_label = saved;
+        self.line("end");
+    }
+
+    /// `: T` in Luau, nothing in Lua.
+    fn annotation(&self, typ: Option<&Type>) -> String {
+        match (self.target, typ) {
+            (Target::Luau, Some(typ)) => format!(": {}", luau_type(typ, "any")),
+            _ => String::new(),
+        }
+    }
+
+    /// Luau methods of lowered classes are declared with `.` and an explicitly typed `self`.
+    fn function_name(&self, func: &FunctionDecl) -> String {
+        let mut name = func.path.join(".");
+        if !func.path.is_empty() {
+            let typed_self = self.target == Target::Luau && func.is_class;
+            name.push(if func.is_method && !typed_self { ':' } else { '.' });
+        }
+        name.push_str(&func.name);
+        name
+    }
+
+    /// The parameter list and, in Luau, the return annotation.
+    fn signature(&self, func: &FunctionDecl) -> String {
+        let mut params: Vec<String> = Vec::new();
+        if self.target == Target::Luau && func.is_class && func.is_method {
+            params.push(format!("self: {}", func.path.join(".")));
+        }
+        params.extend(func.args.iter().map(|arg| format!("{}{}", arg.name, self.annotation(arg.typ.as_ref()))));
+        if func.varargs {
+            params.push(format!("...{}", self.annotation(func.varargs_typ.as_ref())));
+        }
+        format!("({}){}", params.join(", "), self.annotation(func.returns.as_ref()))
+    }
+
+    /// `a op= b` for `a = a op b` in Luau, when `a` can be evaluated once.
+    fn compound_assignment(&mut self, targets: &[Expression], values: &[Expression]) -> Option<String> {
+        let ([target], [value]) = (targets, values) else {
+            return None;
+        };
+        let Expressions::Binary { op, lhs, rhs } = &value.kind else {
+            return None;
+        };
+        if self.target != Target::Luau || !op.is_compound() || !is_plain_reference(target) {
+            return None;
+        }
+        let printed = self.expression(target);
+        if self.expression(lhs) != printed {
+            return None;
+        }
+        Some(format!("{} {}= {}", printed, op, self.expression(rhs)))
+    }
+
+    /// A Luau interpolated string for a concatenation mixing string literals and values.
+    fn interpolation(&mut self, expr: &Expression) -> Option<String> {
+        let mut parts = Vec::new();
+        concat_parts(expr, &mut parts);
+        let literals = parts.iter().filter(|part| matches!(part.kind, Expressions::String(_))).count();
+        if literals == 0 || literals == parts.len() {
+            return None;
+        }
+        let mut interpolated = String::from("`");
+        for part in parts {
+            match &part.kind {
+                Expressions::String(s) => interpolated_text(&mut interpolated, s),
+                _ => {
+                    let value = self.expression(part);
+                    // `{{` is rejected by Luau
+                    if value.starts_with('{') {
+                        interpolated.push_str(&format!("{{({})}}", value));
+                    } else {
+                        interpolated.push_str(&format!("{{{}}}", value));
+                    }
+                }
+            }
+        }
+        interpolated.push('`');
+        Some(interpolated)
+    }
+
+    fn expression_list(&mut self, exprs: &[Expression]) -> String {
+        exprs.iter().map(|expr| self.expression(expr)).collect::<Vec<_>>().join(", ")
+    }
+
+    fn prefix(&mut self, expr: &Expression) -> String {
+        let printed = self.expression(expr);
+        if is_prefix_expression(expr) { printed } else { format!("({})", printed) }
+    }
+
+    fn operand(&mut self, parent: BinaryOps, operand: &Expression, is_left: bool) -> String {
+        let printed = self.expression(operand);
+        if needs_parens(parent, operand, is_left) { format!("({})", printed) } else { printed }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> String {
+        match &expr.kind {
+            Expressions::Nil => "nil".to_string(),
+            Expressions::True => "true".to_string(),
+            Expressions::False => "false".to_string(),
+            Expressions::Number(n) => number(*n),
+            Expressions::Float(n) => float(*n),
+            Expressions::String(s) => quote(s),
+            Expressions::Dots => "...".to_string(),
+            Expressions::Error => "nil".to_string(),
+            Expressions::Ident(name) => name.clone(),
+            Expressions::Function(func) => self.function_expression(func),
+            Expressions::Paren(inner) => format!("({})", self.expression(inner)),
+            Expressions::Field { target, name } => format!("{}.{}", self.prefix(target), name),
+            Expressions::Index { target, key } => format!("{}[{}]", self.prefix(target), self.expression(key)),
+            Expressions::Call { target, args } => format!("{}({})", self.prefix(target), self.expression_list(args)),
+            Expressions::MethodCall { target, method, args } => {
+                format!("{}:{}({})", self.prefix(target), method, self.expression_list(args))
+            }
+            Expressions::Binary { op, lhs, rhs } => {
+                // floor division of floats, the only numbers of the older dialects
+                if *op == BinaryOps::IntDiv && !self.target.has_floor_division() {
+                    let left = self.operand(BinaryOps::Div, lhs, true);
+                    let right = self.operand(BinaryOps::Div, rhs, false);
+                    return format!("math.floor({} / {})", left, right);
+                }
+                if let (false, Some(library), Some(function)) = (self.target.has_bitwise_operators(), self.target.bit_library(), bit_function(*op)) {
+                    return format!("{}.{}({}, {})", library, function, self.expression(lhs), self.expression(rhs));
+                }
+                if *op == BinaryOps::Concat && self.target == Target::Luau {
+                    if let Some(interpolated) = self.interpolation(expr) {
+                        return interpolated;
+                    }
+                }
+                let left = self.operand(*op, lhs, true);
+                let right = self.operand(*op, rhs, false);
+                format!("{} {} {}", left, op, right)
+            }
+            Expressions::Unary { op, expr: operand } => {
+                if let (false, Some(library), Some(function)) = (self.target.has_bitwise_operators(), self.target.bit_library(), bit_unary_function(*op)) {
+                    return format!("{}.{}({})", library, function, self.expression(operand));
+                }
+                let mut printed = self.expression(operand);
+                if needs_parens_unary(operand) {
+                    printed = format!("({})", printed);
+                }
+                match op {
+                    UnaryOps::Not => format!("not {}", printed),
+                    // `- -x` would otherwise start a comment
+                    _ if printed.starts_with('-') => format!("{} {}", op, printed),
+                    _ => format!("{}{}", op, printed),
+                }
+            }
+            Expressions::Table(fields) => {
+                let fields: Vec<String> = fields.iter().map(|field| match field {
+                    TableField::Positional(value) => self.expression(value),
+                    TableField::Named(name, value) => format!("{} = {}", name, self.expression(value)),
+                    TableField::Keyed(key, value) => format!("[{}] = {}", self.expression(key), self.expression(value)),
+                }).collect();
+                if fields.is_empty() { "{}".to_string() } else { format!("{{ {} }}", fields.join(", ")) }
+            }
+        }
+    }
+
+    /// Arrow functions stay on one line, other function bodies are printed one level deeper.
+    fn function_expression(&mut self, func: &FunctionDecl) -> String {
+        if let (true, [AstNode { kind: AstNodes::Return(values), .. }]) = (func.is_arrow, func.body.as_slice()) {
+            return format!("function{} return {} end", self.signature(func), self.expression_list(values));
+        }
+        let mut body = LuaEmitter::new(self.depth + 1, self.target, self.comments);
+        body.block(&func.body);
+        let mut printed = format!("function{}\n{}", self.signature(func), body.out);
+        self.bodies.push((body.out, body.segments));
+        for _ in 0..self.depth {
+            printed.push_str(INDENT);
+        }
+        printed.push_str("end");
+        printed
+    }
+}
+
+/// `<const>` is only enforced by the checker on targets without attributes.
+fn local_name(name: &NamedTokenWithTypeInfo, target: Target) -> String {
+    match name.attrib {
+        Some(Attrib::Const) if target.has_attributes() => format!("{} <const>", name.name),
+        Some(Attrib::Close) => format!("{} <close>", name.name),
+        _ => name.name.clone(),
+    }
+}
+
+
+
+#[test]
+fn emit_erased_lua() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        type Id = string | number
+        interface Named
+            name: string
+        end
+        -- a counter
+        class Counter implements Named
+            name: string = "c"
+            constructor(start: number)
+                self.value = start
+            end
+            increment(by: number): number
+                self.value = self.value + by
+                return self.value
+            end
+        end
+        local double = (n: number) => n * 2
+        local function label(id: Id): string
+            switch type(id)
+                case "string" then
+                    return "s:" .. id
+                else
+                    return "n:" .. -(id - 1) .. "\t\"q\""
+            end
+        end
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    assert_eq!(emit_lua(block, Target::Lua54, true), r#"-- a counter
+local Counter = {}
+Counter.__index = Counter
+function Counter.new(start)
+    local self = setmetatable({}, Counter)
+    Counter.constructor(self, start)
+    return self
+end
+function Counter:constructor(start)
+    self.name = "c"
+    self.value = start
+end
+function Counter:increment(by)
+    self.value = self.value + by
+    return self.value
+end
+local double = function(n) return n * 2 end
+local function label(id)
+    do
+        local __switch = type(id)
+        if __switch == "string" then
+            return "s:" .. id
+        else
+            return "n:" .. -(id - 1) .. "\t\"q\""
+        end
+    end
+end
+"#);
+}
+
+#[test]
+fn emit_for_targets() {
+    use crate::parser::{parse, Lexer};
+    let src = "local mask <const> = ~(a & 255) << 2\nlocal half = (a + b) // 2";
+    let emit = |target| emit_lua(parse(Lexer::new(src).collect()).unwrap(), target, false);
+    assert_eq!(emit(Target::Lua54), "local mask <const> = ~(a & 255) << 2\nlocal half = (a + b) // 2\n");
+    assert_eq!(emit(Target::Lua53), "local mask = ~(a & 255) << 2\nlocal half = (a + b) // 2\n");
+    assert_eq!(emit(Target::LuaJIT), "local mask = bit.lshift(bit.bnot((bit.band(a, 255))), 2)\nlocal half = math.floor((a + b) / 2)\n");
+    assert_eq!(emit(Target::Lua52), "local mask = bit32.lshift(bit32.bnot((bit32.band(a, 255))), 2)\nlocal half = math.floor((a + b) / 2)\n");
+}
+
+#[test]
+fn emit_literals_unchanged() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"local values = { 3, 3.0, 0.5, "\xFF\255\65\x41", "caf\u{E9}", 0xFF, 1e3, 0x1p4, [==[a]]b]==] }
+        --[==[ closed by ]] ]=] ]==]
+        print(values)"#;
+    let lua = emit_lua(parse(Lexer::new(src).collect()).unwrap(), Target::Lua54, true);
+    assert_eq!(lua, "local values = { 3, 3.0, 0.5, \"\\255\\255AA\", \"caf\u{e9}\", 255, 1000.0, 16.0, \"a]]b\" }\n--[==[ closed by ]] ]=] ]==]\nprint(values)\n");
+}
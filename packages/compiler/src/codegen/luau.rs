@@ -0,0 +1,188 @@
+use crate::codegen::{escape, quote};
+use crate::codegen::lua::emit;
+use crate::parser::ast::{BinaryOps, Block, Expression, Expressions, FunctionType, GenericParam, LiteralType, Type, Types};
+use crate::target::Target;
+
+/// Lowers a checked file and prints it as Luau, keeping the type annotations Luau can express.
+/// `goto` to a label ending a loop body becomes `continue`, `a = a + b` becomes `a += b` and
+/// concatenations of string literals with values become interpolated strings.
+pub fn emit_luau(block: Block, comments: bool) -> String {
+    emit(block, Target::Luau, comments)
+}
+
+/// A tlua type written as a Luau type, `any` standing in for the ones Luau cannot express.
+/// Method signatures take a `self` of type `self_type`.
+pub fn luau_type(typ: &Type, self_type: &str) -> String {
+    match &typ.kind {
+        Types::Named { name, args } => match name.as_str() {
+            "table" => "{ [any]: any }".to_string(),
+            "function" => "(...any) -> ...any".to_string(),
+            "userdata" => "any".to_string(),
+            _ if args.is_empty() => name.clone(),
+            _ => format!("{}<{}>", name, list(args, self_type)),
+        },
+        Types::Nil => "nil".to_string(),
+        Types::Literal(LiteralType::String(s)) => quote(s),
+        Types::Literal(LiteralType::Boolean(b)) => b.to_string(),
+        // Luau has no numeric singleton types
+        Types::Literal(LiteralType::Number(_)) => "number".to_string(),
+        Types::Union(types) => types.iter().map(|typ| operand(typ, self_type)).collect::<Vec<_>>().join(" | "),
+        Types::Intersection(types) => types.iter().map(|typ| operand(typ, self_type)).collect::<Vec<_>>().join(" & "),
+        Types::Optional(inner) => format!("{}?", operand(inner, self_type)),
+        Types::Array(inner) => format!("{{ {} }}", luau_type(inner, self_type)),
+        Types::Tuple(types) => format!("({})", list(types, self_type)),
+        Types::Variadic(inner) => format!("...{}", luau_type(inner, self_type)),
+        Types::Function(func) => function_type(func, None, self_type),
+        Types::Table(table) => {
+            let mut fields: Vec<String> = table.fields.iter().map(|field| match &field.typ.kind {
+                Types::Function(func) if field.is_method => format!("{}: {}", field.name, function_type(func, Some(self_type), self_type)),
+                _ if field.optional => format!("{}: {}?", field.name, operand(&field.typ, self_type)),
+                _ => format!("{}: {}", field.name, luau_type(&field.typ, self_type)),
+            }).collect();
+            // a Luau table type has at most one indexer
+            if let Some((key, value)) = table.indexers.first() {
+                fields.push(format!("[{}]: {}", luau_type(key, self_type), luau_type(value, self_type)));
+            }
+            if fields.is_empty() { "{}".to_string() } else { format!("{{ {} }}", fields.join(", ")) }
+        }
+        Types::Conditional { .. } => "any".to_string(),
+    }
+}
+
+fn list(types: &[Type], self_type: &str) -> String {
+    types.iter().map(|typ| luau_type(typ, self_type)).collect::<Vec<_>>().join(", ")
+}
+
+/// A member of a union, intersection or optional type, function types need parentheses there.
+fn operand(typ: &Type, self_type: &str) -> String {
+    match &typ.kind {
+        Types::Function(_) | Types::Union(_) | Types::Intersection(_) => format!("({})", luau_type(typ, self_type)),
+        _ => luau_type(typ, self_type),
+    }
+}
+
+fn function_type(func: &FunctionType, method_of: Option<&str>, self_type: &str) -> String {
+    let mut params: Vec<String> = method_of.map(|owner| format!("self: {}", owner)).into_iter().collect();
+    params.extend(func.params.iter().map(|param| {
+        let typ = if param.optional { format!("{}?", operand(&param.typ, self_type)) } else { luau_type(&param.typ, self_type) };
+        match &param.name {
+            Some(name) => format!("{}: {}", name, typ),
+            None => typ,
+        }
+    }));
+    if let Some(varargs) = &func.varargs {
+        params.push(format!("...{}", luau_type(varargs, self_type)));
+    }
+    format!("({}) -> {}", params.join(", "), luau_type(&func.returns, self_type))
+}
+
+/// `<T, U = D>`, constraints are dropped as Luau generics have none.
+pub(super) fn generics(params: &[GenericParam], self_type: &str) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let params: Vec<String> = params.iter().map(|param| match &param.default {
+        Some(default) => format!("{} = {}", param.name, luau_type(default, self_type)),
+        None => param.name.clone(),
+    }).collect();
+    format!("<{}>", params.join(", "))
+}
+
+/// A variable or a field path without calls, evaluating it twice has no side effect.
+pub(super) fn is_plain_reference(expr: &Expression) -> bool {
+    match &expr.kind {
+        Expressions::Ident(_) => true,
+        Expressions::Field { target, .. } => is_plain_reference(target),
+        Expressions::Index { target, key } => is_plain_reference(target)
+            && matches!(key.kind, Expressions::Ident(_) | Expressions::String(_) | Expressions::Number(_) | Expressions::Float(_)),
+        _ => false,
+    }
+}
+
+/// The operands of a chain of `..`, in order.
+pub(super) fn concat_parts<'a>(expr: &'a Expression, parts: &mut Vec<&'a Expression>) {
+    match &expr.kind {
+        Expressions::Binary { op: BinaryOps::Concat, lhs, rhs } => {
+            concat_parts(lhs, parts);
+            concat_parts(rhs, parts);
+        }
+        Expressions::Paren(inner) if matches!(inner.kind, Expressions::Binary { op: BinaryOps::Concat, .. }) => concat_parts(inner, parts),
+        _ => parts.push(expr),
+    }
+}
+
+/// The literal text of an interpolated string.
+pub(super) fn interpolated_text(quoted: &mut String, s: &str) {
+    escape(quoted, s, &['`', '{']);
+}
+
+
+#[test]
+fn emit_luau_code() {
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        type Pair<T = number> = { first: T, second: T? }
+        interface Shape
+            area(): number
+            name?: string
+        end
+        class Square implements Shape
+            side: number = 1
+            area(): number
+                return self.side ^ 2
+            end
+        end
+        local function total(shapes: Shape[], scale: number): string
+            local sum: number = 0
+            for _, shape in ipairs(shapes) do
+                if shape:area() == 0 then
+                    goto continue
+                end
+                sum = sum + shape:area() * scale
+                ::continue::
+            end
+            return "total: " .. sum .. "{}"
+        end
+        local mask = 6 & 3
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    assert_eq!(emit_luau(block, false), r#"type Pair<T = number> = { first: T, second: T? }
+type Shape = { area: (self: Shape) -> number, name: string? }
+type Square = { side: number, area: (self: Square) -> number }
+local Square = {}
+Square.__index = Square
+function Square.new(): Square
+    local self = setmetatable({}, Square)
+    Square.constructor(self)
+    return self
+end
+function Square.constructor(self: Square)
+    self.side = 1
+end
+function Square.area(self: Square): number
+    return self.side ^ 2
+end
+local function total(shapes: { Shape }, scale: number): string
+    local sum: number = 0
+    for _, shape in ipairs(shapes) do
+        if shape:area() == 0 then
+            continue
+        end
+        sum += shape:area() * scale
+    end
+    return `total: {sum}\{}`
+end
+local mask = bit32.band(6, 3)
+"#);
+}
+
+#[test]
+fn keep_compound_assignments() {
+    use crate::parser::{parse_for, parse_recovering, Lexer};
+    let src = "local n = 0\nn *= 2 + 1\n";
+    let (block, errors) = parse_for(Lexer::new(src).collect(), Target::Luau);
+    assert!(errors.is_empty());
+    assert_eq!(emit_luau(block, false), "local n = 0\nn *= 2 + 1\n");
+    let (_, errors) = parse_recovering(Lexer::new(src).collect());
+    assert_eq!(errors[0].message, "Compound assignment '*=' is not available when targeting Lua 5.4, the assigned variable must be repeated.");
+}
@@ -0,0 +1,106 @@
+mod lua;
+mod luau;
+
+pub use lua::{emit_lua, emit_mapped};
+pub use luau::{emit_luau, luau_type};
+
+use crate::parser::ast::{BinaryOps, Expression, Expressions, UNARY_PRIORITY};
+use crate::parser::tokens::{raw_byte, Comment};
+
+pub(crate) const INDENT: &str = "    ";
+
+/// A Lua string literal with the same value, using decimal escapes for control characters and escaped bytes.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    escape(&mut quoted, s, &['"']);
+    quoted.push('"');
+    quoted
+}
+
+/// Appends the contents of a string literal, escaping backslashes, control characters and `special`.
+fn escape(quoted: &mut String, s: &str, special: &[char]) {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            c if special.contains(&c) => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() && (c as u32) < 0x80 => {
+                // a digit right after a short decimal escape would be read as part of it
+                if chars.peek().is_some_and(char::is_ascii_digit) {
+                    quoted.push_str(&format!("\\{:03}", c as u32));
+                } else {
+                    quoted.push_str(&format!("\\{}", c as u32));
+                }
+            }
+            c => match raw_byte(c) {
+                Some(byte) => quoted.push_str(&format!("\\{}", byte)),
+                None => quoted.push(c),
+            },
+        }
+    }
+}
+
+pub fn number(n: f64) -> String {
+    if n.is_nan() {
+        "(0/0)".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "math.huge" } else { "-math.huge" }.to_string()
+    } else if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
+/// A float literal, keeping the decimal point of whole values so that Lua 5.3 and later do not read an integer.
+pub fn float(n: f64) -> String {
+    if n.is_finite() { format!("{:?}", n) } else { number(n) }
+}
+
+pub fn comment(comment: &Comment) -> String {
+    match comment {
+        Comment::SingleLine(text) => format!("--{}", text),
+        Comment::MultiLine(text) => {
+            let closes = |level: &String| format!("{}]", text).contains(&format!("]{}]", level));
+            let level = (0..).map(|level| "=".repeat(level)).find(|level| !closes(level)).unwrap_or_default();
+            format!("--[{}[{}]{}]", level, text, level)
+        }
+    }
+}
+
+/// Whether an expression can be called or indexed without parentheses.
+pub fn is_prefix_expression(expr: &Expression) -> bool {
+    matches!(expr.kind, Expressions::Ident(_) | Expressions::Paren(_) | Expressions::Field { .. }
+        | Expressions::Index { .. } | Expressions::Call { .. } | Expressions::MethodCall { .. })
+}
+
+/// Binding power of an operand, `None` for expressions that never need parentheses.
+fn operand_priority(expr: &Expression) -> Option<(u8, u8)> {
+    match &expr.kind {
+        Expressions::Binary { op, .. } => Some(op.priority()),
+        Expressions::Unary { .. } => Some((UNARY_PRIORITY, UNARY_PRIORITY)),
+        _ => None,
+    }
+}
+
+/// Whether the left or right operand of a binary operator needs parentheses to keep its grouping.
+pub fn needs_parens(parent: BinaryOps, operand: &Expression, is_left: bool) -> bool {
+    let (left, right) = parent.priority();
+    match operand_priority(operand) {
+        Some((_, operand_right)) if is_left => operand_right < left,
+        Some((operand_left, _)) => operand_left <= right,
+        None => false,
+    }
+}
+
+/// Whether the operand of a unary operator needs parentheses.
+pub fn needs_parens_unary(operand: &Expression) -> bool {
+    matches!(&operand.kind, Expressions::Binary { op, .. } if op.priority().0 < UNARY_PRIORITY)
+}
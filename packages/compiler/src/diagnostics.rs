@@ -0,0 +1,493 @@
+use std::fmt::{Display, Formatter};
+use colored::{Color, ColoredString, Colorize};
+use crate::parser::errors::{ErrorKind, ParseError, TypeError};
+use crate::parser::tokens::Span;
+
+mod explain;
+mod fix;
+mod format;
+
+pub use explain::{codes, explain};
+pub use fix::Fixed;
+pub use format::{sarif, DiagnosticFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A span of the source, with what it points out.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    /// Empty for a bare marker.
+    pub message: String,
+}
+
+/// A change to the source, `replacement` in place of the spanned text.
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// A suggested correction, its edits applied together.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub message: String,
+    pub edits: Vec<Edit>,
+}
+
+/// An error or warning about a source file.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The stable code of the diagnostic, `TL2008`.
+    pub code: &'static str,
+    pub message: String,
+    /// Where the problem is.
+    pub primary: Label,
+    /// Related places, such as the construct an unexpected token interrupts.
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub fixes: Vec<Fix>,
+}
+
+impl Diagnostic {
+    pub fn error(kind: &ErrorKind, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: kind.code(),
+            message: kind.to_string(),
+            primary: Label { span, message: String::new() },
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            fixes: Vec::new(),
+        }
+    }
+
+    pub fn warning(kind: &ErrorKind, span: Span) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, ..Diagnostic::error(kind, span) }
+    }
+
+    /// Describes the primary span.
+    pub fn label(mut self, message: &str) -> Diagnostic {
+        self.primary.message = message.to_string();
+        self
+    }
+
+    pub fn secondary(mut self, span: Span, message: &str) -> Diagnostic {
+        self.secondary.push(Label { span, message: message.to_string() });
+        self
+    }
+
+    pub fn note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn help(mut self, help: &str) -> Diagnostic {
+        self.help.push(help.to_string());
+        self
+    }
+
+    pub fn fix(mut self, message: &str, edits: Vec<Edit>) -> Diagnostic {
+        self.fixes.push(Fix { message: message.to_string(), edits });
+        self
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(&ErrorKind::Parse(error.kind), error.at.clone());
+        diagnostic.fixes.extend(error.fix);
+        if error.origin.start < error.at.start {
+            diagnostic.secondary(error.origin, "in this statement")
+        } else {
+            diagnostic
+        }
+    }
+}
+
+impl From<TypeError> for Diagnostic {
+    fn from(error: TypeError) -> Diagnostic {
+        Diagnostic::error(&ErrorKind::Type(error.kind), error.span)
+    }
+}
+
+/// The diagnostics of a source file, rendered only on request.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub path: String,
+    pub source: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(path: &str, source: &str) -> Diagnostics {
+        Diagnostics { path: path.to_string(), source: source.to_string(), diagnostics: Vec::new() }
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// Every diagnostic of the file, in order, as text for a terminal:
+    ///
+    /// ```text
+    /// error[TL2008]: Type 'string' is not assignable to type 'number'.
+    ///  --> main.tlua:2:19
+    ///   |
+    /// 1 | local name = "tlua"
+    /// 2 | local n: number = name
+    ///   |                   ^^^^ this is a string
+    ///   |
+    ///   = help: convert it with `tonumber`
+    /// ```
+    pub fn render(&self) -> String {
+        let lines = Lines::new(&self.source);
+        self.iter().map(|diagnostic| self.render_one(diagnostic, &lines)).collect()
+    }
+
+    fn render_one(&self, diagnostic: &Diagnostic, lines: &Lines) -> String {
+        let marks: Vec<Mark> = std::iter::once((&diagnostic.primary, true))
+            .chain(diagnostic.secondary.iter().map(|label| (label, false)))
+            .map(|(label, primary)| lines.mark(label, primary))
+            .collect();
+        let shown = lines.shown(&marks);
+        let width = (shown.last().copied().unwrap_or(0) + 1).to_string().len();
+        let multi_line = marks.iter().any(|mark| mark.start.0 != mark.last.0);
+        let gutter = |line: Option<usize>| {
+            let number = line.map_or(String::new(), |line| (line + 1).to_string());
+            format!("{:>width$} |", number, width = width).blue().bold()
+        };
+
+        let (severity, color) = match diagnostic.severity {
+            Severity::Warning => ("warning", Color::Yellow),
+            Severity::Error => ("error", Color::Red),
+        };
+        let mut out = format!("{}{}\n", format!("{}[{}]", severity, diagnostic.code).color(color).bold(), format!(": {}", diagnostic.message).bold());
+        let (line, column) = lines.header_position(diagnostic.primary.span.start);
+        out.push_str(&format!("{:width$}{} {}:{}:{}\n", "", "-->".blue().bold(), self.path, line, column, width = width));
+        out.push_str(&format!("{}\n", gutter(None)));
+        let mut previous: Option<usize> = None;
+        for &line in &shown {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                out.push_str(&format!("{}\n", "...".blue().bold()));
+            }
+            previous = Some(line);
+            let margin = |line: usize| match marks.iter().find(|mark| mark.start.0 < line && line <= mark.last.0) {
+                Some(mark) => format!("{} ", paint("|", mark.primary)),
+                None if multi_line => "  ".to_string(),
+                None => String::new(),
+            };
+            let row = format!("{} {}{}", gutter(Some(line)), margin(line), expand(lines.text(line)));
+            out.push_str(row.trim_end());
+            out.push('\n');
+            let mut on_line: Vec<&Mark> = marks.iter().filter(|mark| mark.start.0 == line && mark.last.0 == line).collect();
+            on_line.sort_by_key(|mark| mark.start.1);
+            for mark in on_line {
+                let underline = format!("{}{}", " ".repeat(mark.start.1), paint(&mark.marker().repeat(mark.end - mark.start.1), mark.primary));
+                out.push_str(&annotation(&gutter(None), &format!("{}{}", margin(line), underline), mark));
+            }
+            for mark in marks.iter().filter(|mark| mark.last.0 == line && mark.start.0 < line) {
+                let underline = format!("{}{}", paint(&"_".repeat(mark.last.1 + 1), mark.primary), paint(mark.marker(), mark.primary));
+                out.push_str(&annotation(&gutter(None), &format!("{}{}", paint("|", mark.primary), underline), mark));
+            }
+            for mark in marks.iter().filter(|mark| mark.start.0 == line && mark.last.0 > line) {
+                let underline = format!("{}{}", paint(&"_".repeat(mark.start.1 + 1), mark.primary), paint(mark.marker(), mark.primary));
+                out.push_str(&format!("{}  {}\n", gutter(None), underline));
+            }
+        }
+        if !diagnostic.notes.is_empty() || !diagnostic.help.is_empty() || !diagnostic.fixes.is_empty() {
+            out.push_str(&format!("{}\n", gutter(None)));
+        }
+        for note in &diagnostic.notes {
+            out.push_str(&format!("{:width$} {} {}\n", "", "= note:".bold(), note, width = width));
+        }
+        // fixes are applied with `--fix`, their description is all the terminal shows
+        for help in diagnostic.help.iter().chain(diagnostic.fixes.iter().map(|fix| &fix.message)) {
+            out.push_str(&format!("{:width$} {} {}\n", "", "= help:".bold(), help, width = width));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+/// Lines shown around the labelled ones.
+const CONTEXT: usize = 1;
+/// Lines of a multi-line label shown at each of its ends, the ones between are elided.
+const SPAN_LINES: usize = 2;
+/// Tabs are shown as four spaces.
+const TAB: &str = "    ";
+
+/// A label resolved to 0-based lines and display columns.
+struct Mark<'a> {
+    label: &'a Label,
+    primary: bool,
+    start: (usize, usize),
+    /// The line and column of the last character of the span.
+    last: (usize, usize),
+    /// The column after the last character.
+    end: usize,
+}
+
+impl Mark<'_> {
+    fn marker(&self) -> &'static str {
+        if self.primary { "^" } else { "-" }
+    }
+}
+
+/// The lines of a source, to find the display position of a byte offset.
+struct Lines<'a> {
+    source: &'a str,
+    /// The byte offset each line starts at.
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(source: &'a str) -> Lines<'a> {
+        let starts = std::iter::once(0).chain(source.match_indices('\n').map(|(ix, _)| ix + 1)).collect();
+        Lines { source, starts }
+    }
+
+    /// The offset clamped to the source, at a character boundary.
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    fn text(&self, line: usize) -> &'a str {
+        let end = self.starts.get(line + 1).map_or(self.source.len(), |&next| next - 1);
+        self.source[self.starts[line]..end].trim_end_matches('\r')
+    }
+
+    /// The 0-based line and display column of an offset.
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        (line, self.source[self.starts[line]..offset].chars().map(char_width).sum())
+    }
+
+    /// The 1-based line and character column of an offset.
+    fn header_position(&self, offset: usize) -> (usize, usize) {
+        let offset = self.clamp(offset);
+        let line = self.line(offset);
+        (line + 1, self.source[self.starts[line]..offset].chars().count() + 1)
+    }
+
+    fn mark<'l>(&self, label: &'l Label, primary: bool) -> Mark<'l> {
+        let start = self.clamp(label.span.start);
+        let end = self.clamp(label.span.end).max(start);
+        // an empty span marks the character at its start
+        let last = self.source[..end].char_indices().next_back().map_or(start, |(ix, _)| ix).max(start);
+        let width = self.source[last..].chars().next().map_or(1, |c| char_width(c).max(1));
+        let last_position = self.position(last);
+        Mark { label, primary, start: self.position(start), last: last_position, end: last_position.1 + width }
+    }
+
+    /// The lines shown for the marks, in order: the marked ones, the ends of multi-line marks and the context
+    /// around them.
+    fn shown(&self, marks: &[Mark]) -> Vec<usize> {
+        let mut shown = std::collections::BTreeSet::new();
+        for mark in marks {
+            let (first, last) = (mark.start.0, mark.last.0);
+            shown.extend((first..=last).filter(|line| line - first < SPAN_LINES || last - line < SPAN_LINES));
+        }
+        let (Some(&first), Some(&last)) = (shown.first(), shown.last()) else {
+            return Vec::new();
+        };
+        let context = first.saturating_sub(CONTEXT)..first;
+        let after = last + 1..(last + 1 + CONTEXT).min(self.starts.len());
+        shown.extend(context.chain(after).filter(|&line| !self.text(line).trim().is_empty()));
+        // a single elided line is shown rather than the `...` standing for it
+        let gaps: Vec<usize> = shown.iter().zip(shown.iter().skip(1))
+            .filter(|(line, next)| **next == **line + 2)
+            .map(|(line, _)| line + 1)
+            .collect();
+        shown.extend(gaps);
+        shown.into_iter().collect()
+    }
+}
+
+/// The row under a source line marking a label, with its message.
+fn annotation(gutter: &ColoredString, underline: &str, mark: &Mark) -> String {
+    if mark.label.message.is_empty() {
+        format!("{} {}\n", gutter, underline)
+    } else {
+        format!("{} {} {}\n", gutter, underline, paint(&mark.label.message, mark.primary))
+    }
+}
+
+/// Primary labels are red, secondary ones blue.
+fn paint(text: &str, primary: bool) -> ColoredString {
+    text.color(if primary { Color::Red } else { Color::Blue }).bold()
+}
+
+fn expand(text: &str) -> String {
+    text.replace('\t', TAB)
+}
+
+/// The columns a character takes in a terminal: none for combining marks, two for wide ones such as CJK.
+fn char_width(c: char) -> usize {
+    match c {
+        '\t' => TAB.len(),
+        '\u{0300}'..='\u{036F}' | '\u{200B}'..='\u{200F}' | '\u{FE00}'..='\u{FE0F}' => 0,
+        '\u{1100}'..='\u{115F}' | '\u{2E80}'..='\u{A4CF}' | '\u{AC00}'..='\u{D7A3}' | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}' | '\u{FF00}'..='\u{FF60}' | '\u{FFE0}'..='\u{FFE6}'
+            | '\u{1F300}'..='\u{1F64F}' | '\u{1F900}'..='\u{1F9FF}' | '\u{20000}'..='\u{3FFFD}' => 2,
+        c if c.is_control() => 0,
+        _ => 1,
+    }
+}
+
+impl<'a> IntoIterator for &'a Diagnostics {
+    type Item = &'a Diagnostic;
+    type IntoIter = std::slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Why compilation stopped.
+#[derive(Debug)]
+pub enum CompileError {
+    Io(std::io::Error),
+    /// The diagnostics of every file with one, at least one of them an error.
+    Failed(Vec<Diagnostics>),
+}
+
+impl CompileError {
+    /// The diagnostics as text for a terminal.
+    pub fn render(&self) -> String {
+        self.render_as(DiagnosticFormat::Human)
+    }
+
+    /// The diagnostics in the given format. An I/O error is a line of text whatever the format.
+    pub fn render_as(&self, format: DiagnosticFormat) -> String {
+        match self {
+            CompileError::Io(error) => format!("{}\n", error),
+            CompileError::Failed(files) => format.render(files),
+        }
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Io(error) => write!(f, "{}", error),
+            CompileError::Failed(files) => {
+                let errors = files.iter().flatten().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+                write!(f, "Compilation failed with {} error(s).", errors)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<std::io::Error> for CompileError {
+    fn from(error: std::io::Error) -> CompileError {
+        CompileError::Io(error)
+    }
+}
+
+
+#[test]
+fn collect_diagnostics() {
+    use crate::parser::{parse, Lexer};
+    let src = "local x = 1\nif x then\n    print(x)\n";
+    let errors = parse(Lexer::new(src).collect()).unwrap_err();
+    let mut diagnostics = Diagnostics::new("main.tlua", src);
+    errors.into_iter().for_each(|diagnostic| diagnostics.push(diagnostic));
+    let diagnostic = diagnostics.iter().next().unwrap();
+    assert_eq!((diagnostic.severity, diagnostic.code), (Severity::Error, "TL1006"));
+    assert!(diagnostics.has_errors());
+    colored::control::set_override(false);
+    assert_eq!(diagnostics.render(), "error[TL1006]: Unexpected end of file.\n --> main.tlua:3:12\n  |\n1 | local x = 1\n2 | if x then\n  | -- in this statement\n3 |     print(x)\n  |            ^\n  |\n  = help: insert the missing `end`\n\n");
+    let error = CompileError::Failed(vec![diagnostics]);
+    assert_eq!(error.to_string(), "Compilation failed with 1 error(s).");
+}
+
+
+#[test]
+fn render_labels() {
+    use crate::parser::errors::TypeErrors;
+    colored::control::set_override(false);
+    use crate::parser::tokens::{Token, Tokens};
+    use crate::parser::Lexer;
+    let src = "local name = \"t\u{e9}l\u{fc}a\"\nlocal n: number = name\n\tlocal t = {\n  a = 1,\n  b = 2,\n  c = 3,\n  d = 4,\n}\nlocal s = \"\u{540d}\u{524d}\" .. t\n";
+    let tokens: Vec<Token> = Lexer::new(src).collect();
+    let span = |kind: Tokens, nth: usize| tokens.iter().filter(|token| token.kind == kind).nth(nth).unwrap().span.clone();
+    let mismatch = ErrorKind::Type(TypeErrors::TypeMismatch("string".to_string(), "number".to_string()));
+    let mut diagnostics = Diagnostics::new("main.tlua", src);
+    let name = Tokens::Ident("name".to_string());
+    diagnostics.push(Diagnostic::error(&mismatch, span(name.clone(), 1)).label("this is a string")
+        .secondary(span(name, 0), "declared here")
+        .help("convert it with `tonumber`"));
+    let table = Span { start: span(Tokens::LCurly, 0).start, end: span(Tokens::RCurly, 0).end };
+    diagnostics.push(Diagnostic::error(&mismatch, table).label("this table"));
+    diagnostics.push(Diagnostic::error(&mismatch, span(Tokens::Ident("t".to_string()), 1)).label("a table")
+        .secondary(span(Tokens::String("\u{540d}\u{524d}".to_string()), 0), "wide"));
+    assert_eq!(diagnostics.render(), [
+        "error[TL2008]: Type 'string' is not assignable to type 'number'.",
+        " --> main.tlua:2:19",
+        "  |",
+        "1 | local name = \"t\u{e9}l\u{fc}a\"",
+        "  |       ---- declared here",
+        "2 | local n: number = name",
+        "  |                   ^^^^ this is a string",
+        "3 |     local t = {",
+        "  |",
+        "  = help: convert it with `tonumber`",
+        "",
+        "error[TL2008]: Type 'string' is not assignable to type 'number'.",
+        " --> main.tlua:3:12",
+        "  |",
+        "2 |   local n: number = name",
+        "3 |       local t = {",
+        "  |  _______________^",
+        "4 | |   a = 1,",
+        "...",
+        "7 | |   d = 4,",
+        "8 | | }",
+        "  | |_^ this table",
+        "9 |   local s = \"\u{540d}\u{524d}\" .. t",
+        "",
+        "error[TL2008]: Type 'string' is not assignable to type 'number'.",
+        " --> main.tlua:9:19",
+        "  |",
+        "8 | }",
+        "9 | local s = \"\u{540d}\u{524d}\" .. t",
+        "  |           ------ wide",
+        "  |                     ^ a table",
+        "",
+        "",
+    ].join("\n"));
+}
@@ -0,0 +1,109 @@
+/// Long-form explanations of the diagnostic codes, each with an erroneous example and its correction.
+macro_rules! explanations {
+    ($($code:literal),* $(,)?) => {
+        const EXPLANATIONS: &[(&str, &str)] = &[$(($code, include_str!(concat!("explanations/", $code, ".md")))),*];
+    };
+}
+
+explanations![
+    "TL1001",
+    "TL1002",
+    "TL1003",
+    "TL1004",
+    "TL1005",
+    "TL1006",
+    "TL1007",
+    "TL1008",
+    "TL1009",
+    "TL1010",
+    "TL1011",
+    "TL1012",
+    "TL1013",
+    "TL2001",
+    "TL2002",
+    "TL2003",
+    "TL2004",
+    "TL2005",
+    "TL2006",
+    "TL2007",
+    "TL2008",
+    "TL2009",
+    "TL2010",
+    "TL2011",
+    "TL2012",
+    "TL2013",
+    "TL2014",
+    "TL2015",
+    "TL2016",
+    "TL2017",
+    "TL2018",
+    "TL2019",
+    "TL2020",
+    "TL2021",
+    "TL2022",
+    "TL2023",
+    "TL2024",
+    "TL2025",
+    "TL2026",
+    "TL2027",
+    "TL2028",
+    "TL2029",
+    "TL3001",
+    "TL3002",
+    "TL3003",
+    "TL4001",
+    "TL4002",
+    "TL4003",
+    "TL4004",
+    "TL4005",
+    "TL4006",
+];
+
+/// The explanation of a diagnostic code such as `TL1001`, in Markdown. Codes are case-insensitive.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code.trim()))
+        .map(|(_, explanation)| *explanation)
+}
+
+/// Every code with an explanation, in order.
+pub fn codes() -> impl Iterator<Item = &'static str> {
+    EXPLANATIONS.iter().map(|(code, _)| *code)
+}
+
+
+#[test]
+fn explain_every_code() {
+    use crate::checker::Checker;
+    use crate::lint::{lint, LintLevels};
+    use crate::parser::{parse_recovering, Lexer};
+    use crate::parser::tokens::Token;
+    use crate::target::Target;
+    assert_eq!(codes().count(), 51);
+    assert_eq!(explain("tl2008").map(|text| text.lines().next()), Some(Some("A value is not assignable to the type it is given.")));
+    assert!(explain("TL9999").is_none());
+    // the examples of a single file report their code, and only the erroneous one. TL1002 is reported as TL1004
+    // at the start of a file, TL2004 needs more nested aliases than an example has and bundle errors need several files.
+    let diagnose = |code: &str, source: &str| {
+        let target = if source.starts_with("-- targeting Lua 5.1") { Target::Lua51 } else { Target::Lua54 };
+        let tokens: Vec<Token> = Lexer::new(source).collect();
+        let (block, errors) = parse_recovering(tokens.clone());
+        let mut checker = Checker::new(["TL2015", "TL2016", "TL2017", "TL2018"].contains(&code)).target(target);
+        checker.check(&block);
+        let mut codes: Vec<&str> = errors.iter().map(|error| error.code).collect();
+        codes.extend(checker.errors.iter().map(|error| error.kind.code()));
+        if code.starts_with("TL4") {
+            codes.extend(lint(&block, source, &tokens, &LintLevels::default()).iter().map(|warning| warning.code));
+        }
+        codes
+    };
+    for code in codes().filter(|code| !["TL1002", "TL2004", "TL2021", "TL3001", "TL3002", "TL3003"].contains(code)) {
+        let explanation = explain(code).unwrap();
+        let examples: Vec<&str> = explanation.split("```tlua\n").skip(1).map(|block| &block[..block.find("```").unwrap()]).collect();
+        let [bad, good] = examples[..] else {
+            panic!("{} has an erroneous and a corrected example", code);
+        };
+        assert!(diagnose(code, bad).contains(&code), "{}: {:?}", code, diagnose(code, bad));
+        assert_eq!(diagnose(code, good), Vec::<&str>::new(), "{}", code);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::diagnostics::{Diagnostics, Edit, Fix};
+
+/// A source file with the fixes of its diagnostics applied.
+#[derive(Debug, Clone)]
+pub struct Fixed {
+    pub path: String,
+    pub source: String,
+    /// How many fixes were applied.
+    pub applied: usize,
+    /// Fixes left out because their edits overlap those of an earlier fix, or each other.
+    pub skipped: Vec<Fix>,
+}
+
+impl Diagnostics {
+    /// Applies the fixes of the diagnostics to the source, in order. A fix whose edits overlap the edits of
+    /// one applied before is refused whole, the source it would change being ambiguous; checking the fixed
+    /// source again suggests it anew if it is still needed.
+    pub fn apply_fixes(&self) -> Fixed {
+        let mut accepted: Vec<&Edit> = Vec::new();
+        let mut skipped = Vec::new();
+        let mut applied = 0;
+        for fix in self.iter().flat_map(|diagnostic| &diagnostic.fixes) {
+            let valid = fix.edits.iter().enumerate().all(|(ix, edit)| {
+                edit.span.start <= edit.span.end && self.source.get(edit.span.start..edit.span.end).is_some()
+                    && !fix.edits[..ix].iter().chain(accepted.iter().copied()).any(|other| overlaps(edit, other))
+            });
+            if valid {
+                accepted.extend(&fix.edits);
+                applied += 1;
+            } else {
+                skipped.push(fix.clone());
+            }
+        }
+        // from the end of the source, so that the spans of the edits left to apply stay valid
+        accepted.sort_by_key(|edit| std::cmp::Reverse(edit.span.start));
+        let mut source = self.source.clone();
+        for edit in accepted {
+            source.replace_range(edit.span.start..edit.span.end, &edit.replacement);
+        }
+        Fixed { path: self.path.clone(), source, applied, skipped }
+    }
+}
+
+/// Whether two edits touch the same text. Insertions at the same place overlap too, their order being unknown.
+fn overlaps(a: &Edit, b: &Edit) -> bool {
+    a.span.start == b.span.start || a.span.start < b.span.end && b.span.start < a.span.end
+}
+
+
+#[test]
+fn apply_fixes() {
+    use crate::diagnostics::Diagnostic;
+    use crate::parser::errors::{ErrorKind, ParseErrors};
+    use crate::parser::tokens::Span;
+    use crate::parser::{parse_recovering, Lexer};
+    let fix = |source: &str| {
+        let mut diagnostics = Diagnostics::new("main.tlua", source);
+        parse_recovering(Lexer::new(source).collect()).1.into_iter().for_each(|diagnostic| diagnostics.push(diagnostic));
+        diagnostics.apply_fixes()
+    };
+    let fixed = fix("local a = 1\nif a != 2\n    a *= a + 1\n    t.n ..= \"x\"\n    while a > 0\n        a -= 1\n    end\n");
+    assert_eq!(fixed.source, "local a = 1\nif a ~= 2 then\n    a = a * (a + 1)\n    t.n = t.n .. \"x\"\n    while a > 0 do\n        a = a - 1\n    end\nend\n");
+    assert_eq!((fixed.applied, fixed.skipped.len()), (7, 0));
+    assert!(parse_recovering(Lexer::new(&fixed.source).collect()).1.is_empty());
+    // a field of a call cannot be repeated, the error stands without a fix
+    assert_eq!(fix("f().n += 1\n").applied, 0);
+
+    let edit = |start, end, replacement: &str| Edit { span: Span { start, end }, replacement: replacement.to_string() };
+    let mut diagnostics = Diagnostics::new("main.tlua", "a != b");
+    diagnostics.push(Diagnostic::error(&ErrorKind::Parse(ParseErrors::NotEqualOperator), Span { start: 2, end: 4 })
+        .fix("replace", vec![edit(2, 4, "~=")])
+        .fix("overlapping", vec![edit(3, 4, "")])
+        .fix("inserted twice", vec![edit(0, 0, "x"), edit(0, 0, "y")]));
+    let fixed = diagnostics.apply_fixes();
+    assert_eq!(fixed.source, "a ~= b");
+    assert_eq!(fixed.skipped.iter().map(|fix| fix.message.as_str()).collect::<Vec<_>>(), ["overlapping", "inserted twice"]);
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+use crate::diagnostics::{Diagnostic, Diagnostics, Lines, Severity};
+use crate::json;
+use crate::parser::tokens::Span;
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiagnosticFormat {
+    /// Source snippets for a terminal, see [`Diagnostics::render`].
+    #[default]
+    Human,
+    /// A JSON object per line and diagnostic, see [`Diagnostics::to_json_lines`].
+    JsonLines,
+    /// A SARIF 2.1.0 log of every file, see [`sarif`].
+    Sarif,
+}
+
+impl DiagnosticFormat {
+    pub fn render(self, files: &[Diagnostics]) -> String {
+        match self {
+            DiagnosticFormat::Human => files.iter().map(Diagnostics::render).collect(),
+            DiagnosticFormat::JsonLines => files.iter().map(Diagnostics::to_json_lines).collect(),
+            DiagnosticFormat::Sarif => sarif(files),
+        }
+    }
+}
+
+impl Diagnostics {
+    /// Every diagnostic of the file as a line of JSON, positions 1-based with columns in characters and
+    /// ranges ending after their last character:
+    ///
+    /// ```json
+    /// {"code": "TL2008", "severity": "error", "message": "...", "path": "main.tlua",
+    ///  "range": {"start": {"line": 2, "column": 19}, "end": {"line": 2, "column": 23}},
+    ///  "labels": [{"message": "...", "range": {...}}], "notes": [], "help": [],
+    ///  "fixes": [{"message": "...", "edits": [{"range": {...}, "replacement": "~="}]}]}
+    /// ```
+    pub fn to_json_lines(&self) -> String {
+        let lines = Lines::new(&self.source);
+        self.iter().map(|diagnostic| format!("{}\n", self.json(diagnostic, &lines))).collect()
+    }
+
+    fn json(&self, diagnostic: &Diagnostic, lines: &Lines) -> String {
+        let labels: Vec<String> = std::iter::once(&diagnostic.primary).chain(&diagnostic.secondary)
+            .filter(|label| !label.message.is_empty())
+            .map(|label| format!("{{\"message\": {}, \"range\": {}}}", json::string(&label.message), json_range(lines, &label.span)))
+            .collect();
+        let fixes: Vec<String> = diagnostic.fixes.iter().map(|fix| {
+            let edits: Vec<String> = fix.edits.iter()
+                .map(|edit| format!("{{\"range\": {}, \"replacement\": {}}}", json_range(lines, &edit.span), json::string(&edit.replacement)))
+                .collect();
+            format!("{{\"message\": {}, \"edits\": [{}]}}", json::string(&fix.message), edits.join(", "))
+        }).collect();
+        format!("{{\"code\": {}, \"severity\": {}, \"message\": {}, \"path\": {}, \"range\": {}, \"labels\": [{}], \"notes\": {}, \"help\": {}, \"fixes\": [{}]}}",
+            json::string(diagnostic.code), json::string(severity(diagnostic)), json::string(&diagnostic.message), json::string(&self.path),
+            json_range(lines, &diagnostic.primary.span), labels.join(", "), json::string_array(&diagnostic.notes), json::string_array(&diagnostic.help),
+            fixes.join(", "))
+    }
+}
+
+/// A SARIF 2.1.0 log of the diagnostics of every file, for code scanning tools. Each code is a rule,
+/// secondary labels are related locations and fixes replace regions of their file.
+pub fn sarif(files: &[Diagnostics]) -> String {
+    let codes: BTreeSet<&str> = files.iter().flatten().map(|diagnostic| diagnostic.code).collect();
+    let rules: Vec<String> = codes.iter().map(|code| format!("{{\"id\": {}}}", json::string(code))).collect();
+    let results: Vec<String> = files.iter().flat_map(|file| {
+        let lines = Lines::new(&file.source);
+        file.iter().map(move |diagnostic| sarif_result(file, diagnostic, &lines)).collect::<Vec<_>>()
+    }).collect();
+    format!("{{\"$schema\": \"https://json.schemastore.org/sarif-2.1.0.json\", \"version\": \"2.1.0\", \"runs\": [{{\"tool\": {{\"driver\": {{\"name\": \"tluac\", \"rules\": [{}]}}}}, \"columnKind\": \"unicodeCodePoints\", \"results\": [{}]}}]}}\n",
+        rules.join(", "), results.join(", "))
+}
+
+fn sarif_result(file: &Diagnostics, diagnostic: &Diagnostic, lines: &Lines) -> String {
+    let uri = json::string(&file.path.replace('\\', "/"));
+    let location = |span: &Span| format!("{{\"artifactLocation\": {{\"uri\": {}}}, \"region\": {}}}", uri, sarif_region(lines, span));
+    let related: Vec<String> = diagnostic.secondary.iter().enumerate()
+        .map(|(ix, label)| format!("{{\"id\": {}, \"physicalLocation\": {}, \"message\": {{\"text\": {}}}}}", ix, location(&label.span), json::string(&label.message)))
+        .collect();
+    let fixes: Vec<String> = diagnostic.fixes.iter().map(|fix| {
+        let replacements: Vec<String> = fix.edits.iter()
+            .map(|edit| format!("{{\"deletedRegion\": {}, \"insertedContent\": {{\"text\": {}}}}}", sarif_region(lines, &edit.span), json::string(&edit.replacement)))
+            .collect();
+        format!("{{\"description\": {{\"text\": {}}}, \"artifactChanges\": [{{\"artifactLocation\": {{\"uri\": {}}}, \"replacements\": [{}]}}]}}",
+            json::string(&fix.message), uri, replacements.join(", "))
+    }).collect();
+    // the text of a SARIF message is plain, notes and help follow the message on their own lines
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    for help in &diagnostic.help {
+        message.push_str(&format!("\nhelp: {}", help));
+    }
+    format!("{{\"ruleId\": {}, \"level\": {}, \"message\": {{\"text\": {}}}, \"locations\": [{{\"physicalLocation\": {}}}], \"relatedLocations\": [{}], \"fixes\": [{}]}}",
+        json::string(diagnostic.code), json::string(severity(diagnostic)), json::string(&message), location(&diagnostic.primary.span),
+        related.join(", "), fixes.join(", "))
+}
+
+/// `error` or `warning`, as both formats name them.
+fn severity(diagnostic: &Diagnostic) -> &'static str {
+    match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
+
+fn json_range(lines: &Lines, span: &Span) -> String {
+    let ((line, column), (end_line, end_column)) = (lines.header_position(span.start), lines.header_position(span.end.max(span.start)));
+    format!("{{\"start\": {{\"line\": {}, \"column\": {}}}, \"end\": {{\"line\": {}, \"column\": {}}}}}", line, column, end_line, end_column)
+}
+
+fn sarif_region(lines: &Lines, span: &Span) -> String {
+    let ((line, column), (end_line, end_column)) = (lines.header_position(span.start), lines.header_position(span.end.max(span.start)));
+    format!("{{\"startLine\": {}, \"startColumn\": {}, \"endLine\": {}, \"endColumn\": {}}}", line, column, end_line, end_column)
+}
+
+
+#[test]
+fn machine_readable_diagnostics() {
+    use crate::diagnostics::Edit;
+    use crate::parser::errors::{ErrorKind, TypeErrors};
+    let src = "local a = 1\nif a != 2 then\n    print(\"é\", b)\nend\n";
+    let mut diagnostics = Diagnostics::new("src\\main.tlua", src);
+    let op = src.find("!=").unwrap();
+    let span = Span { start: op, end: op + 2 };
+    diagnostics.push(Diagnostic::error(&ErrorKind::Type(TypeErrors::UnknownName("!=".to_string())), span.clone())
+        .label("not a Lua operator")
+        .secondary(Span { start: 12, end: 14 }, "in this condition")
+        .help("Lua writes inequality as `~=`")
+        .fix("replace with `~=`", vec![Edit { span, replacement: "~=".to_string() }]));
+    let b = src.find("b)").unwrap();
+    let mut warning = Diagnostic::error(&ErrorKind::Type(TypeErrors::UnknownName("b".to_string())), Span { start: b, end: b + 1 });
+    warning.severity = Severity::Warning;
+    diagnostics.push(warning);
+    let json = diagnostics.to_json_lines();
+    assert_eq!(json.lines().count(), 2);
+    assert_eq!(json.lines().next(), Some(concat!(
+        r#"{"code": "TL2017", "severity": "error", "message": "Cannot find name '!='.", "path": "src\\main.tlua", "#,
+        r#""range": {"start": {"line": 2, "column": 6}, "end": {"line": 2, "column": 8}}, "#,
+        r#""labels": [{"message": "not a Lua operator", "range": {"start": {"line": 2, "column": 6}, "end": {"line": 2, "column": 8}}}, "#,
+        r#"{"message": "in this condition", "range": {"start": {"line": 2, "column": 1}, "end": {"line": 2, "column": 3}}}], "#,
+        r#""notes": [], "help": ["Lua writes inequality as `~=`"], "#,
+        r#""fixes": [{"message": "replace with `~=`", "edits": [{"range": {"start": {"line": 2, "column": 6}, "end": {"line": 2, "column": 8}}, "replacement": "~="}]}]}"#,
+    )));
+    // columns count characters, not bytes
+    assert!(json.lines().nth(1).unwrap().contains(r#""severity": "warning", "message": "Cannot find name 'b'.", "path": "src\\main.tlua", "range": {"start": {"line": 3, "column": 16}"#));
+
+    let log = sarif(&[diagnostics]);
+    assert!(log.starts_with(r#"{"$schema": "https://json.schemastore.org/sarif-2.1.0.json", "version": "2.1.0", "runs": [{"tool": {"driver": {"name": "tluac", "rules": [{"id": "TL2017"}]}}"#));
+    assert!(log.contains(r#""ruleId": "TL2017", "level": "error", "message": {"text": "Cannot find name '!='.\nhelp: Lua writes inequality as `~=`"}, "locations": [{"physicalLocation": {"artifactLocation": {"uri": "src/main.tlua"}, "region": {"startLine": 2, "startColumn": 6, "endLine": 2, "endColumn": 8}}}]"#));
+    assert!(log.contains(r#""fixes": [{"description": {"text": "replace with `~=`"}, "artifactChanges": [{"artifactLocation": {"uri": "src/main.tlua"}, "replacements": [{"deletedRegion": {"startLine": 2, "startColumn": 6, "endLine": 2, "endColumn": 8}, "insertedContent": {"text": "~="}}]}]}]"#));
+    assert!(log.contains(r#""ruleId": "TL2017", "level": "warning""#));
+}
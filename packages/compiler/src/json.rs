@@ -0,0 +1,71 @@
+//! Writing the JSON of diagnostics, name maps and source maps, and reading source maps back.
+
+/// A JSON string literal.
+pub fn string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A JSON array of strings.
+pub fn string_array(strings: &[String]) -> String {
+    let strings: Vec<String> = strings.iter().map(|s| string(s)).collect();
+    format!("[{}]", strings.join(", "))
+}
+
+/// The raw value of a top-level field of a JSON object, up to the end of the object.
+pub fn field<'a>(json: &'a str, name: &str) -> Option<&'a str> {
+    let key = json.find(&string(name))?;
+    let value = json[key + name.len() + 2..].trim_start().strip_prefix(':')?;
+    Some(value.trim_start())
+}
+
+/// The strings of a JSON string or array of strings, up to its end.
+pub fn strings(value: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut chars = value.chars();
+    let single = value.starts_with('"');
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut s = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('n') => s.push('\n'),
+                            Some('r') => s.push('\r'),
+                            Some('t') => s.push('\t'),
+                            Some('u') => {
+                                let code: String = chars.by_ref().take(4).collect();
+                                s.extend(u32::from_str_radix(&code, 16).ok().and_then(char::from_u32));
+                            }
+                            Some(c) => s.push(c),
+                            None => break,
+                        },
+                        c => s.push(c),
+                    }
+                }
+                strings.push(s);
+                if single {
+                    break;
+                }
+            }
+            ']' => break,
+            _ => {}
+        }
+    }
+    strings
+}
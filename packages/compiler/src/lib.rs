@@ -1,11 +1,82 @@
 
 
-mod parser;
+pub mod parser;
+pub mod diagnostics;
+pub mod checker;
+pub mod lower;
+pub mod codegen;
+pub mod target;
+pub mod mangle;
+pub mod bundle;
+pub mod sourcemap;
+pub mod traceback;
+pub mod lint;
+pub mod json;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::checker::{Checker, ModuleExports};
+use crate::parser::ast::Block;
+use crate::parser::tokens::Span;
+use crate::diagnostics::{CompileError, Diagnostic, Diagnostics, Fixed, Severity};
+use crate::lint::{Level, Lint, LintLevels};
+use crate::sourcemap::{Segment, SourceMap, SourceMaps};
+use crate::target::Target;
+
+/// A source file after parsing and type checking.
+pub struct CheckedFile {
+    pub path: String,
+    pub source: String,
+    pub block: Block,
+    /// The class declaring the member each field access and method call resolves to, by the span of the access.
+    pub members: HashMap<Span, String>,
+    /// The type errors found, warnings when not strict unless the code cannot run, and the lint warnings.
+    pub diagnostics: Diagnostics,
+}
+
+/// The Lua source generated for a file.
+pub struct CompiledFile {
+    pub path: String,
+    /// `path` with a `.lua` extension, `.luau` when targeting Luau.
+    pub output_path: String,
+    pub code: String,
+    /// JSON map of the mangled names back to the source ones, when mangling.
+    pub name_map: Option<String>,
+    /// Source Map v3 JSON, when written to its own file.
+    pub source_map: Option<String>,
+    /// The diagnostics of the files compiled into this one that have any, left to the caller to report.
+    pub diagnostics: Vec<Diagnostics>,
+}
+
+impl CompiledFile {
+    /// Writes the code to `output_path`, creating its directory, the name map next to it as `.map.json` and the
+    /// source map as `.lua.map`.
+    pub fn write(&self) -> std::io::Result<()> {
+        if let Some(directory) = Path::new(&self.output_path).parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(&self.output_path, &self.code)?;
+        if let Some(name_map) = &self.name_map {
+            std::fs::write(Path::new(&self.output_path).with_extension("map.json"), name_map)?;
+        }
+        if let Some(source_map) = &self.source_map {
+            std::fs::write(format!("{}.map", self.output_path), source_map)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct Compiler {
     files: Vec<String>,
     strict_mode: bool,
     mangle_names: bool,
-    bundle: bool
+    bundle: bool,
+    preserve_comments: bool,
+    target: Target,
+    reserved_names: Vec<String>,
+    source_maps: SourceMaps,
+    lint_levels: LintLevels,
+    out_dir: Option<String>
 }
 
 impl Compiler {
@@ -14,8 +85,264 @@ impl Compiler {
                 files,
                 strict_mode,
                 mangle_names,
-                bundle
+                bundle,
+                preserve_comments: false,
+                target: Target::default(),
+                reserved_names: Vec::new(),
+                source_maps: SourceMaps::default(),
+                lint_levels: LintLevels::default(),
+                out_dir: None
+            }
+    }
+
+    /// Keep the comments of the source files in the generated code.
+    pub fn preserve_comments(mut self, preserve: bool) -> Compiler {
+        self.preserve_comments = preserve;
+        self
+    }
+
+    /// The Lua dialect to generate code for, Lua 5.4 by default.
+    pub fn target(mut self, target: Target) -> Compiler {
+        self.target = target;
+        self
+    }
+
+    /// Map the generated code back to the source files, in a `.lua.map` file or inline.
+    pub fn source_maps(mut self, source_maps: SourceMaps) -> Compiler {
+        self.source_maps = source_maps;
+        self
+    }
+
+    /// Names that mangling must leave untouched, such as locals read through `debug.getlocal`.
+    pub fn reserve_names(mut self, names: Vec<String>) -> Compiler {
+        self.reserved_names = names;
+        self
+    }
+
+    /// Write the generated files under `dir`, laid out as the sources are under the directory of the first
+    /// file, rather than next to their sources.
+    pub fn out_dir(mut self, dir: &str) -> Compiler {
+        self.out_dir = Some(dir.to_string());
+        self
+    }
+
+    /// How each lint is reported, every lint warns by default. A denied lint fails compilation.
+    pub fn lint_levels(mut self, levels: LintLevels) -> Compiler {
+        self.lint_levels = levels;
+        self
+    }
+
+    /// Parses and type checks every file and the files they import or require, dependencies first.
+    /// Every file is checked as far as it parses. Any error then fails compilation, with the other diagnostics
+    /// found along with it. When not strict, type errors are reported as warnings and compilation goes on,
+    /// except for those that leave the code unable to run on the target.
+    pub fn check(&self) -> Result<Vec<CheckedFile>, CompileError> {
+        self.check_modules(bundle::module_graph(&self.files, self.bundle, self.target)?)
+    }
+
+    /// Type checks parsed modules in order, the exports of a module typing the imports of the later ones.
+    fn check_modules(&self, modules: Vec<bundle::Module>) -> Result<Vec<CheckedFile>, CompileError> {
+        let mut exports: Vec<(String, ModuleExports)> = Vec::with_capacity(modules.len());
+        let mut levels = self.lint_levels.clone();
+        if self.strict_mode {
+            // reported as a type error already
+            levels.set(Lint::UndeclaredGlobal, Level::Allow);
+        }
+        let checked: Vec<CheckedFile> = modules.into_iter().map(|module| {
+            let mut checker = Checker::new(self.strict_mode).target(self.target);
+            for (name, exports) in &exports {
+                checker.add_module(name, ModuleExports::clone(exports));
+            }
+            checker.check(&module.block);
+            let mut block = module.block;
+            checker.mark_type_imports(&mut block);
+            exports.push((module.name, std::mem::take(&mut checker.exports)));
+            let members = std::mem::take(&mut checker.members);
+            let parsed = module.errors.is_empty();
+            let mut diagnostics = Diagnostics::new(&module.path, &module.source);
+            module.errors.into_iter().for_each(|error| diagnostics.push(error));
+            for error in checker.errors {
+                let severity = if self.strict_mode || error.kind.breaks_output() { Severity::Error } else { Severity::Warning };
+                diagnostics.push(Diagnostic { severity, ..error.into() });
+            }
+            if parsed {
+                lint::lint(&block, &module.source, &module.comments, &levels).into_iter().for_each(|warning| diagnostics.push(warning));
+            }
+            CheckedFile { path: module.path, source: module.source, block, members, diagnostics }
+        }).collect();
+        if checked.iter().any(|file| file.diagnostics.has_errors()) {
+            return Err(CompileError::Failed(checked.into_iter().map(|file| file.diagnostics).filter(|diagnostics| !diagnostics.is_empty()).collect()));
+        }
+        Ok(checked)
+    }
+
+    /// Applies the fixes suggested for every file and the files they import to the files in place, a fix whose
+    /// edits overlap those of another being refused. Returns the files that had fixes, changed or not.
+    pub fn fix(&self) -> Result<Vec<Fixed>, CompileError> {
+        let files = match self.check() {
+            Ok(checked) => checked.into_iter().map(|file| file.diagnostics).collect(),
+            Err(CompileError::Failed(files)) => files,
+            Err(error) => return Err(error),
+        };
+        let mut fixed = Vec::new();
+        for diagnostics in files.iter().filter(|diagnostics| diagnostics.iter().any(|diagnostic| !diagnostic.fixes.is_empty())) {
+            let file = diagnostics.apply_fixes();
+            if file.applied > 0 {
+                std::fs::write(&file.path, &file.source)?;
+            }
+            fixed.push(file);
+        }
+        Ok(fixed)
+    }
+
+    /// Checks every file and generates its Lua code, type annotations erased and tlua constructs lowered.
+    /// The files they import are compiled along with them.
+    /// With `mangle_names`, locals and private members are shortened and each file gets a name map.
+    /// With `bundle`, the files are entries and every module they require is compiled into a single file.
+    pub fn compile(&self) -> Result<Vec<CompiledFile>, CompileError> {
+        if self.bundle {
+            return Ok(vec![self.compile_bundle()?]);
+        }
+        Ok(self.check()?.into_iter().map(|file| {
+            let output_path = self.output_path(&file.path);
+            let (path, source) = (file.path.clone(), file.source.clone());
+            let diagnostics = Some(file.diagnostics.clone()).filter(|diagnostics| !diagnostics.is_empty()).into_iter().collect();
+            let (code, segments, name_map) = self.generate(file);
+            let mut source_map = SourceMap::new(&relative_path(&output_path, &output_path));
+            source_map.add_source(&relative_path(&path, &output_path), &source, &segments, 0, 0);
+            let (code, source_map) = self.attach_source_map(code, source_map);
+            CompiledFile { path, output_path, code, name_map, source_map, diagnostics }
+        }).collect())
+    }
+
+    /// Links the code to its source map, returning the map when it goes to its own file.
+    fn attach_source_map(&self, mut code: String, source_map: SourceMap) -> (String, Option<String>) {
+        match self.source_maps {
+            SourceMaps::None => (code, None),
+            SourceMaps::File => {
+                code.push_str(&source_map.comment());
+                code.push('\n');
+                (code, Some(source_map.to_json()))
+            }
+            SourceMaps::Inline => {
+                code.push_str(&source_map.inline_comment());
+                code.push('\n');
+                (code, None)
+            }
+        }
+    }
+
+    fn compile_bundle(&self) -> Result<CompiledFile, CompileError> {
+        let modules = bundle::module_graph(&self.files, true, self.target)?;
+        let names: Vec<String> = modules.iter().map(|module| module.name.clone()).collect();
+        let entries: Vec<String> = self.files.iter()
+            .filter_map(|entry| modules.iter().find(|module| module.path == *entry))
+            .map(|module| module.name.clone())
+            .collect();
+        let mut codes = Vec::new();
+        let mut name_maps = Vec::new();
+        let mut sources = Vec::new();
+        let mut diagnostics = Vec::new();
+        for (name, file) in names.into_iter().zip(self.check_modules(modules)?) {
+            let (path, source) = (file.path.clone(), file.source.clone());
+            if !file.diagnostics.is_empty() {
+                diagnostics.push(file.diagnostics.clone());
+            }
+            let (code, segments, name_map) = self.generate(file);
+            if let Some(name_map) = name_map {
+                name_maps.push(format!("{}: {}", json::string(&name), name_map));
             }
+            codes.push((name, code));
+            sources.push((path, source, segments));
+        }
+        let entry = self.files.first().map_or("bundle", String::as_str);
+        let output_path = self.output_path(entry);
+        let (code, starts) = bundle::link(&codes, &entries);
+        let mut source_map = SourceMap::new(&relative_path(&output_path, &output_path));
+        for ((path, source, segments), start) in sources.iter().zip(starts) {
+            source_map.add_source(&relative_path(path, &output_path), source, segments, start, codegen::INDENT.len());
+        }
+        let (code, source_map) = self.attach_source_map(code, source_map);
+        Ok(CompiledFile {
+            path: entry.to_string(),
+            output_path,
+            code,
+            name_map: self.mangle_names.then(|| format!("{{{}}}", name_maps.join(",\n"))),
+            source_map,
+            diagnostics,
+        })
+    }
+
+    /// Where the code of a source file goes: next to it, or at its place under the out directory. A file outside
+    /// of the directory of the first file goes to the top of the out directory.
+    fn output_path(&self, path: &str) -> String {
+        let output = Path::new(path).with_extension(self.extension());
+        let Some(out_dir) = &self.out_dir else {
+            return output.to_string_lossy().into_owned();
+        };
+        let root = self.files.first().and_then(|entry| Path::new(entry).parent()).unwrap_or(Path::new(""));
+        let relative = match output.strip_prefix(root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => output.file_name().map(PathBuf::from).unwrap_or_default(),
+        };
+        Path::new(out_dir).join(relative).to_string_lossy().into_owned()
+    }
+
+    fn extension(&self) -> &'static str {
+        if self.target == Target::Luau { "luau" } else { "lua" }
+    }
+
+    /// The code of a checked file, the positions its statements were printed at and, when mangling, its name map.
+    fn generate(&self, mut file: CheckedFile) -> (String, Vec<Segment>, Option<String>) {
+        let name_map = if self.mangle_names {
+            let names = mangle::mangle_block(&mut file.block, &file.members, &self.reserved_names);
+            Some(mangle::name_map_json(&names, &file.source))
+        } else {
+            None
+        };
+        let (code, segments) = codegen::emit_mapped(file.block, self.target, self.preserve_comments);
+        (code, segments, name_map)
+    }
+}
+
+/// `path` relative to the directory of `output_path`, as source maps name their sources.
+fn relative_path(path: &str, output_path: &str) -> String {
+    let directory: Vec<_> = Path::new(output_path).parent().unwrap_or(Path::new("")).components().collect();
+    let components: Vec<_> = Path::new(path).components().collect();
+    let common = directory.iter().zip(&components).take_while(|(a, b)| a == b).count();
+    // out of the output directory to the first directory both paths share
+    let mut relative: PathBuf = directory[common..].iter().map(|_| "..").collect();
+    relative.extend(&components[common..]);
+    relative.to_string_lossy().into_owned()
+}
+
+/// A temporary directory of source files for a test, removed when dropped, a failed assertion included.
+#[cfg(test)]
+pub(crate) struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Creates the directory `name`, unique to the test, with files given by their path in it and contents.
+    pub(crate) fn new(name: &str, files: &[(&str, &str)]) -> TestDir {
+        let dir = TestDir(std::env::temp_dir().join(format!("tlua-{}-{}", name, std::process::id())));
+        for (file, contents) in files {
+            let path = dir.0.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    /// The path of a file of the directory.
+    pub(crate) fn path(&self, file: &str) -> String {
+        self.0.join(file).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
     }
 }
 
@@ -37,5 +364,87 @@ fn parse() {
     "#;
     println!("Input length: {}", inp.len());
     let lex: Vec<_> = Lexer::new(inp).collect();
-    parse(inp.to_string(), lex);
-}
\ No newline at end of file
+    parse(lex).unwrap();
+}
+
+#[test]
+fn report_every_error() {
+    let dir = TestDir::new("errors", &[("main.tlua", "local n: number = \"one\"\nlocal x = = 2\nlocal s: string = 3\n")]);
+    let Err(CompileError::Failed(files)) = Compiler::new(vec![dir.path("main.tlua")], false, false, false).check() else {
+        panic!("a file that does not parse fails");
+    };
+    let codes: Vec<&str> = files.iter().flatten().map(|diagnostic| diagnostic.code).collect();
+    assert_eq!(codes, ["TL1004", "TL2008", "TL2008"]);
+}
+
+#[test]
+fn fail_on_errors_only() {
+    let dir = TestDir::new("severity", &[("main.tlua", "local n: number = \"one\"\nprint(n)\n")]);
+    let checked = Compiler::new(vec![dir.path("main.tlua")], false, false, false).check().unwrap();
+    assert_eq!(checked[0].diagnostics.iter().map(|error| (error.code, error.severity)).collect::<Vec<_>>(), [("TL2008", Severity::Warning)]);
+    let Err(CompileError::Failed(files)) = Compiler::new(vec![dir.path("main.tlua")], true, false, false).check() else {
+        panic!("a type error fails in strict mode");
+    };
+    assert!(files[0].has_errors());
+}
+
+#[test]
+fn fail_on_unavailable_features() {
+    let dir = TestDir::new("features", &[("main.tlua", "local f <close> = nil\nprint(1 & 2)\ngoto done\n::done::\n")]);
+    for target in [Target::Lua51, Target::Lua52, Target::Lua53, Target::LuaJIT] {
+        let compiler = Compiler::new(vec![dir.path("main.tlua")], false, false, false).target(target);
+        let Err(CompileError::Failed(files)) = compiler.compile() else {
+            panic!("a feature {} lacks fails", target);
+        };
+        assert!(files[0].iter().all(|error| error.code == "TL2019" && error.severity == Severity::Error));
+    }
+    assert!(Compiler::new(vec![dir.path("main.tlua")], false, false, false).target(Target::Lua54).compile().is_ok());
+}
+
+#[test]
+fn fail_on_return_with_exports() {
+    let dir = TestDir::new("exports", &[("main.tlua", "export const answer = 42\nreturn {}\n")]);
+    let Err(CompileError::Failed(files)) = Compiler::new(vec![dir.path("main.tlua")], false, false, false).compile() else {
+        panic!("a return dropping the exports fails");
+    };
+    assert_eq!(files[0].iter().map(|error| (error.code, error.severity)).collect::<Vec<_>>(), [("TL2023", Severity::Error)]);
+}
+
+#[test]
+fn fail_on_luau_goto() {
+    let continues = "for i = 1, 3 do\n    if i == 2 then\n        goto continue\n    end\n    print(i)\n    ::continue::\nend\n";
+    let dir = TestDir::new("luau", &[("continue.tlua", continues), ("jump.tlua", "goto done\nprint(1)\n::done::\n")]);
+    let compiled = Compiler::new(vec![dir.path("continue.tlua")], false, false, false).target(Target::Luau).compile().unwrap();
+    assert!(compiled[0].code.contains("continue") && !compiled[0].code.contains("goto"));
+    let Err(CompileError::Failed(files)) = Compiler::new(vec![dir.path("jump.tlua")], false, false, false).target(Target::Luau).compile() else {
+        panic!("a goto Luau cannot express fails");
+    };
+    let errors: Vec<&str> = files[0].iter().filter(|error| error.severity == Severity::Error).map(|error| error.code).collect();
+    assert_eq!(errors, ["TL2019", "TL2019"]);
+}
+
+#[test]
+fn fix_files_in_place() {
+    let dir = TestDir::new("fix", &[("main.tlua", "local n = 1 -- \u{e9}t\u{e9}\nif n != 2\n    print\"\u{e9}\"\n    n += 1\n")]);
+    let path = dir.path("main.tlua");
+    let compiler = Compiler::new(vec![path.clone()], false, false, false);
+    let fixed = compiler.fix().unwrap();
+    assert_eq!(fixed.iter().map(|file| file.applied).collect::<Vec<_>>(), [4]);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "local n = 1 -- \u{e9}t\u{e9}\nif n ~= 2 then\n    print\"\u{e9}\"\n    n = n + 1\nend\n");
+    assert!(compiler.check().is_ok());
+    assert!(compiler.fix().unwrap().is_empty());
+}
+
+#[test]
+fn deny_lints() {
+    let dir = TestDir::new("lints", &[("main.tlua", "local unused = 1\nprint(\"ok\")\n")]);
+    let path = dir.path("main.tlua");
+    let checked = Compiler::new(vec![path.clone()], false, false, false).check().unwrap();
+    assert_eq!(checked[0].diagnostics.iter().map(|warning| (warning.code, warning.severity)).collect::<Vec<_>>(), [("TL4001", Severity::Warning)]);
+    let mut levels = LintLevels::default();
+    levels.set(Lint::UnusedLocal, Level::Deny);
+    let Err(CompileError::Failed(files)) = Compiler::new(vec![path], false, false, false).lint_levels(levels).check() else {
+        panic!("a denied lint fails compilation");
+    };
+    assert!(files[0].has_errors());
+}
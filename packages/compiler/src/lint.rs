@@ -0,0 +1,590 @@
+use std::collections::{HashMap, HashSet};
+use line_col::LineColLookup;
+use crate::checker::is_standard_global;
+use crate::diagnostics::{Diagnostic, Label, Severity};
+use crate::lower::IMPLICIT;
+use crate::parser::ast::{Attrib, AstNode, AstNodes, Block, ClassNode, Expression, Expressions, FunctionDecl, GenericParam, TableField, Type};
+use crate::parser::errors::{ErrorKind, LintWarnings};
+use crate::parser::tokens::{Comment, Span, Token, Tokens};
+use crate::parser::visit::{terminates, walk_table_type_names, walk_type_names};
+
+/// A check for code that is valid but likely a mistake, reported at its [`Level`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedLocal,
+    UnusedImport,
+    UnusedParameter,
+    Shadowing,
+    UnreachableCode,
+    UndeclaredGlobal,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [Lint::UnusedLocal, Lint::UnusedImport, Lint::UnusedParameter, Lint::Shadowing, Lint::UnreachableCode, Lint::UndeclaredGlobal];
+
+    /// The name of the lint in levels and comments, `unused-local`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedLocal => "unused-local",
+            Lint::UnusedImport => "unused-import",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::Shadowing => "shadowing",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::UndeclaredGlobal => "undeclared-global",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UnusedLocal => "TL4001",
+            Lint::UnusedImport => "TL4002",
+            Lint::UnusedParameter => "TL4003",
+            Lint::Shadowing => "TL4004",
+            Lint::UnreachableCode => "TL4005",
+            Lint::UndeclaredGlobal => "TL4006",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+/// How a lint is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    /// Not at all.
+    Allow,
+    /// As a warning, compilation goes on.
+    #[default]
+    Warn,
+    /// As an error, failing compilation.
+    Deny,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// The level of every lint, `warn` unless set.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Lint, Level>,
+}
+
+impl LintLevels {
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    /// Sets the level of every lint.
+    pub fn set_all(&mut self, level: Level) {
+        for lint in Lint::ALL {
+            self.set(lint, level);
+        }
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or_default()
+    }
+}
+
+/// Lints a parsed file, returning what the lints at `levels` report, in source order.
+///
+/// `comments`, the comment tokens of the file, adjust the levels. `--@tlua-allow`, `--@tlua-warn` and `--@tlua-deny` followed by
+/// lint names set their level for the whole file, and `--@tlua-ignore unused-local` silences a lint on its
+/// line, or on the next one when the comment is alone on its line. Names starting with `_` are never reported
+/// as unused nor as shadowing.
+pub fn lint(block: &Block, source: &str, comments: &[Token], levels: &LintLevels) -> Vec<Diagnostic> {
+    let directives = Directives::new(source, comments, levels);
+    let mut linter = Linter { scopes: Vec::new(), globals: HashSet::new(), source, diagnostics: Vec::new() };
+    linter.block(block);
+    let lines = LineColLookup::new(source);
+    let mut diagnostics: Vec<Diagnostic> = linter.diagnostics.into_iter().filter_map(|(lint, mut diagnostic)| {
+        let line = lines.get(diagnostic.primary.span.start).0;
+        let level = if directives.ignored.contains(&(line, lint)) { Level::Allow } else { directives.levels.level(lint) };
+        match level {
+            Level::Allow => return None,
+            Level::Warn => diagnostic.severity = Severity::Warning,
+            Level::Deny => diagnostic.severity = Severity::Error,
+        }
+        Some(diagnostic)
+    }).collect();
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.primary.span.start, diagnostic.primary.span.end));
+    diagnostics
+}
+
+/// The lint levels set by the comments of a file.
+struct Directives {
+    levels: LintLevels,
+    /// Lines and the lints silenced on them.
+    ignored: HashSet<(usize, Lint)>,
+}
+
+impl Directives {
+    fn new(source: &str, comments: &[Token], levels: &LintLevels) -> Directives {
+        let lines = LineColLookup::new(source);
+        let mut directives = Directives { levels: levels.clone(), ignored: HashSet::new() };
+        for token in comments {
+            let Tokens::Comment(Comment::SingleLine(text) | Comment::MultiLine(text)) = &token.kind else {
+                continue;
+            };
+            let Some((verb, names)) = text.trim().strip_prefix("@tlua-").and_then(|directive| directive.split_once(char::is_whitespace)) else {
+                continue;
+            };
+            let before = source.get(..token.span.start).unwrap_or_default();
+            let alone = before.rsplit('\n').next().unwrap_or_default().trim().is_empty();
+            let line = lines.get(token.span.start).0 + usize::from(alone);
+            let names = names.split(|c: char| c == ',' || c.is_whitespace()).filter_map(Lint::from_name);
+            match (verb, Level::from_name(verb)) {
+                ("ignore", _) => directives.ignored.extend(names.map(|lint| (line, lint))),
+                (_, Some(level)) => names.for_each(|lint| directives.levels.set(lint, level)),
+                _ => {}
+            }
+        }
+        directives
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BindingKind {
+    Local,
+    Import,
+    Parameter,
+    /// Used from elsewhere or implicitly, such as exports, classes, `self` and `<close>` locals.
+    Kept,
+}
+
+struct Binding {
+    kind: BindingKind,
+    span: Span,
+    used: bool,
+}
+
+struct Linter<'a> {
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Globals assigned so far, only the first assignment is reported.
+    globals: HashSet<String>,
+    source: &'a str,
+    diagnostics: Vec<(Lint, Diagnostic)>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, kind: LintWarnings, span: Span) -> &mut Diagnostic {
+        let lint = kind.lint();
+        self.diagnostics.push((lint, Diagnostic::warning(&ErrorKind::Lint(kind), span)));
+        &mut self.diagnostics.last_mut().expect("a diagnostic was just pushed").1
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn declare(&mut self, name: &str, span: &Span, kind: BindingKind) {
+        if kind != BindingKind::Kept && !name.starts_with('_') {
+            if let Some(previous) = self.lookup(name).filter(|_| !IMPLICIT.contains(&name)).map(|binding| binding.span.clone()) {
+                let diagnostic = self.report(LintWarnings::Shadowing(name.to_string()), span.clone());
+                diagnostic.secondary.push(Label { span: previous, message: "previously declared here".to_string() });
+            }
+        }
+        let binding = Binding { kind, span: span.clone(), used: false };
+        // a redeclaration in the same scope ends the previous binding
+        if let Some(previous) = self.scopes.last_mut().expect("a scope to declare into").insert(name.to_string(), binding) {
+            self.unused(name, previous);
+        }
+    }
+
+    /// Marks a local as read, returning whether the name is a local at all.
+    fn resolve(&mut self, name: &str) -> bool {
+        match self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            Some(binding) => {
+                binding.used = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the imports a type annotation names as used.
+    fn annotation(&mut self, typ: &Type, generics: &[GenericParam]) {
+        let mut names = Vec::new();
+        walk_type_names(typ, &mut |name| names.push(name.to_string()));
+        self.type_names(&names, generics);
+    }
+
+    /// Marks the imports named as types as used, skipping the type parameters in scope. Values of the same name
+    /// as an import do not hide it, only imports and classes name types.
+    fn type_names(&mut self, names: &[String], generics: &[GenericParam]) {
+        for name in names.iter().filter(|name| !generics.iter().any(|generic| generic.name == **name)) {
+            // `geo.Point` is a type of the namespace `geo`
+            let root = name.split('.').next().unwrap_or(name);
+            let binding = self.scopes.iter_mut().rev()
+                .find_map(|scope| scope.get_mut(root).filter(|binding| matches!(binding.kind, BindingKind::Import | BindingKind::Kept)));
+            if let Some(binding) = binding {
+                binding.used = true;
+            }
+        }
+    }
+
+    /// An assignment to a name, reported when it is an undeclared global.
+    fn assign(&mut self, name: &str, span: &Span) {
+        if self.lookup(name).is_some() || IMPLICIT.contains(&name) || is_standard_global(name) {
+            return;
+        }
+        if self.globals.insert(name.to_string()) {
+            self.report(LintWarnings::UndeclaredGlobal(name.to_string()), span.clone())
+                .help.push(format!("declare it with `local {}`, or assign it through `_G` if it must be global", name));
+        }
+    }
+
+    fn unused(&mut self, name: &str, binding: Binding) {
+        if binding.used || name.starts_with('_') {
+            return;
+        }
+        let kind = match binding.kind {
+            BindingKind::Local => LintWarnings::UnusedLocal(name.to_string()),
+            BindingKind::Import => LintWarnings::UnusedImport(name.to_string()),
+            BindingKind::Parameter => LintWarnings::UnusedParameter(name.to_string()),
+            BindingKind::Kept => return,
+        };
+        self.report(kind, binding.span).help.push(format!("prefix it with an underscore if it is unused on purpose: `_{}`", name));
+    }
+
+    fn enter(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit(&mut self) {
+        let scope = self.scopes.pop().expect("a scope to exit");
+        for (name, binding) in scope {
+            self.unused(&name, binding);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.enter();
+        self.nodes(block);
+        self.exit();
+    }
+
+    fn nodes(&mut self, block: &[AstNode]) {
+        if let Some(end) = (1..block.len()).find(|end| terminates(&block[..*end])) {
+            // a label after the exit can still be jumped to
+            let unreachable: Vec<&AstNode> = block[end..].iter().take_while(|node| !matches!(node.kind, AstNodes::Label(_))).collect();
+            if let (Some(first), Some(last)) = (unreachable.first(), unreachable.last()) {
+                let exit = block[end - 1].span.clone();
+                let diagnostic = self.report(LintWarnings::UnreachableCode, Span { start: first.span.start, end: last.span.end });
+                diagnostic.secondary.push(Label { span: exit, message: "any code following this is unreachable".to_string() });
+            }
+        }
+        for node in block {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &AstNode) {
+        match &node.kind {
+            AstNodes::LocalAssign { names, values } => {
+                self.expressions(values);
+                for name in names {
+                    if let Some(typ) = &name.typ {
+                        self.annotation(typ, &[]);
+                    }
+                    // a `<close>` local is used when its scope ends
+                    let kind = if name.attrib == Some(Attrib::Close) { BindingKind::Kept } else { BindingKind::Local };
+                    self.declare(&name.name, &name.span, kind);
+                }
+            }
+            AstNodes::Assign { targets, values } => {
+                for target in targets {
+                    match &target.kind {
+                        Expressions::Ident(name) => self.assign(name, &target.span),
+                        _ => self.expression(target),
+                    }
+                }
+                self.expressions(values);
+            }
+            AstNodes::Call(expr) => self.expression(expr),
+            AstNodes::Do(body) => self.block(body),
+            AstNodes::While { condition, body } => {
+                self.expression(condition);
+                self.block(body);
+            }
+            AstNodes::Repeat { body, condition } => {
+                // the condition sees the locals of the body
+                self.enter();
+                self.nodes(body);
+                self.expression(condition);
+                self.exit();
+            }
+            AstNodes::If { condition, body, else_ifs, else_body } => {
+                self.expression(condition);
+                self.block(body);
+                for (condition, body) in else_ifs {
+                    self.expression(condition);
+                    self.block(body);
+                }
+                if let Some(body) = else_body {
+                    self.block(body);
+                }
+            }
+            AstNodes::Switch { subject, cases, default } => {
+                self.expression(subject);
+                for (values, body) in cases {
+                    self.expressions(values);
+                    self.block(body);
+                }
+                if let Some(body) = default {
+                    self.block(body);
+                }
+            }
+            AstNodes::NumericFor { var, start, limit, step, body } => {
+                self.expression(start);
+                self.expression(limit);
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+                self.enter();
+                self.declare(&var.name, &var.span, BindingKind::Local);
+                self.block(body);
+                self.exit();
+            }
+            AstNodes::GenericFor { names, exprs, body } => {
+                self.expressions(exprs);
+                self.enter();
+                for name in names {
+                    self.declare(&name.name, &name.span, BindingKind::Local);
+                }
+                self.block(body);
+                self.exit();
+            }
+            AstNodes::Function(func) => {
+                // `function f()` assigns `f`, `function a.b:c()` reads `a`
+                match func.path.first() {
+                    Some(root) => {
+                        self.resolve(root);
+                    }
+                    None => {
+                        let span = self.name_span(&func.name, &func.span);
+                        self.assign(&func.name, &span);
+                    }
+                }
+                self.function(func);
+            }
+            AstNodes::LocalFunction(func) => {
+                // the function can call itself
+                let span = self.name_span(&func.name, &func.span);
+                self.declare(&func.name, &span, BindingKind::Local);
+                self.function(func);
+            }
+            AstNodes::Return(values) => self.expressions(values),
+            AstNodes::Class(class) => self.class(class, &node.span),
+            AstNodes::Import(import) if import.type_only => {}
+            AstNodes::Import(import) => {
+                for name in import.names.iter().filter(|name| !name.type_only) {
+                    self.declare(&name.local, &name.span, BindingKind::Import);
+                }
+                if let Some(namespace) = &import.namespace {
+                    let span = self.name_span(namespace, &node.span);
+                    self.declare(namespace, &span, BindingKind::Import);
+                }
+            }
+            // exported bindings are used by the importing modules
+            AstNodes::Export(inner) => match &inner.kind {
+                AstNodes::LocalAssign { names, values } => {
+                    self.expressions(values);
+                    for name in names {
+                        if let Some(typ) = &name.typ {
+                            self.annotation(typ, &[]);
+                        }
+                        self.declare(&name.name, &name.span, BindingKind::Kept);
+                    }
+                }
+                AstNodes::LocalFunction(func) => {
+                    self.declare(&func.name, &func.span, BindingKind::Kept);
+                    self.function(func);
+                }
+                _ => self.node(inner),
+            },
+            AstNodes::ExportDefault(value) => self.expression(value),
+            AstNodes::TypeAlias { generics, typ, .. } => {
+                self.generics(generics);
+                self.annotation(typ, generics);
+            }
+            AstNodes::Interface(interface) => {
+                self.generics(&interface.generics);
+                for base in &interface.extends {
+                    self.annotation(base, &interface.generics);
+                }
+                let mut names = Vec::new();
+                walk_table_type_names(&interface.body, &mut |name| names.push(name.to_string()));
+                self.type_names(&names, &interface.generics);
+            }
+            AstNodes::Break | AstNodes::Goto(_) | AstNodes::Label(_) | AstNodes::Error => {}
+        }
+    }
+
+    /// The constraints and defaults of type parameters, which may name the other parameters.
+    fn generics(&mut self, generics: &[GenericParam]) {
+        for generic in generics {
+            for bound in generic.constraint.iter().chain(&generic.default) {
+                self.annotation(bound, generics);
+            }
+        }
+    }
+
+    fn function(&mut self, func: &FunctionDecl) {
+        let annotations = func.args.iter().filter_map(|arg| arg.typ.as_ref()).chain(&func.varargs_typ).chain(&func.returns);
+        for typ in annotations {
+            self.annotation(typ, &[]);
+        }
+        self.enter();
+        if func.is_method {
+            self.declare("self", &func.span, BindingKind::Kept);
+        }
+        for arg in &func.args {
+            self.declare(&arg.name, &arg.span, BindingKind::Parameter);
+        }
+        self.nodes(&func.body);
+        self.exit();
+    }
+
+    fn class(&mut self, class: &ClassNode, span: &Span) {
+        if let Some(base) = &class.extends {
+            self.resolve(base);
+        }
+        self.declare(&class.name, span, BindingKind::Kept);
+        self.type_names(&class.implements, &[]);
+        for typ in class.fields.iter().filter_map(|field| field.field.typ.as_ref()) {
+            self.annotation(typ, &[]);
+        }
+        for value in class.fields.iter().filter_map(|field| field.value.as_ref()) {
+            // instance initializers run in the constructor, with `self` in scope
+            self.enter();
+            self.declare("self", span, BindingKind::Kept);
+            self.expression(value);
+            self.exit();
+        }
+        let methods = class.methods.iter().map(|method| &method.func);
+        for func in class.constructor.iter().chain(methods) {
+            self.function(func);
+        }
+    }
+
+    fn expressions(&mut self, exprs: &[Expression]) {
+        for expr in exprs {
+            self.expression(expr);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match &expr.kind {
+            Expressions::Ident(name) => {
+                self.resolve(name);
+            }
+            Expressions::Function(func) => self.function(func),
+            Expressions::Paren(inner) => self.expression(inner),
+            Expressions::Field { target, .. } => self.expression(target),
+            Expressions::Index { target, key } => {
+                self.expression(target);
+                self.expression(key);
+            }
+            Expressions::Call { target, args } | Expressions::MethodCall { target, args, .. } => {
+                self.expression(target);
+                self.expressions(args);
+            }
+            Expressions::Binary { lhs, rhs, .. } => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            Expressions::Unary { expr, .. } => self.expression(expr),
+            Expressions::Table(fields) => {
+                for field in fields {
+                    match field {
+                        TableField::Positional(value) | TableField::Named(_, value) => self.expression(value),
+                        TableField::Keyed(key, value) => {
+                            self.expression(key);
+                            self.expression(value);
+                        }
+                    }
+                }
+            }
+            Expressions::Nil | Expressions::True | Expressions::False | Expressions::Number(_) | Expressions::Float(_)
+            | Expressions::String(_) | Expressions::Dots | Expressions::Error => {}
+        }
+    }
+
+    /// The span of `name` written as a word inside of `span`, such as the name of a function declaration.
+    fn name_span(&self, name: &str, span: &Span) -> Span {
+        let text = self.source.get(span.start..span.end).unwrap_or_default();
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        text.match_indices(name)
+            .find(|(ix, _)| !text[..*ix].ends_with(is_word) && !text[ix + name.len()..].starts_with(is_word))
+            .map_or(span.clone(), |(ix, _)| Span { start: span.start + ix, end: span.start + ix + name.len() })
+    }
+}
+
+
+#[test]
+fn lint_levels() {
+    use crate::parser::{parse, Lexer};
+    let lint_with = |source: &str, levels: &LintLevels| {
+        let tokens: Vec<Token> = Lexer::new(source).collect();
+        let block = parse(tokens.clone()).unwrap();
+        lint(&block, source, &tokens, levels).into_iter().map(|diagnostic| (diagnostic.code, diagnostic.severity, diagnostic.message)).collect::<Vec<_>>()
+    };
+    let src = r#"
+import { helper, Shape, unused } from "./shapes"
+local count = 1
+local function area(shape: Shape, _scale)
+    local count = shape.size
+    return count * count
+end
+function report(value, extra)
+    do return area(value, helper) end
+    print(value)
+end
+local _ignored = 2
+for i = 1, 3 do print("tick") end
+total = 0
+total = 1
+"#;
+    let levels = LintLevels::default();
+    let warn = Severity::Warning;
+    assert_eq!(lint_with(src, &levels), vec![
+        ("TL4002", warn, "Import 'unused' is never used.".to_string()),
+        ("TL4001", warn, "Local 'count' is never used.".to_string()),
+        ("TL4004", warn, "Local 'count' shadows a local of the same name.".to_string()),
+        ("TL4006", warn, "Assignment to undeclared global 'report'.".to_string()),
+        ("TL4003", warn, "Parameter 'extra' is never used.".to_string()),
+        ("TL4005", warn, "Unreachable code.".to_string()),
+        ("TL4001", warn, "Local 'i' is never used.".to_string()),
+        ("TL4006", warn, "Assignment to undeclared global 'total'.".to_string()),
+    ]);
+
+    let mut levels = LintLevels::default();
+    levels.set_all(Level::Allow);
+    levels.set(Lint::UnusedLocal, Level::Deny);
+    let src = "--@tlua-warn shadowing\nlocal a = 1\nlocal a = 2 --@tlua-ignore unused-local\n--@tlua-ignore unused-local, shadowing\nlocal a = 3\nlocal b = 4\n";
+    assert_eq!(lint_with(src, &levels), vec![
+        ("TL4001", Severity::Error, "Local 'a' is never used.".to_string()),
+        ("TL4004", warn, "Local 'a' shadows a local of the same name.".to_string()),
+        ("TL4001", Severity::Error, "Local 'b' is never used.".to_string()),
+    ]);
+
+    // a local of the same name hides an import from the code after it, but not from type annotations
+    let src = "import { parse, Config, Entry } from \"./config\"\nlocal function load(text): Config\n    local parse = text\n    return parse\nend\nlocal Entry = load\nprint(Entry)\n";
+    assert_eq!(lint_with(src, &LintLevels::default()), vec![
+        ("TL4002", warn, "Import 'parse' is never used.".to_string()),
+        ("TL4002", warn, "Import 'Entry' is never used.".to_string()),
+        ("TL4004", warn, "Local 'parse' shadows a local of the same name.".to_string()),
+        ("TL4004", warn, "Local 'Entry' shadows a local of the same name.".to_string()),
+    ]);
+}
@@ -0,0 +1,262 @@
+use crate::lower::{call, expr, field, ident, node};
+use crate::parser::ast::{AstNode, AstNodes, ClassNode, Expression, Expressions, FunctionDecl, FunctionType, FunctionTypeParam, NamedTokenWithTypeInfo, TableField, TableType, TableTypeField, Type, Types};
+use crate::parser::tokens::Span;
+use crate::parser::visit::walk_block_mut;
+
+/// Lowers a class to a metatable:
+///
+/// ```lua
+/// type Dog = Animal & { <instance fields and methods> }
+/// local Dog = setmetatable({}, { __index = Animal })
+/// Dog.__index = Dog
+/// function Dog.new(...)
+///     local self = setmetatable({}, Dog)
+///     Dog.constructor(self, ...)
+///     return self
+/// end
+/// function Dog:constructor(...) <super call> <field initializers> <rest of the body> end
+/// function Dog:method() ... end
+/// ```
+///
+/// `super(...)` becomes `Animal.constructor(self, ...)` and `super:m(...)` becomes `Animal.m(self, ...)`.
+pub fn lower_class(class: ClassNode, span: &Span) -> Vec<AstNode> {
+    let name = class.name.clone();
+    let mut nodes = vec![instance_type(&class, span)];
+
+    let table = expr(Expressions::Table(Vec::new()), span);
+    let value = match &class.extends {
+        Some(base) => {
+            let meta = expr(Expressions::Table(vec![TableField::Named("__index".to_string(), ident(base, span))]), span);
+            call(ident("setmetatable", span), vec![table, meta])
+        }
+        None => table,
+    };
+    nodes.push(node(AstNodes::LocalAssign { names: vec![name_info(&name, span)], values: vec![value] }, span));
+    nodes.push(node(AstNodes::Assign { targets: vec![field(ident(&name, span), "__index")], values: vec![ident(&name, span)] }, span));
+
+    for static_field in class.fields.iter().filter(|field| field.is_static) {
+        if let Some(value) = &static_field.value {
+            let target = field(ident(&name, span), &static_field.field.name);
+            nodes.push(node(AstNodes::Assign { targets: vec![target], values: vec![value.clone()] }, &static_field.field.span));
+        }
+    }
+
+    let constructor = build_constructor(&class, span);
+    nodes.push(node(AstNodes::Function(build_new(&name, &constructor, span)), span));
+    nodes.push(node(AstNodes::Function(constructor), span));
+
+    for method in class.methods {
+        let mut func = method.func;
+        if let Some(base) = &class.extends {
+            rewrite_super(&mut func, base);
+        }
+        let span = func.span.clone();
+        nodes.push(node(AstNodes::Function(func), &span));
+    }
+    nodes
+}
+
+/// The type of instances, erased from Lua and kept by typed targets. Unannotated members are `any`.
+fn instance_type(class: &ClassNode, span: &Span) -> AstNode {
+    let mut members = TableType::default();
+    for field in class.fields.iter().filter(|field| !field.is_static) {
+        let typ = field.field.typ.clone().unwrap_or_else(|| {
+            let name = match field.value.as_ref().map(|value| &value.kind) {
+                Some(Expressions::Number(_) | Expressions::Float(_)) => "number",
+                Some(Expressions::String(_)) => "string",
+                Some(Expressions::True | Expressions::False) => "boolean",
+                _ => "any",
+            };
+            named_type(name, &field.field.span)
+        });
+        members.fields.push(TableTypeField { name: field.field.name.clone(), typ, optional: false, is_method: false });
+    }
+    for method in class.methods.iter().filter(|method| !method.is_static) {
+        let func = &method.func;
+        let signature = FunctionType {
+            params: func.args.iter().map(|arg| FunctionTypeParam {
+                name: Some(arg.name.clone()),
+                typ: arg.typ.clone().unwrap_or_else(|| named_type("any", &arg.span)),
+                optional: false,
+            }).collect(),
+            varargs: match (&func.varargs_typ, func.varargs) {
+                (Some(typ), _) => Some(Box::new(typ.clone())),
+                (None, true) => Some(Box::new(named_type("any", &func.span))),
+                (None, false) => None,
+            },
+            returns: Box::new(func.returns.clone().unwrap_or_else(|| named_type("any", &func.span))),
+        };
+        let typ = Type { kind: Types::Function(signature), span: func.span.clone() };
+        members.fields.push(TableTypeField { name: func.name.clone(), typ, optional: false, is_method: true });
+    }
+    let mut typ = Type { kind: Types::Table(members), span: span.clone() };
+    if let Some(base) = &class.extends {
+        typ = Type { kind: Types::Intersection(vec![named_type(base, span), typ]), span: span.clone() };
+    }
+    node(AstNodes::TypeAlias { name: class.name.clone(), generics: Vec::new(), typ }, span)
+}
+
+fn named_type(name: &str, span: &Span) -> Type {
+    Type { kind: Types::Named { name: name.to_string(), args: Vec::new() }, span: span.clone() }
+}
+
+fn name_info(name: &str, span: &Span) -> NamedTokenWithTypeInfo {
+    NamedTokenWithTypeInfo { name: name.to_string(), typ: None, attrib: None, span: span.clone() }
+}
+
+fn empty_function(class: &str, name: &str, is_method: bool, span: &Span) -> FunctionDecl {
+    FunctionDecl {
+        name: name.to_string(),
+        path: vec![class.to_string()],
+        is_method,
+        args: Vec::new(),
+        varargs: false,
+        varargs_typ: None,
+        returns: None,
+        body: Vec::new(),
+        is_class: true,
+        is_arrow: false,
+        span: span.clone(),
+    }
+}
+
+/// `Name:constructor`, running instance field initializers right after the `super(...)` call, or first without
+/// one, so that the base constructor cannot overwrite them. Classes without a declared constructor forward all
+/// arguments to the base constructor.
+fn build_constructor(class: &ClassNode, span: &Span) -> FunctionDecl {
+    let mut constructor = match &class.constructor {
+        Some(declared) => declared.clone(),
+        None => {
+            let mut generated = empty_function(&class.name, "constructor", true, span);
+            if class.extends.is_some() {
+                generated.varargs = true;
+                generated.body.push(node(AstNodes::Call(call(ident("super", span), vec![expr(Expressions::Dots, span)])), span));
+            }
+            generated
+        }
+    };
+    let initializers = class.fields.iter()
+        .filter(|field| !field.is_static)
+        .filter_map(|instance_field| {
+            let value = instance_field.value.clone()?;
+            let target = field(ident("self", &instance_field.field.span), &instance_field.field.name);
+            Some(node(AstNodes::Assign { targets: vec![target], values: vec![value] }, &instance_field.field.span))
+        });
+    let is_super_call = |statement: &AstNode| matches!(&statement.kind, AstNodes::Call(Expression { kind: Expressions::Call { target, .. }, .. })
+        if matches!(&target.kind, Expressions::Ident(name) if name == "super"));
+    let at = constructor.body.iter().position(is_super_call).map_or(0, |ix| ix + 1);
+    constructor.body.splice(at..at, initializers);
+    if let Some(base) = &class.extends {
+        rewrite_super(&mut constructor, base);
+    }
+    constructor
+}
+
+/// `Name.new(...)`, allocating the instance and running the constructor on it.
+fn build_new(class: &str, constructor: &FunctionDecl, span: &Span) -> FunctionDecl {
+    let mut new = empty_function(class, "new", false, span);
+    new.returns = Some(named_type(class, span));
+    new.args = constructor.args.clone();
+    new.varargs = constructor.varargs;
+    new.varargs_typ = constructor.varargs_typ.clone();
+    let instance = call(ident("setmetatable", span), vec![expr(Expressions::Table(Vec::new()), span), ident(class, span)]);
+    new.body.push(node(AstNodes::LocalAssign { names: vec![name_info("self", span)], values: vec![instance] }, span));
+    let mut args = vec![ident("self", span)];
+    args.extend(constructor.args.iter().map(|arg| ident(&arg.name, &arg.span)));
+    if constructor.varargs {
+        args.push(expr(Expressions::Dots, span));
+    }
+    new.body.push(node(AstNodes::Call(call(field(ident(class, span), "constructor"), args)), span));
+    new.body.push(node(AstNodes::Return(vec![ident("self", span)]), span));
+    new
+}
+
+/// Rewrites `super(...)`, `super.m(...)` and `super:m(...)` to explicit calls on the base class.
+fn rewrite_super(func: &mut FunctionDecl, base: &str) {
+    let is_super = |target: &Expression| matches!(&target.kind, Expressions::Ident(name) if name == "super");
+    walk_block_mut(&mut func.body, &mut |expression: &mut Expression| {
+        let span = expression.span.clone();
+        let (method, args) = match &mut expression.kind {
+            Expressions::Call { target, args } if is_super(target) => ("constructor".to_string(), std::mem::take(args)),
+            Expressions::Call { target, args } => match &target.kind {
+                Expressions::Field { target: inner, name } if is_super(inner) => (name.clone(), std::mem::take(args)),
+                _ => return,
+            },
+            Expressions::MethodCall { target, method, args } if is_super(target) => (method.clone(), std::mem::take(args)),
+            _ => return,
+        };
+        let mut with_self = vec![ident("self", &span)];
+        with_self.extend(args);
+        *expression = call(field(ident(base, &span), &method), with_self);
+    });
+}
+
+
+#[test]
+fn lower_derived_class() {
+    use crate::lower::lower_block;
+    use crate::parser::{parse, Lexer};
+    let src = r#"
+        class Dog extends Animal implements Pet
+            private legs: number = 4
+            static count = 0
+            constructor(name: string)
+                super(name)
+            end
+            public speak()
+                return super:speak() .. "woof"
+            end
+            static create()
+                return Dog.new("rex")
+            end
+        end
+    "#;
+    let lowered = lower_block(parse(Lexer::new(src).collect()).unwrap());
+    let functions: Vec<String> = lowered.iter().filter_map(|node| match &node.kind {
+        AstNodes::Function(func) => Some(format!("{}{}{}", func.path.join("."), if func.is_method { ":" } else { "." }, func.name)),
+        _ => None
+    }).collect();
+    assert_eq!(functions, ["Dog.new", "Dog:constructor", "Dog:speak", "Dog.create"]);
+    assert!(matches!(&lowered[0].kind, AstNodes::TypeAlias { typ, .. } if typ.to_string() == "Animal & { legs: number, speak(): any }"));
+    assert!(matches!(&lowered[3].kind, AstNodes::Assign { targets, .. } if matches!(&targets[0].kind, Expressions::Field { name, .. } if name == "count")));
+    let AstNodes::Function(constructor) = &lowered[5].kind else { panic!("expected the constructor") };
+    assert!(matches!(&constructor.body[1].kind, AstNodes::Assign { .. }));
+    let AstNodes::Call(super_call) = &constructor.body[0].kind else { panic!("expected the super call") };
+    let Expressions::Call { target, args } = &super_call.kind else { panic!("expected a call") };
+    assert!(matches!(&target.kind, Expressions::Field { name, .. } if name == "constructor"));
+    assert!(matches!(&args[0].kind, Expressions::Ident(name) if name == "self"));
+}
+
+#[test]
+fn initialize_fields_after_super() {
+    use crate::codegen::emit_lua;
+    use crate::parser::{parse, Lexer};
+    use crate::target::Target;
+    let src = r#"
+        class Base
+            kind = "base"
+            constructor()
+                self.kind = "constructed"
+            end
+        end
+        class Derived extends Base
+            kind = "derived"
+            constructor()
+                print("before")
+                super()
+                print(self.kind)
+            end
+        end
+        class Implicit extends Base
+            kind = "implicit"
+        end
+    "#;
+    let lua = emit_lua(parse(Lexer::new(src).collect()).unwrap(), Target::Lua54, false);
+    let constructor = |class: &str| {
+        let start = lua.find(&format!("function {}:constructor", class)).unwrap();
+        lua[start..start + lua[start..].find("\nend\n").unwrap()].lines().skip(1).map(str::trim).collect::<Vec<_>>().join("; ")
+    };
+    assert_eq!(constructor("Base"), r#"self.kind = "base"; self.kind = "constructed""#);
+    assert_eq!(constructor("Derived"), r#"print("before"); Base.constructor(self); self.kind = "derived"; print(self.kind)"#);
+    assert_eq!(constructor("Implicit"), r#"Base.constructor(self, ...); self.kind = "implicit""#);
+}
@@ -0,0 +1,98 @@
+mod class;
+pub mod module;
+mod switch;
+
+use crate::parser::ast::{AstNode, AstNodes, Block, Expression, Expressions, FunctionDecl};
+use crate::parser::tokens::Span;
+use crate::parser::visit::walk_block_mut;
+
+/// Names in scope without a declaration, and the globals and locals the lowered and generated code refers to,
+/// shared by the linter and the mangler.
+pub const IMPLICIT: &[&str] = &["self", "_ENV", "_G", "setmetatable", "math", "bit", "bit32", switch::SUBJECT];
+
+/// Rewrites tlua-only statements of a block, recursively and inside function expressions, into plain Lua statements.
+/// Imports and exports, only allowed at the top level, become `require` calls and a returned exports table.
+pub fn lower_block(block: Block) -> Block {
+    let mut lowered = lower_statements(module::lower_module(block));
+    walk_block_mut(&mut lowered, &mut |expression| {
+        if let Expressions::Function(func) = &mut expression.kind {
+            func.body = lower_statements(std::mem::take(&mut func.body));
+        }
+    });
+    lowered
+}
+
+fn lower_statements(block: Block) -> Block {
+    let mut lowered = Vec::with_capacity(block.len());
+    for node in block {
+        match node.kind {
+            AstNodes::Class(class) => {
+                let mut nodes = class::lower_class(class, &node.span);
+                if let Some(first) = nodes.first_mut() {
+                    first.comments = node.comments;
+                }
+                lowered.extend(nodes.into_iter().map(lower_nested));
+            }
+            AstNodes::Switch { subject, cases, default } => {
+                let cases = cases.into_iter().map(|(values, body)| (values, lower_statements(body))).collect();
+                let mut nodes = switch::lower_switch(subject, cases, default.map(lower_statements), &node.span);
+                if let Some(first) = nodes.first_mut() {
+                    first.comments = node.comments;
+                }
+                lowered.extend(nodes);
+            }
+            kind => lowered.push(lower_nested(AstNode { kind, ..node })),
+        }
+    }
+    lowered
+}
+
+/// Lowers the blocks nested inside a statement.
+fn lower_nested(mut node: AstNode) -> AstNode {
+    node.kind = match node.kind {
+        AstNodes::Do(body) => AstNodes::Do(lower_statements(body)),
+        AstNodes::While { condition, body } => AstNodes::While { condition, body: lower_statements(body) },
+        AstNodes::Repeat { body, condition } => AstNodes::Repeat { body: lower_statements(body), condition },
+        AstNodes::If { condition, body, else_ifs, else_body } => AstNodes::If {
+            condition,
+            body: lower_statements(body),
+            else_ifs: else_ifs.into_iter().map(|(cond, body)| (cond, lower_statements(body))).collect(),
+            else_body: else_body.map(lower_statements),
+        },
+        AstNodes::NumericFor { var, start, limit, step, body } => AstNodes::NumericFor { var, start, limit, step, body: lower_statements(body) },
+        AstNodes::GenericFor { names, exprs, body } => AstNodes::GenericFor { names, exprs, body: lower_statements(body) },
+        AstNodes::Function(func) => AstNodes::Function(lower_function(func)),
+        AstNodes::LocalFunction(func) => AstNodes::LocalFunction(lower_function(func)),
+        other => other,
+    };
+    node
+}
+
+fn lower_function(mut func: FunctionDecl) -> FunctionDecl {
+    func.body = lower_statements(func.body);
+    func
+}
+
+// ---- builders for synthesized nodes, all spanning the construct they were lowered from ----
+
+pub(crate) fn node(kind: AstNodes, span: &Span) -> AstNode {
+    AstNode { kind, span: span.clone(), comments: Vec::new() }
+}
+
+pub(crate) fn expr(kind: Expressions, span: &Span) -> Expression {
+    Expression { kind, span: span.clone() }
+}
+
+pub(crate) fn ident(name: &str, span: &Span) -> Expression {
+    expr(Expressions::Ident(name.to_string()), span)
+}
+
+pub(crate) fn field(target: Expression, name: &str) -> Expression {
+    let span = target.span.clone();
+    expr(Expressions::Field { target: Box::new(target), name: name.to_string() }, &span)
+}
+
+pub(crate) fn call(target: Expression, args: Vec<Expression>) -> Expression {
+    let span = target.span.clone();
+    expr(Expressions::Call { target: Box::new(target), args }, &span)
+}
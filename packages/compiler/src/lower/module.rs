@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use crate::lower::{call, expr, field, ident, node};
+use crate::parser::ast::{AstNode, AstNodes, Block, Expression, Expressions, ImportName, ImportNode, NamedTokenWithTypeInfo, TableField, Type, Types};
+use crate::parser::tokens::Span;
+
+/// Name of the local holding a default export.
+const DEFAULT: &str = "__default";
+
+/// The name a module specifier is required with, `./pkg/mod` and `pkg/mod` become `pkg.mod`. Relative
+/// specifiers are taken from the root, the module graph resolves them from their file first. A specifier
+/// leading out of the root is kept as written, the module graph reports it.
+pub fn require_name(specifier: &str) -> String {
+    resolve_specifier(specifier, &[]).map_or_else(|| specifier.to_string(), |segments| segments.join("."))
+}
+
+/// Whether a specifier names a module from the directory of the importing file, `./mod` or `../mod`.
+pub fn is_relative(specifier: &str) -> bool {
+    specifier.starts_with("./") || specifier.starts_with("../")
+}
+
+/// The path below the root of the module a specifier names, without extension, from a file in `directory`,
+/// a path below the root too. `None` when a relative specifier leads out of the root.
+pub fn resolve_specifier(specifier: &str, directory: &[String]) -> Option<Vec<String>> {
+    let specifier = specifier.strip_suffix(".tlua").or_else(|| specifier.strip_suffix(".lua")).unwrap_or(specifier);
+    let mut segments = if is_relative(specifier) { directory.to_vec() } else { Vec::new() };
+    for segment in specifier.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            name => segments.push(name.to_string()),
+        }
+    }
+    Some(segments)
+}
+
+/// The runtime bindings an exported declaration introduces, types and interfaces have none.
+pub fn exported_values(declaration: &AstNode) -> Vec<String> {
+    match &declaration.kind {
+        AstNodes::LocalAssign { names, .. } => names.iter().map(|name| name.name.clone()).collect(),
+        AstNodes::LocalFunction(func) => vec![func.name.clone()],
+        AstNodes::Class(class) => vec![class.name.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Lowers the imports and exports of a file to `require` calls and a returned table:
+///
+/// ```lua
+/// local __pkg_mod = require("pkg.mod")
+/// local a, c = __pkg_mod.a, __pkg_mod.b
+/// ...
+/// return { f = f, default = __default }
+/// ```
+///
+/// Type-only imports are erased and imported names marked as types become aliases of the module's types.
+/// Exported types and interfaces stay wrapped in their `Export`.
+/// A file without exports is returned unchanged.
+pub fn lower_module(block: Block) -> Block {
+    let mut lowered = Vec::with_capacity(block.len());
+    let mut exports: Vec<(String, Span)> = Vec::new();
+    let mut modules: HashMap<String, String> = HashMap::new();
+    for node in block {
+        match node.kind {
+            AstNodes::Import(import) if import.type_only => {}
+            AstNodes::Import(import) => {
+                let mut nodes = lower_import(import, &mut modules, &node.span);
+                if let Some(first) = nodes.first_mut() {
+                    first.comments = node.comments;
+                }
+                lowered.extend(nodes);
+            }
+            // types have no runtime binding, an exported type stays marked for Luau
+            AstNodes::Export(declaration) if matches!(declaration.kind, AstNodes::TypeAlias { .. } | AstNodes::Interface(_)) => {
+                lowered.push(AstNode { kind: AstNodes::Export(declaration), ..node });
+            }
+            AstNodes::Export(declaration) => {
+                exports.extend(exported_values(&declaration).into_iter().map(|name| (name, declaration.span.clone())));
+                lowered.push(AstNode { comments: node.comments, ..*declaration });
+            }
+            AstNodes::ExportDefault(value) => {
+                exports.push(("default".to_string(), node.span.clone()));
+                let name = NamedTokenWithTypeInfo { name: DEFAULT.to_string(), typ: None, attrib: None, span: value.span.clone() };
+                lowered.push(AstNode { kind: AstNodes::LocalAssign { names: vec![name], values: vec![value] }, ..node });
+            }
+            kind => lowered.push(AstNode { kind, ..node }),
+        }
+    }
+    let returns = matches!(lowered.last(), Some(AstNode { kind: AstNodes::Return(_), .. }));
+    if let (false, Some((_, span))) = (returns, exports.last()) {
+        let span = span.clone();
+        let fields = exports.into_iter().map(|(name, span)| {
+            let value = ident(if name == "default" { DEFAULT } else { &name }, &span);
+            TableField::Named(name, value)
+        }).collect();
+        lowered.push(node(AstNodes::Return(vec![expr(Expressions::Table(fields), &span)]), &span));
+    }
+    lowered
+}
+
+/// Lowers an import, requiring each module once: `modules` maps the modules required so far to the local
+/// holding them, which later imports of the same module read their names from.
+fn lower_import(import: ImportNode, modules: &mut HashMap<String, String>, span: &Span) -> Vec<AstNode> {
+    let name = require_name(&import.module);
+    let required = call(ident("require", span), vec![expr(Expressions::String(name.clone()), span)]);
+    if import.names.is_empty() && import.namespace.is_none() {
+        return vec![node(AstNodes::Call(required), span)];
+    }
+    let mut nodes = Vec::new();
+    let module = match (import.namespace.clone(), modules.get(&name)) {
+        (Some(namespace), Some(module)) => {
+            nodes.push(local(vec![(namespace.clone(), span.clone())], vec![ident(module, span)], span));
+            namespace
+        }
+        (None, Some(module)) => module.clone(),
+        (namespace, None) => {
+            let module = namespace.unwrap_or_else(|| {
+                let sanitized: String = name.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+                format!("__{}", sanitized)
+            });
+            nodes.push(local(vec![(module.clone(), span.clone())], vec![required], span));
+            modules.insert(name, module.clone());
+            module
+        }
+    };
+    let (types, values): (Vec<ImportName>, Vec<ImportName>) = import.names.into_iter().partition(|name| name.type_only);
+    if !values.is_empty() {
+        let fields: Vec<Expression> = values.iter().map(|name| field(ident(&module, &name.span), &name.name)).collect();
+        let names = values.into_iter().map(|name| (name.local, name.span)).collect();
+        nodes.push(local(names, fields, span));
+    }
+    // imported types refer to the module's exported types, erased from plain Lua
+    for name in types {
+        let typ = Type { kind: Types::Named { name: format!("{}.{}", module, name.name), args: Vec::new() }, span: name.span.clone() };
+        nodes.push(node(AstNodes::TypeAlias { name: name.local, generics: Vec::new(), typ }, &name.span));
+    }
+    nodes
+}
+
+fn local(names: Vec<(String, Span)>, values: Vec<Expression>, span: &Span) -> AstNode {
+    let names = names.into_iter().map(|(name, span)| NamedTokenWithTypeInfo { name, typ: None, attrib: None, span }).collect();
+    node(AstNodes::LocalAssign { names, values }, span)
+}
+
+
+#[test]
+fn lower_imports_and_exports() {
+    use crate::codegen::emit_lua;
+    use crate::parser::{parse, Lexer};
+    use crate::target::Target;
+    let src = r#"
+        import type { Shape } from "shapes"
+        import area, { scale as resize } from "./geometry/area"
+        import * as util from "util"
+        import { round } from "./geometry/area"
+        export const unit = 1
+        export function double(x: number): number
+            return round(resize(x, 2))
+        end
+        export type Size = number
+        export default area
+    "#;
+    let block = parse(Lexer::new(src).collect()).unwrap();
+    assert_eq!(emit_lua(block.clone(), Target::Lua54, false), r#"local __geometry_area = require("geometry.area")
+local area, resize = __geometry_area.default, __geometry_area.scale
+local util = require("util")
+local round = __geometry_area.round
+local unit <const> = 1
+local function double(x)
+    return round(resize(x, 2))
+end
+local __default = area
+return { unit = unit, double = double, default = __default }
+"#);
+    assert!(crate::codegen::emit_luau(block, false).contains("\nexport type Size = number\n"));
+}
+
+#[test]
+fn resolve_relative_specifiers() {
+    let directory = ["app".to_string(), "views".to_string()];
+    let resolve = |specifier| resolve_specifier(specifier, &directory).map(|segments| segments.join("."));
+    assert_eq!(resolve("./list"), Some("app.views.list".to_string()));
+    assert_eq!(resolve("../../lib/util.tlua"), Some("lib.util".to_string()));
+    assert_eq!(resolve("lib/util"), Some("lib.util".to_string()));
+    assert_eq!(resolve("../../../outside"), None);
+    assert_eq!(require_name("./pkg/mod"), "pkg.mod");
+}
@@ -0,0 +1,53 @@
+use crate::lower::{expr, ident, node};
+use crate::parser::ast::{AstNode, AstNodes, BinaryOps, Block, Expression, Expressions, NamedTokenWithTypeInfo};
+use crate::parser::tokens::Span;
+
+/// Name of the local holding a switch subject that is not a plain variable.
+pub(super) const SUBJECT: &str = "__switch";
+
+/// Lowers a switch to an `if` chain comparing the subject against every case value:
+///
+/// ```lua
+/// do
+///     local __switch = subject
+///     if __switch == a or __switch == b then ... elseif __switch == c then ... else ... end
+/// end
+/// ```
+///
+/// Subjects that are plain variables are compared directly, without the `do` block.
+pub fn lower_switch(subject: Expression, cases: Vec<(Vec<Expression>, Block)>, default: Option<Block>, span: &Span) -> Vec<AstNode> {
+    let (compared, binding) = match &subject.kind {
+        Expressions::Ident(_) => (subject, None),
+        _ => {
+            let name = NamedTokenWithTypeInfo { name: SUBJECT.to_string(), typ: None, attrib: None, span: subject.span.clone() };
+            let binding = node(AstNodes::LocalAssign { names: vec![name], values: vec![subject] }, span);
+            (ident(SUBJECT, span), Some(binding))
+        }
+    };
+    let mut branches = cases.into_iter().map(|(values, body)| (condition(&compared, values), body));
+    let Some((condition, body)) = branches.next() else {
+        // without cases only the default runs, the subject is still evaluated once
+        let mut nodes: Vec<AstNode> = binding.into_iter().collect();
+        nodes.extend(default.map(|body| node(AstNodes::Do(body), span)));
+        return nodes;
+    };
+    let chain = node(AstNodes::If { condition, body, else_ifs: branches.collect(), else_body: default }, span);
+    match binding {
+        Some(binding) => vec![node(AstNodes::Do(vec![binding, chain]), span)],
+        None => vec![chain],
+    }
+}
+
+/// `subject == a or subject == b`
+fn condition(subject: &Expression, values: Vec<Expression>) -> Expression {
+    values.into_iter()
+        .map(|value| {
+            let span = value.span.clone();
+            expr(Expressions::Binary { op: BinaryOps::EQ, lhs: Box::new(subject.clone()), rhs: Box::new(value) }, &span)
+        })
+        .reduce(|lhs, rhs| {
+            let span = Span { start: lhs.span.start, end: rhs.span.end };
+            expr(Expressions::Binary { op: BinaryOps::Or, lhs: Box::new(lhs), rhs: Box::new(rhs) }, &span)
+        })
+        .expect("a case has at least one value")
+}
@@ -0,0 +1,498 @@
+use std::collections::{HashMap, HashSet};
+use line_col::LineColLookup;
+use crate::json;
+use crate::lower::IMPLICIT;
+use crate::parser::ast::{AstNode, AstNodes, Block, ClassNode, Expression, Expressions, FunctionDecl, TableField, Visibility};
+use crate::parser::tokens::Span;
+use crate::parser::visit::{walk_block_mut, walk_statements_mut};
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// A declaration renamed by [`mangle_block`].
+#[derive(Debug, Clone)]
+pub struct MangledName {
+    pub original: String,
+    pub mangled: String,
+    /// The declaration in the source file.
+    pub span: Span,
+    /// The class declaring a renamed private member, `None` for local variables.
+    pub member_of: Option<String>,
+}
+
+/// Shortens the names of locals, upvalues and private class members of a file, before lowering.
+///
+/// Globals, `self`, `_ENV`, other fields and the `reserved` names keep their names. `members` holds the class
+/// the checker resolved each member access to: an access is renamed only when it resolves to a private member,
+/// and a private member keeps its name when the file also accesses that name on something else than a class,
+/// through a string or in a table constructor. A fresh name never collides with an identifier of the file, and
+/// inner scopes number their names after the ones visible from outside, so an upvalue is never shadowed by a
+/// renamed local. Class names are kept as they double as type names.
+pub fn mangle_block(block: &mut Block, members: &HashMap<Span, String>, reserved: &[String]) -> Vec<MangledName> {
+    let mut taken: HashSet<String> = KEYWORDS.iter().chain(IMPLICIT).map(|name| name.to_string()).collect();
+    taken.extend(reserved.iter().cloned());
+    let mut fields = HashSet::new();
+    let mut unresolved = HashSet::new();
+    walk_block_mut(block, &mut |expr| match &expr.kind {
+        Expressions::Ident(name) => {
+            taken.insert(name.clone());
+        }
+        Expressions::Field { name, .. } | Expressions::MethodCall { method: name, .. } => {
+            fields.insert(name.clone());
+            if !members.contains_key(&expr.span) {
+                unresolved.insert(name.clone());
+            }
+        }
+        Expressions::Index { key, .. } => {
+            if let Expressions::String(name) = &key.kind {
+                unresolved.insert(name.clone());
+            }
+        }
+        Expressions::Table(table) => unresolved.extend(table.iter().filter_map(|field| match field {
+            TableField::Named(name, _) => Some(name.clone()),
+            _ => None,
+        })),
+        _ => {}
+    });
+    let mut privates = Vec::new();
+    walk_statements_mut(block, &mut |node| match &node.kind {
+        // `function a.b()` assigns or indexes `a`, which may be a global, and sets the field `b`
+        AstNodes::Function(func) => {
+            taken.insert(func.path.first().unwrap_or(&func.name).clone());
+            if !func.path.is_empty() {
+                unresolved.insert(func.name.clone());
+            }
+        }
+        AstNodes::Class(class) => {
+            let declared = class.fields.iter().map(|field| (&field.field.name, field.visibility))
+                .chain(class.methods.iter().map(|method| (&method.func.name, method.visibility)));
+            for (name, visibility) in declared {
+                fields.insert(name.clone());
+                if visibility == Visibility::Private {
+                    privates.push((class.name.clone(), name.clone()));
+                }
+            }
+        }
+        _ => {}
+    });
+    let mut mangler = Mangler {
+        scopes: Vec::new(),
+        next: 0,
+        taken,
+        reserved,
+        members,
+        privates: HashMap::new(),
+        names: Vec::new(),
+    };
+    // private members are numbered across the file, a class and its base never share one
+    let mut next_private = 0;
+    for (class, name) in privates.into_iter().filter(|(_, name)| !unresolved.contains(name)) {
+        let mangled = loop {
+            let mangled = format!("_{}", short_name(next_private));
+            next_private += 1;
+            if !fields.contains(&mangled) && !mangler.taken.contains(&mangled) {
+                break mangled;
+            }
+        };
+        mangler.privates.insert((class, name), mangled);
+    }
+    mangler.block(block);
+    mangler.names
+}
+
+/// The `n`th short identifier: `a`..`Z`, then `aa`, `ab` and so on.
+fn short_name(mut n: usize) -> String {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_";
+    let mut name = vec![FIRST[n % FIRST.len()]];
+    n /= FIRST.len();
+    while n > 0 {
+        n -= 1;
+        name.push(REST[n % REST.len()]);
+        n /= REST.len();
+    }
+    String::from_utf8(name).unwrap()
+}
+
+struct Mangler<'a> {
+    /// Original to mangled name, one map per scope.
+    scopes: Vec<HashMap<String, String>>,
+    /// Index of the next short name, restored when a scope ends so that sibling scopes reuse names.
+    next: usize,
+    /// Identifiers of the file, a fresh name must not capture any of them.
+    taken: HashSet<String>,
+    reserved: &'a [String],
+    /// The class declaring the member each access resolves to, by the span of the access.
+    members: &'a HashMap<Span, String>,
+    /// The renamed private members, by their class and name.
+    privates: HashMap<(String, String), String>,
+    names: Vec<MangledName>,
+}
+
+impl Mangler<'_> {
+    fn fresh(&mut self) -> String {
+        loop {
+            let name = short_name(self.next);
+            self.next += 1;
+            if !self.taken.contains(&name) {
+                return name;
+            }
+        }
+    }
+
+    /// Declares a local in the innermost scope, renaming it unless it must keep its name.
+    fn declare(&mut self, name: &mut String, span: &Span) {
+        let keep = IMPLICIT.contains(&name.as_str()) || self.reserved.contains(name);
+        let mangled = if keep { name.clone() } else { self.fresh() };
+        if !keep {
+            self.names.push(MangledName { original: name.clone(), mangled: mangled.clone(), span: span.clone(), member_of: None });
+        }
+        // a kept name still shadows the renamed ones of the enclosing scopes
+        let original = std::mem::replace(name, mangled.clone());
+        self.scopes.last_mut().expect("a scope to declare into").insert(original, mangled);
+    }
+
+    /// Renames a reference to a local, globals resolve to nothing and keep their names.
+    fn resolve(&self, name: &mut String) {
+        if let Some(mangled) = self.scopes.iter().rev().find_map(|scope| scope.get(name.as_str())) {
+            *name = mangled.clone();
+        }
+    }
+
+    fn enter(&mut self) -> usize {
+        self.scopes.push(HashMap::new());
+        self.next
+    }
+
+    fn exit(&mut self, next: usize) {
+        self.scopes.pop();
+        self.next = next;
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        let next = self.enter();
+        self.nodes(block);
+        self.exit(next);
+    }
+
+    fn nodes(&mut self, block: &mut Block) {
+        for node in block {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &mut AstNode) {
+        match &mut node.kind {
+            AstNodes::LocalAssign { names, values } => {
+                self.expressions(values);
+                for name in names {
+                    self.declare(&mut name.name, &name.span);
+                }
+            }
+            AstNodes::Assign { targets, values } => {
+                self.expressions(targets);
+                self.expressions(values);
+            }
+            AstNodes::Call(expr) => self.expression(expr),
+            AstNodes::Do(body) => self.block(body),
+            AstNodes::While { condition, body } => {
+                self.expression(condition);
+                self.block(body);
+            }
+            AstNodes::Repeat { body, condition } => {
+                // the condition sees the locals of the body
+                let next = self.enter();
+                self.nodes(body);
+                self.expression(condition);
+                self.exit(next);
+            }
+            AstNodes::If { condition, body, else_ifs, else_body } => {
+                self.expression(condition);
+                self.block(body);
+                for (condition, body) in else_ifs {
+                    self.expression(condition);
+                    self.block(body);
+                }
+                if let Some(body) = else_body {
+                    self.block(body);
+                }
+            }
+            AstNodes::Switch { subject, cases, default } => {
+                self.expression(subject);
+                for (values, body) in cases {
+                    self.expressions(values);
+                    self.block(body);
+                }
+                if let Some(body) = default {
+                    self.block(body);
+                }
+            }
+            AstNodes::NumericFor { var, start, limit, step, body } => {
+                self.expression(start);
+                self.expression(limit);
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+                let next = self.enter();
+                self.declare(&mut var.name, &var.span);
+                self.block(body);
+                self.exit(next);
+            }
+            AstNodes::GenericFor { names, exprs, body } => {
+                self.expressions(exprs);
+                let next = self.enter();
+                for name in names {
+                    self.declare(&mut name.name, &name.span);
+                }
+                self.block(body);
+                self.exit(next);
+            }
+            AstNodes::Function(func) => {
+                // `function f()` assigns `f`, `function a.b:c()` indexes `a`
+                match func.path.first_mut() {
+                    Some(root) => self.resolve(root),
+                    None => self.resolve(&mut func.name),
+                }
+                self.function(func);
+            }
+            AstNodes::LocalFunction(func) => {
+                // the function can call itself
+                self.declare(&mut func.name, &func.span);
+                self.function(func);
+            }
+            AstNodes::Return(values) => self.expressions(values),
+            AstNodes::Class(class) => self.class(class, &node.span),
+            // type names are never renamed
+            AstNodes::Import(import) if import.type_only => {}
+            AstNodes::Import(import) => {
+                for name in import.names.iter_mut().filter(|name| !name.type_only) {
+                    self.declare(&mut name.local, &name.span);
+                }
+                if let Some(namespace) = &mut import.namespace {
+                    self.declare(namespace, &node.span);
+                }
+            }
+            // exported bindings are fields of the module's exports table
+            AstNodes::Export(inner) => match &mut inner.kind {
+                AstNodes::LocalAssign { names, values } => {
+                    self.expressions(values);
+                    for name in names {
+                        self.keep(&name.name);
+                    }
+                }
+                AstNodes::LocalFunction(func) => {
+                    self.keep(&func.name);
+                    self.function(func);
+                }
+                _ => self.node(inner),
+            },
+            AstNodes::ExportDefault(value) => self.expression(value),
+            AstNodes::Break | AstNodes::Goto(_) | AstNodes::Label(_) | AstNodes::TypeAlias { .. } | AstNodes::Interface(_)
+                | AstNodes::Error => {}
+        }
+    }
+
+    fn function(&mut self, func: &mut FunctionDecl) {
+        let next = self.enter();
+        if func.is_method {
+            self.declare(&mut "self".to_string(), &func.span);
+        }
+        for arg in &mut func.args {
+            self.declare(&mut arg.name, &arg.span);
+        }
+        self.nodes(&mut func.body);
+        self.exit(next);
+    }
+
+    fn class(&mut self, class: &mut ClassNode, span: &Span) {
+        self.keep(&class.name);
+        let fields = class.fields.iter_mut().map(|field| (&mut field.field.name, &field.field.span));
+        let methods = class.methods.iter_mut().map(|method| (&mut method.func.name, &method.func.span));
+        for (member, span) in fields.chain(methods) {
+            if let Some(mangled) = self.privates.get(&(class.name.clone(), member.clone())) {
+                self.names.push(MangledName { original: member.clone(), mangled: mangled.clone(), span: span.clone(), member_of: Some(class.name.clone()) });
+                *member = mangled.clone();
+            }
+        }
+        for field in &mut class.fields {
+            if let Some(value) = &mut field.value {
+                // instance initializers run in the constructor, with `self` in scope
+                let next = self.enter();
+                self.declare(&mut "self".to_string(), span);
+                self.expression(value);
+                self.exit(next);
+            }
+        }
+        let methods = class.methods.iter_mut().map(|method| &mut method.func);
+        for func in class.constructor.iter_mut().chain(methods) {
+            self.function(func);
+        }
+    }
+
+    /// Declares a name that keeps its spelling, shadowing renamed locals of the enclosing scopes.
+    fn keep(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), name.to_string());
+        }
+    }
+
+    /// Renames an access to a private member, as `self.name` or `Class.name` and on any value of the class.
+    fn member(&self, name: &mut String, span: &Span) {
+        let class = self.members.get(span).cloned().unwrap_or_default();
+        if let Some(mangled) = self.privates.get(&(class, name.clone())) {
+            *name = mangled.clone();
+        }
+    }
+
+    fn expressions(&mut self, exprs: &mut [Expression]) {
+        for expr in exprs {
+            self.expression(expr);
+        }
+    }
+
+    fn expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            Expressions::Ident(name) => self.resolve(name),
+            Expressions::Function(func) => self.function(func),
+            Expressions::Paren(inner) => self.expression(inner),
+            Expressions::Field { target, name } => {
+                self.member(name, &expr.span);
+                self.expression(target);
+            }
+            Expressions::MethodCall { target, method, args } => {
+                self.member(method, &expr.span);
+                self.expression(target);
+                self.expressions(args);
+            }
+            Expressions::Index { target, key } => {
+                self.expression(target);
+                self.expression(key);
+            }
+            Expressions::Call { target, args } => {
+                self.expression(target);
+                self.expressions(args);
+            }
+            Expressions::Binary { lhs, rhs, .. } => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            Expressions::Unary { expr, .. } => self.expression(expr),
+            Expressions::Table(fields) => {
+                for field in fields {
+                    match field {
+                        TableField::Positional(value) | TableField::Named(_, value) => self.expression(value),
+                        TableField::Keyed(key, value) => {
+                            self.expression(key);
+                            self.expression(value);
+                        }
+                    }
+                }
+            }
+            Expressions::Nil | Expressions::True | Expressions::False | Expressions::Number(_) | Expressions::Float(_)
+            | Expressions::String(_) | Expressions::Dots | Expressions::Error => {}
+        }
+    }
+}
+
+/// The renamed declarations as a JSON array, with the line and column of each declaration in `source`:
+///
+/// ```json
+/// [{"mangled": "a", "original": "count", "line": 3, "column": 7},
+///  {"mangled": "_a", "original": "secret", "class": "Vault", "line": 9, "column": 13}]
+/// ```
+pub fn name_map_json(names: &[MangledName], source: &str) -> String {
+    let lookup = LineColLookup::new(source);
+    let entries: Vec<String> = names.iter().map(|name| {
+        let (line, column) = lookup.get(name.span.start);
+        let class = match &name.member_of {
+            Some(class) => format!(", \"class\": {}", json::string(class)),
+            None => String::new(),
+        };
+        format!("{{\"mangled\": {}, \"original\": {}{}, \"line\": {}, \"column\": {}}}",
+            json::string(&name.mangled), json::string(&name.original), class, line, column)
+    }).collect();
+    format!("[{}]", entries.join(",\n "))
+}
+
+
+#[test]
+fn mangle_scopes() {
+    use crate::codegen::emit_lua;
+    use crate::parser::{parse, Lexer};
+    use crate::target::Target;
+    use crate::checker::Checker;
+    let src = r#"
+local counter = 0
+local function bump(step)
+    local a = counter + step
+    counter = a
+    return function() return a, print end
+end
+for i = 1, 2 do local x = i end
+local keep = { counter = counter, ["step"] = bump }
+class Vault
+    private secret = 1
+    private tag = "v"
+    reveal(other: Vault)
+        return self.secret + other.secret + self:hide()
+    end
+    private hide()
+        return _ENV.print
+    end
+end
+local loose: any = {}
+print(loose.tag)
+"#;
+    let mut block = parse(Lexer::new(src).collect()).unwrap();
+    let mut checker = Checker::new(false);
+    checker.check(&block);
+    let names = mangle_block(&mut block, &checker.members, &["keep".to_string()]);
+    assert_eq!(emit_lua(block, Target::Lua54, false), r#"local b = 0
+local function c(d)
+    local e = b + d
+    b = e
+    return function()
+        return e, print
+    end
+end
+for d = 1, 2 do
+    local e = d
+end
+local keep = { counter = b, ["step"] = c }
+local Vault = {}
+Vault.__index = Vault
+function Vault.new()
+    local self = setmetatable({}, Vault)
+    Vault.constructor(self)
+    return self
+end
+function Vault:constructor()
+    self._a = 1
+    self.tag = "v"
+end
+function Vault:reveal(d)
+    return self._a + d._a + self:_b()
+end
+function Vault:_b()
+    return _ENV.print
+end
+local d = {}
+print(d.tag)
+"#);
+    let json = name_map_json(&names, src);
+    assert!(json.starts_with("[{\"mangled\": \"b\", \"original\": \"counter\", \"line\": 2, \"column\": 7},"));
+    assert!(json.contains("{\"mangled\": \"_a\", \"original\": \"secret\", \"class\": \"Vault\", \"line\": 11, \"column\": 13}"));
+}
+
+#[test]
+fn mangle_around_global_functions() {
+    use crate::codegen::emit_lua;
+    use crate::parser::{parse, Lexer};
+    use crate::target::Target;
+    let src = "local x = 1\nfunction a() return x end\nif x then function b.c() return x end end\nprint(x)";
+    let mut block = parse(Lexer::new(src).collect()).unwrap();
+    mangle_block(&mut block, &HashMap::new(), &[]);
+    assert_eq!(emit_lua(block, Target::Lua54, false), "local c = 1\nfunction a()\n    return c\nend\nif c then\n    function b.c()\n        return c\n    end\nend\nprint(c)\n");
+}
@@ -1,41 +1,536 @@
 use crate::parser::tokens::{Span, Tokens};
 use crate::parser::tokens::Comment;
+use std::fmt::{Display, Formatter};
+
+pub type Block = Vec<AstNode>;
+
+#[derive(Debug, Clone)]
 pub enum AstNodes {
-    Class()
+    Class(ClassNode),
+    /// `local a <const>, b: T = x, y`
+    LocalAssign {
+        names: Vec<NamedTokenWithTypeInfo>,
+        values: Vec<Expression>,
+    },
+    /// `a, b.c, d[e] = x, y, z`
+    Assign {
+        targets: Vec<Expression>,
+        values: Vec<Expression>,
+    },
+    /// A function or method call used as a statement.
+    Call(Expression),
+    Do(Block),
+    While {
+        condition: Expression,
+        body: Block,
+    },
+    Repeat {
+        body: Block,
+        condition: Expression,
+    },
+    If {
+        condition: Expression,
+        body: Block,
+        else_ifs: Vec<(Expression, Block)>,
+        else_body: Option<Block>,
+    },
+    /// `switch subject case a, b then ... else ... end`, cases do not fall through.
+    Switch {
+        subject: Expression,
+        cases: Vec<(Vec<Expression>, Block)>,
+        default: Option<Block>,
+    },
+    /// `for i = start, limit[, step] do ... end`
+    NumericFor {
+        var: NamedTokenWithTypeInfo,
+        start: Expression,
+        limit: Expression,
+        step: Option<Expression>,
+        body: Block,
+    },
+    /// `for k, v in explist do ... end`
+    GenericFor {
+        names: Vec<NamedTokenWithTypeInfo>,
+        exprs: Vec<Expression>,
+        body: Block,
+    },
+    /// `function a.b:c() ... end`
+    Function(FunctionDecl),
+    /// `local function f() ... end`
+    LocalFunction(FunctionDecl),
+    Return(Vec<Expression>),
+    Break,
+    Goto(String),
+    /// `::name::`
+    Label(String),
+    Interface(InterfaceNode),
+    Import(ImportNode),
+    /// `export <declaration>`, a local function, local, class, interface or type alias.
+    Export(Box<AstNode>),
+    /// `export default value`
+    ExportDefault(Expression),
+    /// `type Name<T extends C = D> = T`
+    TypeAlias {
+        name: String,
+        generics: Vec<GenericParam>,
+        typ: Type,
+    },
+    /// A statement that failed to parse, standing in for the tokens skipped up to the next statement.
+    Error,
 }
 
+#[derive(Debug, Clone)]
 pub struct SpannedCommend {
-    comment: Comment,
-    span: Span
+    pub comment: Comment,
+    pub span: Span
 }
 
+#[derive(Debug, Clone)]
 pub struct AstNode {
-    kind: AstNodes,
-    children: Vec<AstNodes>,
-    span: Span,
-    comments: Vec<SpannedCommend>
+    pub kind: AstNodes,
+    pub span: Span,
+    pub comments: Vec<SpannedCommend>
+}
+
+#[derive(Debug, Clone)]
+pub struct Expression {
+    pub kind: Expressions,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expressions {
+    Nil,
+    True,
+    False,
+    Number(f64),
+    /// A number literal with a decimal point, a float rather than an integer from Lua 5.3 on.
+    Float(f64),
+    String(String),
+    /// `...` inside a vararg function.
+    Dots,
+    Ident(String),
+    Function(Box<FunctionDecl>),
+    Paren(Box<Expression>),
+    /// `target.name`
+    Field {
+        target: Box<Expression>,
+        name: String,
+    },
+    /// `target[key]`
+    Index {
+        target: Box<Expression>,
+        key: Box<Expression>,
+    },
+    Call {
+        target: Box<Expression>,
+        args: Vec<Expression>,
+    },
+    /// `target:method(args)`
+    MethodCall {
+        target: Box<Expression>,
+        method: String,
+        args: Vec<Expression>,
+    },
+    Binary {
+        op: BinaryOps,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Unary {
+        op: UnaryOps,
+        expr: Box<Expression>,
+    },
+    Table(Vec<TableField>),
+    /// An expression that failed to parse, typed as `any` so it raises no further errors.
+    Error,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOps {
+    Or,
+    And,
+    LT,
+    GT,
+    LTE,
+    GTE,
+    NEQ,
+    EQ,
+    BitOr,
+    BitXor,
+    BitAnd,
+    SHL,
+    SHR,
+    Concat,
+    Plus,
+    Minus,
+    Mul,
+    Div,
+    IntDiv,
+    Mod,
+    Pow,
+}
+
+impl BinaryOps {
+    /// Left and right binding power, mirroring the priority table of the reference Lua parser.
+    /// A right power lower than the left one makes the operator right associative.
+    pub fn priority(&self) -> (u8, u8) {
+        match self {
+            BinaryOps::Or => (1, 1),
+            BinaryOps::And => (2, 2),
+            BinaryOps::LT | BinaryOps::GT | BinaryOps::LTE | BinaryOps::GTE | BinaryOps::NEQ | BinaryOps::EQ => (3, 3),
+            BinaryOps::BitOr => (4, 4),
+            BinaryOps::BitXor => (5, 5),
+            BinaryOps::BitAnd => (6, 6),
+            BinaryOps::SHL | BinaryOps::SHR => (7, 7),
+            BinaryOps::Concat => (9, 8),
+            BinaryOps::Plus | BinaryOps::Minus => (10, 10),
+            BinaryOps::Mul | BinaryOps::Div | BinaryOps::IntDiv | BinaryOps::Mod => (11, 11),
+            BinaryOps::Pow => (14, 13),
+        }
+    }
+
+    /// Operators with a compound assignment form in Luau, `a op= b`.
+    pub fn is_compound(&self) -> bool {
+        matches!(self, BinaryOps::Plus | BinaryOps::Minus | BinaryOps::Mul | BinaryOps::Div | BinaryOps::IntDiv
+            | BinaryOps::Mod | BinaryOps::Pow | BinaryOps::Concat)
+    }
+
+    pub fn from_token(token: &Tokens) -> Option<BinaryOps> {
+        Some(match token {
+            Tokens::Or => BinaryOps::Or,
+            Tokens::And => BinaryOps::And,
+            Tokens::LT => BinaryOps::LT,
+            Tokens::GT => BinaryOps::GT,
+            Tokens::LTE => BinaryOps::LTE,
+            Tokens::GTE => BinaryOps::GTE,
+            Tokens::NEQ => BinaryOps::NEQ,
+            Tokens::EQ => BinaryOps::EQ,
+            Tokens::BitOr => BinaryOps::BitOr,
+            Tokens::BitXor => BinaryOps::BitXor,
+            Tokens::BitAnd => BinaryOps::BitAnd,
+            Tokens::SHL => BinaryOps::SHL,
+            Tokens::SHR => BinaryOps::SHR,
+            Tokens::Concat => BinaryOps::Concat,
+            Tokens::Plus => BinaryOps::Plus,
+            Tokens::Minus => BinaryOps::Minus,
+            Tokens::Mul => BinaryOps::Mul,
+            Tokens::Div => BinaryOps::Div,
+            Tokens::IntDiv => BinaryOps::IntDiv,
+            Tokens::Mod => BinaryOps::Mod,
+            Tokens::Pow => BinaryOps::Pow,
+            _ => return None
+        })
+    }
+}
+
+impl Display for BinaryOps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            BinaryOps::Or => "or",
+            BinaryOps::And => "and",
+            BinaryOps::LT => "<",
+            BinaryOps::GT => ">",
+            BinaryOps::LTE => "<=",
+            BinaryOps::GTE => ">=",
+            BinaryOps::NEQ => "~=",
+            BinaryOps::EQ => "==",
+            BinaryOps::BitOr => "|",
+            BinaryOps::BitXor => "~",
+            BinaryOps::BitAnd => "&",
+            BinaryOps::SHL => "<<",
+            BinaryOps::SHR => ">>",
+            BinaryOps::Concat => "..",
+            BinaryOps::Plus => "+",
+            BinaryOps::Minus => "-",
+            BinaryOps::Mul => "*",
+            BinaryOps::Div => "/",
+            BinaryOps::IntDiv => "//",
+            BinaryOps::Mod => "%",
+            BinaryOps::Pow => "^",
+        })
+    }
 }
 
+/// Binding power of all unary operators, between `*` and `^`.
+pub const UNARY_PRIORITY: u8 = 12;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOps {
+    Not,
+    /// `-x`
+    Neg,
+    /// `#x`
+    Len,
+    /// `~x`
+    BitNot,
+}
+
+impl UnaryOps {
+    pub fn from_token(token: &Tokens) -> Option<UnaryOps> {
+        Some(match token {
+            Tokens::Not => UnaryOps::Not,
+            Tokens::Minus => UnaryOps::Neg,
+            Tokens::Hash => UnaryOps::Len,
+            Tokens::BitXor => UnaryOps::BitNot,
+            _ => return None
+        })
+    }
+}
+
+impl Display for UnaryOps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            UnaryOps::Not => "not",
+            UnaryOps::Neg => "-",
+            UnaryOps::Len => "#",
+            UnaryOps::BitNot => "~",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TableField {
+    /// `{ value }`
+    Positional(Expression),
+    /// `{ name = value }`
+    Named(String, Expression),
+    /// `{ [key] = value }`
+    Keyed(Expression, Expression),
+}
+
+#[derive(Debug, Clone)]
 pub struct Type {
-    name: String
+    pub kind: Types,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Types {
+    /// `number`, `mod.Foo` or a generic application `Map<K, V>`
+    Named {
+        name: String,
+        args: Vec<Type>,
+    },
+    Nil,
+    /// `"yes"`, `1`, `true`
+    Literal(LiteralType),
+    /// `A | B`
+    Union(Vec<Type>),
+    /// `A & B`
+    Intersection(Vec<Type>),
+    /// `T?`
+    Optional(Box<Type>),
+    /// `T[]`
+    Array(Box<Type>),
+    /// `(A, B)`, a list of values such as multiple returns
+    Tuple(Vec<Type>),
+    /// `...T`, any number of values, ending a list of returned values
+    Variadic(Box<Type>),
+    /// `(a: A, ...B) -> R`
+    Function(FunctionType),
+    /// `{ x: number, [string]: any }`
+    Table(TableType),
+    /// `Check extends Extends ? Then : Otherwise`
+    Conditional {
+        check: Box<Type>,
+        extends: Box<Type>,
+        then: Box<Type>,
+        otherwise: Box<Type>,
+    },
+}
+
+/// `import { a, b as c } from "mod"`, `import d from "mod"`, `import * as m from "mod"` or `import type { T } from "mod"`.
+#[derive(Debug, Clone)]
+pub struct ImportNode {
+    /// The module specifier, `"pkg/mod"`.
+    pub module: String,
+    /// Named imports, a default import being the import of `default`.
+    pub names: Vec<ImportName>,
+    /// `* as name`
+    pub namespace: Option<String>,
+    /// `import type`, erased from the output.
+    pub type_only: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportName {
+    /// The name exported by the module.
+    pub name: String,
+    /// The local binding, `c` for `b as c`.
+    pub local: String,
+    /// Names a type and no value, known once the exporting module is checked.
+    pub type_only: bool,
+    pub span: Span,
 }
 
+/// `interface Name<T> extends A, B members... end`
+#[derive(Debug, Clone)]
+pub struct InterfaceNode {
+    pub name: String,
+    pub generics: Vec<GenericParam>,
+    pub extends: Vec<Type>,
+    pub body: TableType,
+}
+
+/// A type parameter of an alias or interface, `T extends Constraint = Default`.
+#[derive(Debug, Clone)]
+pub struct GenericParam {
+    pub name: String,
+    pub constraint: Option<Type>,
+    pub default: Option<Type>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralType {
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionType {
+    pub params: Vec<FunctionTypeParam>,
+    pub varargs: Option<Box<Type>>,
+    pub returns: Box<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionTypeParam {
+    pub name: Option<String>,
+    pub typ: Type,
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TableType {
+    pub fields: Vec<TableTypeField>,
+    /// `[K]: V` index signatures
+    pub indexers: Vec<(Type, Type)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableTypeField {
+    pub name: String,
+    pub typ: Type,
+    /// Declared as `name?: T`
+    pub optional: bool,
+    /// Declared as a method signature `name(a: A): R`, called with `:` and an implicit `self`.
+    pub is_method: bool,
+}
+
+fn join<T: Display>(items: &[T], sep: &str) -> String {
+    items.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(sep)
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            Types::Named { name, args } if args.is_empty() => write!(f, "{}", name),
+            Types::Named { name, args } => write!(f, "{}<{}>", name, join(args, ", ")),
+            Types::Nil => write!(f, "nil"),
+            Types::Literal(LiteralType::String(s)) => write!(f, "{:?}", s),
+            Types::Literal(LiteralType::Number(n)) => write!(f, "{}", n),
+            Types::Literal(LiteralType::Boolean(b)) => write!(f, "{}", b),
+            Types::Union(types) => write!(f, "{}", join(types, " | ")),
+            Types::Intersection(types) => write!(f, "{}", join(types, " & ")),
+            Types::Optional(inner) => write!(f, "{}?", inner),
+            Types::Array(inner) => write!(f, "{}[]", inner),
+            Types::Tuple(types) => write!(f, "({})", join(types, ", ")),
+            Types::Variadic(inner) => write!(f, "...{}", inner),
+            Types::Function(func) => {
+                let mut params: Vec<String> = func.params.iter().map(|param| match &param.name {
+                    Some(name) => format!("{}{}: {}", name, if param.optional { "?" } else { "" }, param.typ),
+                    None => param.typ.to_string()
+                }).collect();
+                if let Some(varargs) = &func.varargs {
+                    params.push(format!("...{}", varargs));
+                }
+                write!(f, "({}) -> {}", params.join(", "), func.returns)
+            }
+            Types::Table(table) => {
+                let mut fields: Vec<String> = table.fields.iter().map(|field| match &field.typ.kind {
+                    Types::Function(func) if field.is_method => {
+                        let sig = Type { kind: Types::Function(func.clone()), span: field.typ.span.clone() }.to_string();
+                        let (params, returns) = sig.split_once(" -> ").unwrap_or((&sig, ""));
+                        format!("{}{}{}: {}", field.name, if field.optional { "?" } else { "" }, params, returns)
+                    }
+                    _ => format!("{}{}: {}", field.name, if field.optional { "?" } else { "" }, field.typ)
+                }).collect();
+                fields.extend(table.indexers.iter().map(|(key, value)| format!("[{}]: {}", key, value)));
+                write!(f, "{{ {} }}", fields.join(", "))
+            }
+            Types::Conditional { check, extends, then, otherwise } => write!(f, "{} extends {} ? {} : {}", check, extends, then, otherwise),
+        }
+    }
+}
+
+/// Local variable attribute, `<const>` or `<close>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attrib {
+    Const,
+    Close,
+}
+
+#[derive(Debug, Clone)]
 pub struct NamedTokenWithTypeInfo {
-    name: String,
-    typ: Type
+    pub name: String,
+    pub typ: Option<Type>,
+    pub attrib: Option<Attrib>,
+    pub span: Span,
 }
 
+#[derive(Debug, Clone)]
 pub struct FunctionDecl {
-    name: String,
-    args: Vec<NamedTokenWithTypeInfo>,
-    is_class: bool,
+    /// Last segment of the declared name, empty for anonymous functions.
+    pub name: String,
+    /// Segments before the last one, `a.b` for `function a.b:c()`.
+    pub path: Vec<String>,
+    /// Declared with `:`, taking an implicit `self`.
+    pub is_method: bool,
+    pub args: Vec<NamedTokenWithTypeInfo>,
+    pub varargs: bool,
+    /// Annotation of `...: T`
+    pub varargs_typ: Option<Type>,
+    pub returns: Option<Type>,
+    pub body: Block,
+    pub is_class: bool,
+    /// `(args) => expr`, the body holds a single `return expr`.
+    pub is_arrow: bool,
+    pub span: Span,
 }
 
+#[derive(Debug, Clone)]
 pub struct ClassNode {
-    name: String,
-    private_methods: Vec<FunctionDecl>,
-    public_methods: Vec<FunctionDecl>,
-    private_fields: Vec<NamedTokenWithTypeInfo>,
-    public_fields: Vec<NamedTokenWithTypeInfo>
-}
\ No newline at end of file
+    pub name: String,
+    pub extends: Option<String>,
+    pub implements: Vec<String>,
+    /// The `constructor(...)` method, if declared.
+    pub constructor: Option<FunctionDecl>,
+    pub methods: Vec<ClassMethod>,
+    pub fields: Vec<ClassField>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassMethod {
+    pub visibility: Visibility,
+    pub is_static: bool,
+    pub func: FunctionDecl,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassField {
+    pub visibility: Visibility,
+    pub is_static: bool,
+    pub field: NamedTokenWithTypeInfo,
+    pub value: Option<Expression>,
+}
@@ -1,56 +1,435 @@
-use crate::parser::tokens::Tokens;
+use std::fmt::{Display, Formatter};
+use crate::diagnostics::{Edit, Fix};
+use crate::lint::Lint;
+use crate::parser::ast::{BinaryOps, Visibility};
+use crate::parser::tokens::{Span, Tokens};
+use crate::target::Target;
 
 #[derive(Debug, Clone)]
 pub enum ErrorKind {
     Parse(ParseErrors),
     Type(TypeErrors),
+    Bundle(BundleErrors),
+    Lint(LintWarnings),
 }
+
+impl ErrorKind {
+    /// The stable code of the error, `TL1xxx` for parse errors, `TL2xxx` for type errors, `TL3xxx` for bundling
+    /// and `TL4xxx` for lints.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Parse(kind) => kind.code(),
+            ErrorKind::Type(kind) => kind.code(),
+            ErrorKind::Bundle(kind) => kind.code(),
+            ErrorKind::Lint(kind) => kind.lint().code(),
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::Parse(kind) => write!(f, "{}", kind),
+            ErrorKind::Type(kind) => write!(f, "{}", kind),
+            ErrorKind::Bundle(kind) => write!(f, "{}", kind),
+            ErrorKind::Lint(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum BundleErrors {
+    /// Modules of the cycle, starting and ending with the same one.
+    CircularRequire(Vec<String>),
+    /// Specifier of a relative import leading out of the root directory.
+    ModuleOutsideRoot(String),
+    /// A module required or imported by a bundle that is neither a file nor provided by the host.
+    ModuleNotFound(String),
+}
+
+impl BundleErrors {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BundleErrors::CircularRequire(_) => "TL3001",
+            BundleErrors::ModuleOutsideRoot(_) => "TL3002",
+            BundleErrors::ModuleNotFound(_) => "TL3003",
+        }
+    }
+}
+
+impl Display for BundleErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleErrors::CircularRequire(chain) => write!(f, "Circular require between modules: {}.", chain.join(" -> ")),
+            BundleErrors::ModuleOutsideRoot(specifier) => write!(f, "Module '{}' is outside of the root directory, the directory of the first file.", specifier),
+            BundleErrors::ModuleNotFound(name) => write!(f, "Cannot find module '{}' to bundle.", name),
+        }
+    }
+}
+
+/// What a lint found, a warning or an error depending on the level of the lint.
+#[derive(Clone, Debug)]
+pub enum LintWarnings {
+    UnusedLocal(String),
+    UnusedImport(String),
+    UnusedParameter(String),
+    /// A local declared with the name of a visible one.
+    Shadowing(String),
+    UnreachableCode,
+    UndeclaredGlobal(String),
+}
+
+impl LintWarnings {
+    pub fn lint(&self) -> Lint {
+        match self {
+            LintWarnings::UnusedLocal(_) => Lint::UnusedLocal,
+            LintWarnings::UnusedImport(_) => Lint::UnusedImport,
+            LintWarnings::UnusedParameter(_) => Lint::UnusedParameter,
+            LintWarnings::Shadowing(_) => Lint::Shadowing,
+            LintWarnings::UnreachableCode => Lint::UnreachableCode,
+            LintWarnings::UndeclaredGlobal(_) => Lint::UndeclaredGlobal,
+        }
+    }
+}
+
+impl Display for LintWarnings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintWarnings::UnusedLocal(name) => write!(f, "Local '{}' is never used.", name),
+            LintWarnings::UnusedImport(name) => write!(f, "Import '{}' is never used.", name),
+            LintWarnings::UnusedParameter(name) => write!(f, "Parameter '{}' is never used.", name),
+            LintWarnings::Shadowing(name) => write!(f, "Local '{}' shadows a local of the same name.", name),
+            LintWarnings::UnreachableCode => write!(f, "Unreachable code."),
+            LintWarnings::UndeclaredGlobal(name) => write!(f, "Assignment to undeclared global '{}'.", name),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ParseErrors {
-    ExpectedAfterButReceived(Tokens, Tokens, Tokens)
+    ExpectedAfterButReceived(Tokens, Tokens, Tokens),
+    ExpectedButReceived(Tokens, Tokens),
+    UnexpectedToken(Tokens),
+    ExpectedExpression(Tokens),
+    ExpectedType(Tokens),
+    UnexpectedEof,
+    NotAStatement,
+    InvalidAssignmentTarget,
+    InvalidConstructor,
+    /// A contextual keyword such as `from`, and the token received instead.
+    ExpectedKeyword(&'static str, Tokens),
+    InvalidExport,
+    /// `!=`, which Lua writes `~=`.
+    NotEqualOperator,
+    /// `a += b` and the like, with the operator and the target lacking them.
+    CompoundAssignment(BinaryOps, Target),
+}
+
+/// A parse error together with the span of the construct it belongs to (`origin`)
+/// and the span of the offending token (`at`).
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub kind: ParseErrors,
+    pub origin: Span,
+    pub at: Span,
+    /// The edits that correct the error, when they are certain.
+    pub fix: Option<Fix>,
+}
+
+impl ParseError {
+    pub fn fix(mut self, message: &str, edits: Vec<Edit>) -> ParseError {
+        self.fix = Some(Fix { message: message.to_string(), edits });
+        self
+    }
 }
 
-const VOWELS: &'static str = "aeiou";
+const VOWELS: &str = "aeiou";
 
 fn disp_enum(val: &Tokens, upper_first: bool, add_prefix: bool) -> String {
     let matched = match val {
         Tokens::Ident(_) => "identifier".into(),
         Tokens::String(_) => "string literal".into(),
+        Tokens::Number(_) | Tokens::Float(_) => "number literal".into(),
         Tokens::Class => "class declaration".into(),
-
-        _ => format!("{:?}", val)
+        Tokens::NewLine => "new line".into(),
+        Tokens::Unknown(s) => format!("unknown character '{}'", s),
+        Tokens::Comment(_) => "comment".into(),
+        other => match keyword_or_symbol(other) {
+            Some(kw) => format!("'{}'", kw),
+            None => format!("{:?}", other)
+        }
     };
-    return if add_prefix {
+    if add_prefix {
         a_or_an(matched, upper_first)
     } else {
         matched
     }
 }
 
+/// The source text of a keyword or punctuation token.
+pub fn keyword_or_symbol(val: &Tokens) -> Option<&'static str> {
+    Some(match val {
+        Tokens::And => "and",
+        Tokens::Break => "break",
+        Tokens::Do => "do",
+        Tokens::Else => "else",
+        Tokens::ElseIf => "elseif",
+        Tokens::End => "end",
+        Tokens::Type => "type",
+        Tokens::Const => "const",
+        Tokens::Interface => "interface",
+        Tokens::Public => "public",
+        Tokens::Private => "private",
+        Tokens::Extends => "extends",
+        Tokens::Implements => "implements",
+        Tokens::Switch => "switch",
+        Tokens::For => "for",
+        Tokens::Function => "function",
+        Tokens::Goto => "goto",
+        Tokens::If => "if",
+        Tokens::In => "in",
+        Tokens::Local => "local",
+        Tokens::Nil => "nil",
+        Tokens::Not => "not",
+        Tokens::Or => "or",
+        Tokens::Repeat => "repeat",
+        Tokens::Return => "return",
+        Tokens::Then => "then",
+        Tokens::True => "true",
+        Tokens::False => "false",
+        Tokens::Until => "until",
+        Tokens::While => "while",
+        Tokens::Arrow => "=>",
+        Tokens::ThinArrow => "->",
+        Tokens::Qmark => "?",
+        Tokens::IntDiv => "//",
+        Tokens::Concat => "..",
+        Tokens::Dots => "...",
+        Tokens::Period => ".",
+        Tokens::LParen => "(",
+        Tokens::RParen => ")",
+        Tokens::LCurly => "{",
+        Tokens::RCurly => "}",
+        Tokens::LBracket => "[",
+        Tokens::RBracket => "]",
+        Tokens::Comma => ",",
+        Tokens::Plus => "+",
+        Tokens::Minus => "-",
+        Tokens::Mul => "*",
+        Tokens::Div => "/",
+        Tokens::Mod => "%",
+        Tokens::Pow => "^",
+        Tokens::BitAnd => "&",
+        Tokens::BitOr => "|",
+        Tokens::BitXor => "~",
+        Tokens::Assign => "=",
+        Tokens::EQ => "==",
+        Tokens::NEQ => "~=",
+        Tokens::GTE => ">=",
+        Tokens::LTE => "<=",
+        Tokens::LT => "<",
+        Tokens::GT => ">",
+        Tokens::SHL => "<<",
+        Tokens::SHR => ">>",
+        Tokens::Hash => "#",
+        Tokens::SemiColon => ";",
+        Tokens::DBColon => "::",
+        Tokens::Colon => ":",
+        _ => return None
+    })
+}
+
 fn str_cap(s: String) -> String {
-    format!("{}{}", (&s[..1].to_string()).to_uppercase(), &s[1..])
+    format!("{}{}", s[..1].to_uppercase(), &s[1..])
 }
 
 fn a_or_an(str: String, upper_first: bool) -> String {
-    let first_char = str.chars().nth(0).unwrap().to_lowercase().to_string();
+    let first_char = str.chars().next().unwrap().to_lowercase().to_string();
     let an = if upper_first {"An "} else {"an "};
     let a = if upper_first {"A "} else {"a "};
-    return if VOWELS.contains(&first_char) {
+    if VOWELS.contains(&first_char) {
         an.to_owned() + &str
     } else {
         a.to_owned() + &str
     }
 }
 
-impl ToString for ParseErrors {
-    fn to_string(&self) -> String {
-        String::from(match self {
-            ParseErrors::ExpectedAfterButReceived(exp, after, recv) => format!("{} expected after {}, but received {}.", str_cap(disp_enum(exp, true, false)), disp_enum(after, false, true), disp_enum(recv, false, true))
-        })
+impl ParseErrors {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrors::ExpectedAfterButReceived(..) => "TL1001",
+            ParseErrors::ExpectedButReceived(..) => "TL1002",
+            ParseErrors::UnexpectedToken(_) => "TL1003",
+            ParseErrors::ExpectedExpression(_) => "TL1004",
+            ParseErrors::ExpectedType(_) => "TL1005",
+            ParseErrors::UnexpectedEof => "TL1006",
+            ParseErrors::NotAStatement => "TL1007",
+            ParseErrors::InvalidAssignmentTarget => "TL1008",
+            ParseErrors::InvalidConstructor => "TL1009",
+            ParseErrors::ExpectedKeyword(..) => "TL1010",
+            ParseErrors::InvalidExport => "TL1011",
+            ParseErrors::NotEqualOperator => "TL1012",
+            ParseErrors::CompoundAssignment(..) => "TL1013",
+        }
+    }
+}
+
+impl Display for ParseErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ParseErrors::ExpectedAfterButReceived(exp, after, recv) => format!("{} expected after {}, but received {}.", str_cap(disp_enum(exp, true, false)), disp_enum(after, false, true), disp_enum(recv, false, true)),
+            ParseErrors::ExpectedButReceived(exp, recv) => format!("{} expected, but received {}.", str_cap(disp_enum(exp, true, false)), disp_enum(recv, false, true)),
+            ParseErrors::UnexpectedToken(tok) => format!("Unexpected {}.", disp_enum(tok, false, false)),
+            ParseErrors::ExpectedExpression(recv) => format!("Expression expected, but received {}.", disp_enum(recv, false, true)),
+            ParseErrors::ExpectedType(recv) => format!("Type expected, but received {}.", disp_enum(recv, false, true)),
+            ParseErrors::UnexpectedEof => "Unexpected end of file.".to_string(),
+            ParseErrors::NotAStatement => "Only assignments and calls can be used as statements.".to_string(),
+            ParseErrors::InvalidAssignmentTarget => "Only names, fields and indexed values can be assigned to.".to_string(),
+            ParseErrors::InvalidConstructor => "A class can have a single, non-static constructor.".to_string(),
+            ParseErrors::ExpectedKeyword(keyword, recv) => format!("'{}' expected, but received {}.", keyword, disp_enum(recv, false, true)),
+            ParseErrors::InvalidExport => "Only functions, locals, classes, interfaces and types can be exported.".to_string(),
+            ParseErrors::NotEqualOperator => "'!=' is not an operator, inequality is written '~='.".to_string(),
+            ParseErrors::CompoundAssignment(op, target) => format!("Compound assignment '{}=' is not available when targeting {}, the assigned variable must be repeated.", op, target),
+        };
+        f.write_str(&msg)
     }
 }
 
 #[derive(Clone, Debug)]
 pub enum TypeErrors {
+    UnknownType(String),
+    /// Type name, expected and received number of type arguments.
+    WrongTypeArgumentCount(String, usize, usize),
+    /// Type argument, generic parameter and its constraint.
+    ConstraintNotSatisfied(String, String, String),
+    RecursiveAlias(String),
+    /// Type, missing member and the interface requiring it.
+    MissingMember(String, String, String),
+    /// Member, its type, the expected type and the interface requiring it.
+    IncompatibleMember(String, String, String, String),
+    NotAnInterface(String),
+    /// Found and expected type.
+    TypeMismatch(String, String),
+    /// Expected and received number of arguments.
+    WrongArgumentCount(usize, usize),
+    /// Expected and received number of return values.
+    WrongReturnCount(usize, usize),
+    NotCallable(String),
+    /// Property and the type it was looked up on.
+    UnknownProperty(String, String),
+    /// Operator and operand type.
+    InvalidOperand(String, String),
+    /// Operator and both operand types.
+    InvalidOperands(String, String, String),
+    PossiblyNil(String),
+    UndeclaredGlobal(String),
+    UnknownName(String),
+    /// Parameter without a type annotation.
+    ImplicitAny(String),
+    /// Feature and the target lacking it.
+    UnsupportedFeature(String, String),
+    ConstantAssignment(String),
+    /// Member and module specifier.
+    NotExported(String, String),
+    NestedModuleDeclaration,
+    ReturnWithExports,
+    /// Member, the class declaring it and its visibility.
+    InaccessibleMember(String, String, Visibility),
+    /// Specifier of a relative import naming no file.
+    UnresolvedModule(String),
+    /// Base class named by `extends` that is not declared.
+    UnknownBaseClass(String),
+    /// Base class named by `extends` that is declared further down the file.
+    BaseClassUsedBeforeDeclaration(String),
+    /// Class extending itself, directly or through its bases.
+    CircularInheritance(String),
+    /// Alias expanding to itself, directly or through other aliases.
+    CircularAlias(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct TypeError {
+    pub kind: TypeErrors,
+    pub span: Span,
+}
 
+impl TypeErrors {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeErrors::UnknownType(_) => "TL2001",
+            TypeErrors::WrongTypeArgumentCount(..) => "TL2002",
+            TypeErrors::ConstraintNotSatisfied(..) => "TL2003",
+            TypeErrors::RecursiveAlias(_) => "TL2004",
+            TypeErrors::MissingMember(..) => "TL2005",
+            TypeErrors::IncompatibleMember(..) => "TL2006",
+            TypeErrors::NotAnInterface(_) => "TL2007",
+            TypeErrors::TypeMismatch(..) => "TL2008",
+            TypeErrors::WrongArgumentCount(..) => "TL2009",
+            TypeErrors::WrongReturnCount(..) => "TL2010",
+            TypeErrors::NotCallable(_) => "TL2011",
+            TypeErrors::UnknownProperty(..) => "TL2012",
+            TypeErrors::InvalidOperand(..) => "TL2013",
+            TypeErrors::InvalidOperands(..) => "TL2014",
+            TypeErrors::PossiblyNil(_) => "TL2015",
+            TypeErrors::UndeclaredGlobal(_) => "TL2016",
+            TypeErrors::UnknownName(_) => "TL2017",
+            TypeErrors::ImplicitAny(_) => "TL2018",
+            TypeErrors::UnsupportedFeature(..) => "TL2019",
+            TypeErrors::ConstantAssignment(_) => "TL2020",
+            TypeErrors::NotExported(..) => "TL2021",
+            TypeErrors::NestedModuleDeclaration => "TL2022",
+            TypeErrors::ReturnWithExports => "TL2023",
+            TypeErrors::InaccessibleMember(..) => "TL2024",
+            TypeErrors::UnresolvedModule(_) => "TL2025",
+            TypeErrors::UnknownBaseClass(_) => "TL2026",
+            TypeErrors::BaseClassUsedBeforeDeclaration(_) => "TL2027",
+            TypeErrors::CircularInheritance(_) => "TL2028",
+            TypeErrors::CircularAlias(_) => "TL2029",
+        }
+    }
+
+    /// Whether the code generated despite the error cannot run as written: a feature the target lacks, exports
+    /// a `return` would drop, or a class without a usable base. Such an error fails compilation even when not
+    /// strict.
+    pub fn breaks_output(&self) -> bool {
+        matches!(self, TypeErrors::UnsupportedFeature(..) | TypeErrors::ReturnWithExports | TypeErrors::UnknownBaseClass(_)
+            | TypeErrors::BaseClassUsedBeforeDeclaration(_) | TypeErrors::CircularInheritance(_))
+    }
+}
+
+impl Display for TypeErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeErrors::UnknownType(name) => write!(f, "Cannot find type '{}'.", name),
+            TypeErrors::WrongTypeArgumentCount(name, expected, received) => write!(f, "Type '{}' expects {} type argument(s), but received {}.", name, expected, received),
+            TypeErrors::ConstraintNotSatisfied(arg, param, constraint) => write!(f, "Type '{}' does not satisfy the constraint '{}' of type parameter '{}'.", arg, constraint, param),
+            TypeErrors::RecursiveAlias(name) => write!(f, "Type alias '{}' expands infinitely.", name),
+            TypeErrors::MissingMember(typ, member, interface) => write!(f, "Property '{}' is missing in type '{}' but required in type '{}'.", member, typ, interface),
+            TypeErrors::IncompatibleMember(member, found, expected, interface) => write!(f, "Property '{}' of type '{}' is not assignable to type '{}' required by '{}'.", member, found, expected, interface),
+            TypeErrors::NotAnInterface(name) => write!(f, "'{}' is not an interface and cannot be implemented.", name),
+            TypeErrors::TypeMismatch(found, expected) => write!(f, "Type '{}' is not assignable to type '{}'.", found, expected),
+            TypeErrors::WrongArgumentCount(expected, received) => write!(f, "Expected {} argument(s), but received {}.", expected, received),
+            TypeErrors::WrongReturnCount(expected, received) => write!(f, "Expected {} return value(s), but received {}.", expected, received),
+            TypeErrors::NotCallable(typ) => write!(f, "Type '{}' has no call signatures.", typ),
+            TypeErrors::UnknownProperty(name, typ) => write!(f, "Property '{}' does not exist on type '{}'.", name, typ),
+            TypeErrors::InvalidOperand(op, typ) => write!(f, "Operator '{}' cannot be applied to type '{}'.", op, typ),
+            TypeErrors::PossiblyNil(typ) => write!(f, "Object of type '{}' is possibly 'nil'.", typ),
+            TypeErrors::UndeclaredGlobal(name) => write!(f, "Cannot assign to undeclared global '{}', declare it with 'local'.", name),
+            TypeErrors::UnknownName(name) => write!(f, "Cannot find name '{}'.", name),
+            TypeErrors::ImplicitAny(name) => write!(f, "Parameter '{}' implicitly has an 'any' type.", name),
+            TypeErrors::UnsupportedFeature(feature, target) => write!(f, "{} is not available when targeting {}.", feature, target),
+            TypeErrors::ConstantAssignment(name) => write!(f, "Cannot assign to '{}' because it is a constant.", name),
+            TypeErrors::NotExported(name, module) => write!(f, "Module '{}' has no exported member '{}'.", module, name),
+            TypeErrors::NestedModuleDeclaration => write!(f, "Imports and exports can only be declared at the top level of a file."),
+            TypeErrors::ReturnWithExports => write!(f, "A file with exports cannot return a value, its exports are returned."),
+            TypeErrors::InaccessibleMember(name, class, Visibility::Protected) => write!(f, "Property '{}' is protected and only accessible within class '{}' and its subclasses.", name, class),
+            TypeErrors::InaccessibleMember(name, class, _) => write!(f, "Property '{}' is private and only accessible within class '{}'.", name, class),
+            TypeErrors::UnresolvedModule(module) => write!(f, "Cannot find module '{}'.", module),
+            TypeErrors::UnknownBaseClass(base) => write!(f, "Cannot find class '{}' to extend.", base),
+            TypeErrors::BaseClassUsedBeforeDeclaration(base) => write!(f, "Class '{}' is extended before its declaration.", base),
+            TypeErrors::CircularInheritance(class) => write!(f, "Class '{}' extends itself, directly or through its bases.", class),
+            TypeErrors::CircularAlias(name) => write!(f, "Type alias '{}' circularly references itself.", name),
+            TypeErrors::InvalidOperands(op, lhs, rhs) => write!(f, "Operator '{}' cannot be applied to types '{}' and '{}'.", op, lhs, rhs),
+        }
+    }
 }
@@ -1,9 +1,9 @@
-use std::fmt::Write;
-use crate::parser::tokens::{Comment, Span, Token, Tokens};
+use crate::parser::tokens::{byte_char, Comment, Span, Token, Tokens};
 
 #[derive(Debug)]
 pub struct Lexer {
     input: String,
+    /// Byte offset of the next character, spans being byte ranges of the input.
     cursor: usize,
 }
 
@@ -20,78 +20,58 @@ impl Lexer {
     }
 
     fn incr_cursor(&mut self) -> Option<char> {
-        let c = self.input.chars().nth(self.cursor);
-        self.cursor += 1;
+        let c = self.cur_char();
+        self.cursor += c.map_or(1, char::len_utf8);
         c
     }
 
-    fn peek(&self, n: usize) -> Option<&str> {
-        if self.input.len() <= self.cursor + n {
-            None
-        } else {
-            Some(&self.input[self.cursor..self.cursor + n])
-        }
-    }
-
-    fn peek_is_number(&mut self) -> bool {
-        if let Some(c) = self.next_char() {
-            return match c {
-                '0'..='9' => true,
-                _ => false,
-            };
-        }
-
-        false
+    fn next_char_is_number(&self) -> bool {
+        matches!(self.next_char(), Some('0'..='9'))
     }
 
-    fn next_char_is_number(&self) -> bool {
-        match self.next_char() {
-            Some('0'..='9') => true,
-            _ => false,
-        }
+    fn rest(&self) -> &str {
+        self.input.get(self.cursor..).unwrap_or_default()
     }
 
     fn cur_char(&self) -> Option<char> {
-        self.input.chars().nth(self.cursor)
+        self.rest().chars().next()
     }
 
     fn next_char(&self) -> Option<char> {
-        self.input.chars().nth(self.cursor + 1)
+        self.rest().chars().nth(1)
     }
 
     pub fn match_chars(&mut self, other: &str) -> bool {
-        if self.input.len() < other.len() {
-            return false;
-        }
-        for (i, c) in self.input.chars().skip(self.cursor).enumerate() {
-            if Some(c) != other.chars().nth(i) {
-                return false;
-            }
-        }
-        true
+        self.rest().starts_with(other)
     }
 
-    fn multi_line_comment(&mut self) -> Option<Token> {
-        let start = self.cursor;
-
-        self.move_cursor(4);
+    /// The level of the long bracket `[==[` opening at a byte offset, its number of `=`.
+    fn long_bracket_level(&self, at: usize) -> Option<usize> {
+        let rest = self.input.get(at..)?.strip_prefix('[')?;
+        let level = rest.len() - rest.trim_start_matches('=').len();
+        rest[level..].starts_with('[').then_some(level)
+    }
 
-        let mut comment = String::new();
+    /// The text between a long bracket opening at the cursor and the closing bracket of the same level, `None`
+    /// when it is never closed.
+    fn long_bracket(&mut self, level: usize) -> Option<String> {
+        self.move_cursor(level + 2);
+        let closing = format!("]{}]", "=".repeat(level));
+        let length = self.rest().find(&closing)?;
+        let text = self.rest()[..length].to_string();
+        self.move_cursor(length + closing.len());
+        Some(text)
+    }
 
-        loop {
-            if self.match_chars("]]--") {
-                self.move_cursor(4);
-                let end = self.cursor;
-                break Some(Token {
-                    kind: Tokens::Comment(Comment::MultiLine(comment)),
-                    span: Span { start, end },
-                });
-            } else if let Some(c) = self.incr_cursor() {
-                comment.push(c);
-            } else {
-                break None;
-            }
-        }
+    fn multi_line_comment(&mut self, level: usize) -> Option<Token> {
+        let start = self.cursor;
+        self.move_cursor(2);
+        let comment = self.long_bracket(level)?;
+        let end = self.cursor;
+        Some(Token {
+            kind: Tokens::Comment(Comment::MultiLine(comment)),
+            span: Span { start, end },
+        })
     }
 
     fn single_line_comment(&mut self) -> Option<Token> {
@@ -122,7 +102,8 @@ impl Lexer {
         loop {
             match self.incr_cursor() {
                 Some(e) if Some(e) == closing => break,
-                Some(sc) if sc == '\n' => return None,
+                Some('\n') => return None,
+                Some('\\') => s.push_str(&self.escape_sequence()?),
                 Some(sc) => s.push(sc),
                 None => return None,
             }
@@ -134,24 +115,68 @@ impl Lexer {
         })
     }
 
-    fn multi_line_string(&mut self) -> Option<Token> {
-        let start = self.cursor;
-        self.move_cursor(2);
-        let mut s = String::new();
-        let end = self.cursor;
-        loop {
-            if self.match_chars("]]") {
-                self.move_cursor(2);
-                break Some(Token {
-                    kind: Tokens::String(s),
-                    span: Span { start, end },
-                });
+    /// Decodes the escape sequence following a `\\` in a quoted string.
+    fn escape_sequence(&mut self) -> Option<String> {
+        let escaped = match self.incr_cursor()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            '\n' => '\n',
+            'x' => {
+                let hex: String = [self.incr_cursor()?, self.incr_cursor()?].iter().collect();
+                byte_char(u8::from_str_radix(&hex, 16).ok()?)
             }
-            match self.incr_cursor() {
-                Some(sc) => s.push(sc),
-                None => break None,
+            'u' => {
+                if self.incr_cursor()? != '{' {
+                    return None;
+                }
+                let mut hex = String::new();
+                loop {
+                    match self.incr_cursor()? {
+                        '}' => break,
+                        digit => hex.push(digit),
+                    }
+                }
+                char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
             }
-        }
+            'z' => {
+                while matches!(self.cur_char(), Some(' ' | '\t' | '\r' | '\n')) {
+                    self.move_cursor(1);
+                }
+                return Some(String::new());
+            }
+            digit @ '0'..='9' => {
+                let mut code = digit.to_digit(10)?;
+                for _ in 0..2 {
+                    match self.cur_char().and_then(|c| c.to_digit(10)) {
+                        Some(next) => {
+                            code = code * 10 + next;
+                            self.move_cursor(1);
+                        }
+                        None => break,
+                    }
+                }
+                byte_char(u8::try_from(code).ok()?)
+            }
+            other => other,
+        };
+        Some(escaped.to_string())
+    }
+
+    /// A long bracket string, skipping a line break right after the opening bracket like Lua does.
+    fn multi_line_string(&mut self, level: usize) -> Option<Token> {
+        let start = self.cursor;
+        let s = self.long_bracket(level)?;
+        let s = s.strip_prefix("\r\n").or_else(|| s.strip_prefix('\n')).unwrap_or(&s).to_string();
+        let end = self.cursor;
+        Some(Token {
+            kind: Tokens::String(s),
+            span: Span { start, end },
+        })
     }
 
     fn identifier(&mut self) -> Option<Token> {
@@ -212,58 +237,70 @@ impl Lexer {
         Some(Token { kind, span })
     }
 
+    /// A decimal or hexadecimal numeral, with a fraction and an exponent, `1`, `.5`, `1e10`, `0xFF` or `0x1p4`.
+    /// Letters right after the numeral are part of it, making it malformed.
     fn number(&mut self) -> Option<Token> {
         let start = self.cursor;
-        let mut s = String::new();
-
-        if self.cur_char() == Some('-') {
-            if let Some(c) = self.incr_cursor() {
-                s.push(c);
-            }
-        }
-
-        while let Some(n) = self.cur_char() {
-            match n {
-                '0'..='9' | '.' => {
-                    if let Some(c) = self.incr_cursor() {
-                        s.push(c);
-                    } else {
-                        break;
-                    }
-                }
-                _ => break,
+        let hex = self.match_chars("0x") || self.match_chars("0X");
+        let exponent: &[char] = if hex { &['p', 'P'] } else { &['e', 'E'] };
+        while let Some(c) = self.cur_char() {
+            let signed = matches!(c, '+' | '-') && self.input[..self.cursor].ends_with(exponent);
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' || signed {
+                self.move_cursor(1);
+            } else {
+                break;
             }
         }
-
+        let s = &self.input[start..self.cursor];
         let span = Span {
             start,
             end: self.cursor,
         };
 
-        match s.parse() {
-            Ok(num) => Some(Token {
-                kind: Tokens::Number(num),
+        let value = if hex { hex_numeral(&s[2..]) } else { s.parse().ok() };
+        let is_float = s[if hex { 2 } else { 0 }..].contains(|c: char| c == '.' || exponent.contains(&c));
+        match value {
+            Some(num) => Some(Token {
+                kind: if is_float { Tokens::Float(num) } else { Tokens::Number(num) },
                 span,
             }),
-            _ => Some(Token {
-                kind: Tokens::Unknown(s),
+            None => Some(Token {
+                kind: Tokens::Unknown(s.to_string()),
                 span,
             }),
         }
     }
 }
 
+/// The value of the digits of a hexadecimal numeral following `0x`, its exponent being a power of 2.
+fn hex_numeral(digits: &str) -> Option<f64> {
+    let (mantissa, exponent) = match digits.find(['p', 'P']) {
+        Some(ix) => (&digits[..ix], digits[ix + 1..].parse::<i32>().ok()?),
+        None => (digits, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let mut value = 0.0;
+    for c in whole.chars().chain(fraction.chars()) {
+        value = value * 16.0 + f64::from(c.to_digit(16)?);
+    }
+    Some(value * 2f64.powi(exponent - 4 * i32::try_from(fraction.len()).ok()?))
+}
+
 impl Iterator for Lexer {
     type Item = Token;
     fn next(&mut self) -> Option<Token> {
-        if let Some("--[[") = &self.peek(4) {
-            self.multi_line_comment()
+        if let Some(level) = self.long_bracket_level(self.cursor + 2).filter(|_| self.match_chars("--")) {
+            self.multi_line_comment(level)
+        } else if let Some(level) = self.long_bracket_level(self.cursor) {
+            self.multi_line_string(level)
         } else if let Some(c) = self.cur_char() {
             let start = self.cursor;
             let next = self.next_char();
             match c {
                 '\'' | '"' | '`' => self.single_line_string(),
-                '[' if next == Some('[') => self.multi_line_string(),
                 '=' if next == Some('=') => {
                     self.move_cursor(2);
                     let end = self.cursor;
@@ -322,17 +359,22 @@ impl Iterator for Lexer {
                         span: Span { start, end },
                     })
                 }
-                ' ' => {
+                ' ' | '\t' | '\r' => {
                     self.incr_cursor();
                     self.next()
                 }
                 '.' if self.next_char_is_number() => self.number(),
                 '0'..='9' => self.number(),
                 '-' if next == Some('-') => self.single_line_comment(),
+                '-' if next == Some('>') => {
+                    self.move_cursor(2);
+                    let end = self.cursor;
+                    Some(Token {
+                        kind: Tokens::ThinArrow,
+                        span: Span { start, end },
+                    })
+                }
                 '-' => {
-                    if self.peek_is_number() {
-                        return self.number();
-                    }
                     self.incr_cursor();
                     let end = self.cursor;
                     Some(Token {
@@ -578,4 +620,31 @@ impl Iterator for Lexer {
             None
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn lex_non_ascii() {
+    let src = "-- ü\nlocal s = \"héllo\" .. 'ß'";
+    let tokens: Vec<Token> = Lexer::new(src).collect();
+    let strings: Vec<(&Tokens, &str)> = tokens.iter()
+        .filter(|token| matches!(token.kind, Tokens::String(_) | Tokens::Comment(_)))
+        .map(|token| (&token.kind, &src[token.span.start..token.span.end]))
+        .collect();
+    assert_eq!(strings, [
+        (&Tokens::Comment(Comment::SingleLine(" ü".to_string())), "-- ü"),
+        (&Tokens::String("héllo".to_string()), "\"héllo\""),
+        (&Tokens::String("ß".to_string()), "'ß'"),
+    ]);
+}
+
+#[test]
+fn lex_numerals_and_long_brackets() {
+    let src = "0xFF 0x.8 1e10 2E-2 0x1p4 0XAp-1 .5 3. 3x 0x --[==[ ]] ]==] [[\nline]] [=[]]]=]";
+    let kinds: Vec<Tokens> = Lexer::new(src).map(|token| token.kind).collect();
+    assert_eq!(kinds, [
+        Tokens::Number(255.0), Tokens::Float(0.5), Tokens::Float(1e10), Tokens::Float(0.02), Tokens::Float(16.0),
+        Tokens::Float(5.0), Tokens::Float(0.5), Tokens::Float(3.0), Tokens::Unknown("3x".to_string()),
+        Tokens::Unknown("0x".to_string()), Tokens::Comment(Comment::MultiLine(" ]] ".to_string())),
+        Tokens::String("line".to_string()), Tokens::String("]]".to_string()),
+    ]);
+}
@@ -1,8 +1,10 @@
 mod lex;
-mod tokens;
+pub mod tokens;
+#[allow(clippy::module_inception)]
 mod parser;
-mod ast;
-mod errors;
+pub mod ast;
+pub mod errors;
+pub mod visit;
 
 pub use lex::Lexer;
-pub use parser::parse;
\ No newline at end of file
+pub use parser::{parse, parse_for, parse_recovering};
\ No newline at end of file
@@ -1,97 +1,1645 @@
-use std::iter::{Enumerate, Peekable};
-use std::panic;
-use std::slice::Iter;
 use crate::parser::tokens::{Span, Token, Tokens};
-use line_col::LineColLookup;
-use crate::parser::errors::{ErrorKind, ParseErrors};
-use colored::Colorize;
+use crate::parser::ast::{Attrib, AstNode, AstNodes, BinaryOps, Block, ClassField, ClassMethod, ClassNode, Visibility, Expression, Expressions, FunctionDecl, NamedTokenWithTypeInfo, SpannedCommend, TableField, TableType, TableTypeField, Type, Types, FunctionType, FunctionTypeParam, GenericParam, InterfaceNode, ImportName, ImportNode, LiteralType, UnaryOps, UNARY_PRIORITY};
+use crate::diagnostics::{Diagnostic, Edit};
+use crate::parser::errors::{keyword_or_symbol, ParseError, ParseErrors};
+use crate::target::Target;
 
 
-struct ErrorClient<'a> {
-    original_source: &'a String,
-    lookup: LineColLookup<'a>
+type ParseResult<T> = Result<T, Box<ParseError>>;
+
+struct Parser {
+    /// Significant tokens only, new lines and comments are stripped on construction.
+    tokens: Vec<Token>,
+    /// Whether each token is the first of its line, lines end statements when recovering from an error.
+    line_starts: Vec<bool>,
+    cursor: usize,
+    /// Comments paired with the index of the token that follows them.
+    comments: Vec<(usize, SpannedCommend)>,
+    comment_cursor: usize,
+    /// Span of the first token of the statement being parsed, used as error origin.
+    origin: Span,
+    /// Set while parsing the `extends` clause of a conditional type, where `?` starts the true branch.
+    in_extends_clause: bool,
+    /// Set while parsing the cases of a `switch`, where `case` starts the next case.
+    in_switch: bool,
+    /// Dialect whose missing syntax is reported.
+    target: Target,
+    /// Errors recovered from, in source order.
+    errors: Vec<ParseError>,
 }
 
 
-impl <'a>ErrorClient<'a> {
-    pub fn new(source: &'a String) -> ErrorClient<'a> {
-        ErrorClient {original_source: source, lookup: LineColLookup::new(source.as_str())}
+
+impl Parser {
+    fn new(tokens: Vec<Token>, target: Target) -> Parser {
+        let mut significant = Vec::with_capacity(tokens.len());
+        let mut line_starts = Vec::with_capacity(tokens.len());
+        let mut comments = Vec::new();
+        let mut new_line = true;
+        for token in tokens {
+            match token.kind {
+                Tokens::NewLine => new_line = true,
+                Tokens::Comment(comment) => comments.push((significant.len(), SpannedCommend { comment, span: token.span })),
+                _ => {
+                    significant.push(token);
+                    line_starts.push(std::mem::replace(&mut new_line, false));
+                }
+            }
+        }
+        Parser {
+            tokens: significant,
+            line_starts,
+            cursor: 0,
+            comments,
+            comment_cursor: 0,
+            origin: Span { start: 0, end: 0 },
+            in_extends_clause: false,
+            in_switch: false,
+            target,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn parse(&mut self) -> Block {
+        let mut block = self.parse_block();
+        // a terminator without a construct to close, the statements after it are parsed all the same
+        while let Some(token) = self.peek() {
+            let error = self.error_at(ParseErrors::UnexpectedToken(token.kind.clone()), token.span.clone());
+            self.report(*error);
+            self.bump();
+            block.extend(self.parse_block());
+        }
+        block
+    }
+
+    // ---- token cursor ----
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor)
+    }
+
+    fn peek_kind(&self) -> Option<&Tokens> {
+        self.peek().map(|t| &t.kind)
+    }
+
+    fn peek_nth_kind(&self, n: usize) -> Option<&Tokens> {
+        self.tokens.get(self.cursor + n).map(|t| &t.kind)
     }
-    fn is_inside_bounds(&self, span: &Span) -> bool {
-        span.start < self.original_source.len() && span.end <= self.original_source.len()
+
+    fn check(&self, kind: &Tokens) -> bool {
+        self.peek_kind() == Some(kind)
     }
-    pub fn raise_parse_err(&self, kind: ParseErrors, origin: &Span, at: &Span, exit: bool) {
-            let err = kind.to_string();
-            dbg!(at);
-            let (ln, clm) = if self.is_inside_bounds(origin) { self.lookup.get(origin.start) } else {(0, 0)};
-            let content = &self.original_source[origin.start..=at.end];
-            let highlighted = &self.create_spanned_chars(&at, '^')[origin.start..=at.end];
-            println!("> {}", content);
-            println!("  {}", highlighted.red());
-            eprintln!("Compiler error at line {}:{}\n{}", ln, clm, err);
-            if exit {
-                std::process::exit(1);
+
+    fn bump(&mut self) -> Token {
+        let token = self.tokens[self.cursor].clone();
+        self.cursor += 1;
+        token
+    }
+
+    fn eat(&mut self, kind: &Tokens) -> bool {
+        if self.check(kind) {
+            self.cursor += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn previous(&self) -> Option<&Token> {
+        self.cursor.checked_sub(1).and_then(|ix| self.tokens.get(ix))
+    }
+
+    fn prev_end(&self) -> usize {
+        self.previous().map(|t| t.span.end).unwrap_or(0)
+    }
+
+    fn current_start(&self) -> usize {
+        match self.peek() {
+            Some(token) => token.span.start,
+            None => self.prev_end()
+        }
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span { start, end: self.prev_end().max(start) }
+    }
+
+    fn eof_span(&self) -> Span {
+        let end = self.prev_end();
+        Span { start: end.saturating_sub(1), end }
+    }
+
+    fn error_at(&self, kind: ParseErrors, at: Span) -> Box<ParseError> {
+        Box::new(ParseError { kind, origin: self.origin.clone(), at, fix: None })
+    }
+
+    /// Error for the current token, which was not what the grammar expected.
+    fn unexpected(&self) -> Box<ParseError> {
+        match self.peek() {
+            Some(token) => self.error_at(ParseErrors::UnexpectedToken(token.kind.clone()), token.span.clone()),
+            None => self.error_at(ParseErrors::UnexpectedEof, self.eof_span())
+        }
+    }
+
+    /// Error for the current token, which should have been `kind`.
+    fn expected(&self, kind: Tokens) -> Box<ParseError> {
+        match (self.previous(), self.peek()) {
+            (_, None) => self.error_at(ParseErrors::UnexpectedEof, self.eof_span()),
+            (Some(prev), Some(recv)) => self.error_at(ParseErrors::ExpectedAfterButReceived(kind, prev.kind.clone(), recv.kind.clone()), recv.span.clone()),
+            (None, Some(recv)) => self.error_at(ParseErrors::ExpectedButReceived(kind, recv.kind.clone()), recv.span.clone()),
+        }
+    }
+
+    fn expect(&mut self, kind: Tokens) -> ParseResult<Token> {
+        if self.check(&kind) {
+            Ok(self.bump())
+        } else {
+            Err(self.expected(kind))
+        }
+    }
+
+    fn expect_ident(&mut self) -> ParseResult<(String, Span)> {
+        match self.peek() {
+            Some(Token { kind: Tokens::Ident(name), span }) => {
+                let res = (name.clone(), span.clone());
+                self.cursor += 1;
+                Ok(res)
             }
+            _ => Err(self.expected(Tokens::Ident(String::new())))
+        }
     }
 
-    fn create_spanned_chars(&self, span: &Span, with: char) -> String {
-        let mut cnt = self.original_source.to_string().chars().collect::<Vec<char>>();
-        for ix in span.start..=span.end {
-            cnt.insert(ix, with);
+    fn take_comments(&mut self) -> Vec<SpannedCommend> {
+        let mut taken = Vec::new();
+        while let Some((ix, comment)) = self.comments.get(self.comment_cursor) {
+            if *ix > self.cursor {
+                break;
+            }
+            taken.push(comment.clone());
+            self.comment_cursor += 1;
         }
-        cnt.iter().map(|chr| if chr != &with {' '} else {*chr}).collect::<String>()
+        taken
     }
-}
 
+    // ---- error recovery ----
 
+    fn report(&mut self, error: ParseError) {
+        self.errors.push(error);
+    }
 
+    /// Expects a keyword delimiting a block, `then`, `do`, `until` or `end`. A missing one is reported and
+    /// assumed, so that the statements around it still parse.
+    fn expect_delimiter(&mut self, kind: Tokens) {
+        if self.eat(&kind) {
+            return;
+        }
+        let at = self.prev_end();
+        // the keyword surely goes after what came before when the next statement or the end of the file follows
+        let insert = match kind {
+            Tokens::Then | Tokens::Do if self.peek().is_none() || self.line_starts[self.cursor] || self.at_statement_keyword() => Some(" "),
+            Tokens::End if self.peek().is_none() => Some("\n"),
+            _ => None,
+        };
+        let keyword = keyword_or_symbol(&kind).unwrap_or_default();
+        let mut error = *self.expected(kind);
+        if let Some(separator) = insert {
+            error = error.fix(&format!("insert the missing `{}`", keyword), vec![Edit { span: Span { start: at, end: at }, replacement: format!("{}{}", separator, keyword) }]);
+        }
+        self.report(error);
+    }
 
-fn calc_line_and_col(text: &LineColLookup, span: &Span) -> (usize, usize) {
-    text.get(span.start)
-}
+    fn at_statement_keyword(&self) -> bool {
+        matches!(self.peek_kind(), Some(Tokens::Local | Tokens::Function | Tokens::If | Tokens::While | Tokens::For
+            | Tokens::Repeat | Tokens::Return | Tokens::Do | Tokens::Break | Tokens::Goto | Tokens::Switch
+            | Tokens::Class | Tokens::Interface)) || self.at_import() || self.at_export()
+    }
+
+    /// Skips the rest of a statement that failed to parse from `start`: past the next `;`, or up to the next
+    /// statement keyword, block terminator or line. Returns the placeholder of the statement.
+    fn synchronize(&mut self, error: ParseError, start: usize, comments: Vec<SpannedCommend>) -> AstNode {
+        self.report(error);
+        let start_offset = self.tokens.get(start).map_or(self.prev_end(), |token| token.span.start);
+        if self.cursor == start && self.peek().is_some() {
+            self.bump();
+        }
+        while self.peek().is_some() && !self.line_starts[self.cursor] && !self.at_statement_keyword() && !self.block_follows() {
+            if self.bump().kind == Tokens::SemiColon {
+                break;
+            }
+        }
+        AstNode { kind: AstNodes::Error, span: self.span_from(start_offset), comments }
+    }
+
+    /// Skips the rest of an expression or list item that failed to parse from `start`, up to the first of `follow`
+    /// outside of brackets and function bodies, which is left for the caller. Returns the placeholder of the item,
+    /// or the error when a statement keyword, block terminator or the end of the file comes first.
+    fn recover_item(&mut self, error: Box<ParseError>, start: usize, follow: &[Tokens]) -> ParseResult<Expression> {
+        let from = self.cursor;
+        let mut depth = 0usize;
+        while let Some(kind) = self.peek_kind() {
+            if depth == 0 && follow.contains(kind) {
+                self.report(*error);
+                return Ok(Expression { kind: Expressions::Error, span: self.span_from(start) });
+            }
+            match kind {
+                Tokens::LParen | Tokens::LBracket | Tokens::LCurly | Tokens::Function => depth += 1,
+                Tokens::RParen | Tokens::RBracket | Tokens::RCurly | Tokens::End if depth > 0 => depth -= 1,
+                _ if self.at_statement_keyword() || self.block_follows() => break,
+                // a closing bracket of an enclosing list
+                Tokens::RParen | Tokens::RBracket | Tokens::RCurly => break,
+                _ => {}
+            }
+            self.bump();
+        }
+        self.cursor = from;
+        Err(error)
+    }
 
+    /// An expression followed by one of `follow`, such as the condition of an `if` before `then`.
+    fn parse_expression_before(&mut self, follow: &[Tokens]) -> ParseResult<Expression> {
+        let start = self.current_start();
+        match self.parse_expression() {
+            Ok(expr) => Ok(expr),
+            Err(error) => self.recover_item(error, start, follow),
+        }
+    }
 
-type TokenIter<'a> = Peekable<Enumerate<Iter<'a, Token>>>;
+    // ---- statements ----
 
-struct Parser<'a> {
-    err_client: ErrorClient<'a>,
-    iter: &'a mut TokenIter<'a>,
-    source: &'a String
-}
+    fn block_follows(&self) -> bool {
+        matches!(self.peek_kind(), None | Some(Tokens::End | Tokens::Else | Tokens::ElseIf | Tokens::Until))
+            || self.in_switch && self.check_contextual("case")
+    }
 
+    /// Statements up to a block terminator. A statement that fails to parse is reported and replaced by an
+    /// `Error` node, parsing goes on with the next one.
+    fn parse_block(&mut self) -> Block {
+        let mut block = Vec::new();
+        while !self.block_follows() {
+            if self.eat(&Tokens::SemiColon) {
+                continue;
+            }
+            let is_return = self.check(&Tokens::Return);
+            let start = self.cursor;
+            let comments = self.take_comments();
+            let outer = self.origin.clone();
+            let statement = self.parse_statement(comments.clone());
+            self.origin = outer;
+            match statement {
+                Ok(node) => block.push(node),
+                Err(error) => {
+                    block.push(self.synchronize(*error, start, comments));
+                    continue;
+                }
+            }
+            if is_return {
+                // `return` must be the last statement of a block
+                self.eat(&Tokens::SemiColon);
+                break;
+            }
+        }
+        block
+    }
 
+    fn parse_statement(&mut self, comments: Vec<SpannedCommend>) -> ParseResult<AstNode> {
+        let token = self.peek().expect("parse_statement called at end of input").clone();
+        self.origin = token.span.clone();
+        let start = token.span.start;
+        let kind = match token.kind {
+            Tokens::Local => self.parse_local()?,
+            Tokens::Function => self.parse_function_stat()?,
+            Tokens::If => self.parse_if()?,
+            Tokens::While => self.parse_while()?,
+            Tokens::Switch => self.parse_switch()?,
+            Tokens::Do => {
+                self.bump();
+                let body = self.parse_block();
+                self.expect_delimiter(Tokens::End);
+                AstNodes::Do(body)
+            }
+            Tokens::For => self.parse_for()?,
+            Tokens::Repeat => self.parse_repeat()?,
+            Tokens::Return => self.parse_return()?,
+            Tokens::Break => {
+                self.bump();
+                AstNodes::Break
+            }
+            Tokens::Goto => {
+                self.bump();
+                AstNodes::Goto(self.expect_ident()?.0)
+            }
+            Tokens::DBColon => {
+                self.bump();
+                let (name, _) = self.expect_ident()?;
+                self.expect(Tokens::DBColon)?;
+                AstNodes::Label(name)
+            }
+            Tokens::Class => self.parse_class()?,
+            Tokens::Interface => self.parse_interface()?,
+            Tokens::Ident(_) if self.at_import() => self.parse_import()?,
+            Tokens::Ident(_) if self.at_export() => self.parse_export()?,
+            Tokens::Type if matches!(self.peek_nth_kind(1), Some(Tokens::Ident(_))) => self.parse_type_alias()?,
+            _ => self.parse_expr_stat()?
+        };
+        Ok(AstNode { kind, span: self.span_from(start), comments })
+    }
 
-impl <'a>Parser<'a> {
-    pub fn parse(&mut self) {
-        while let Some((ix, token)) = self.iter.next() {
-            match token.kind {
-                Tokens::Class => self.parse_class(ix, token),
-                _ => {} //never happens
+    fn check_contextual(&self, keyword: &str) -> bool {
+        matches!(self.peek_kind(), Some(Tokens::Ident(name)) if name == keyword)
+    }
+
+    /// Consumes an identifier used as a keyword, `from` or `as`, if it comes next.
+    fn eat_contextual(&mut self, keyword: &str) -> bool {
+        if self.check_contextual(keyword) {
+            self.cursor += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether an `import` declaration comes next. `import` stays a name where Lua code can use one, as in
+    /// `import = require` or `import { "a" }`, a call with a table.
+    fn at_import(&self) -> bool {
+        if self.check_contextual("import") && self.peek_nth_kind(1) == Some(&Tokens::LCurly) {
+            let close = self.tokens[self.cursor + 1..].iter().position(|token| token.kind == Tokens::RCurly);
+            return close.is_some_and(|ix| matches!(self.peek_nth_kind(ix + 2), Some(Tokens::Ident(name)) if name == "from"));
+        }
+        self.at_declaration("import")
+    }
+
+    fn at_export(&self) -> bool {
+        self.at_declaration("export")
+    }
+
+    /// Whether `keyword` comes next followed by what cannot follow a name in a Lua statement.
+    fn at_declaration(&self, keyword: &str) -> bool {
+        self.check_contextual(keyword) && !matches!(self.peek_nth_kind(1), None | Some(Tokens::Assign | Tokens::Comma
+            | Tokens::Period | Tokens::LBracket | Tokens::Colon | Tokens::LParen | Tokens::String(_) | Tokens::LCurly))
+    }
+
+    /// Consumes a modifier of a class member, `protected` or `static`, when a name follows it. A member can
+    /// be named after a modifier, as in `static: boolean`.
+    fn eat_modifier(&mut self, modifier: &str) -> bool {
+        if matches!(self.peek_nth_kind(1), Some(Tokens::Ident(_))) {
+            self.eat_contextual(modifier)
+        } else {
+            false
+        }
+    }
+
+    fn expect_contextual(&mut self, keyword: &'static str) -> ParseResult<()> {
+        if self.eat_contextual(keyword) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(self.error_at(ParseErrors::ExpectedKeyword(keyword, token.kind.clone()), token.span.clone())),
+            None => Err(self.error_at(ParseErrors::UnexpectedEof, self.eof_span())),
+        }
+    }
+
+    /// `import [type] [default][,] [{ a, b as c } | * as m] from "mod"`
+    fn parse_import(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let type_only = self.eat(&Tokens::Type);
+        let mut names = Vec::new();
+        let mut namespace = None;
+        if matches!(self.peek_kind(), Some(Tokens::Ident(name)) if name != "from") {
+            let (local, span) = self.expect_ident()?;
+            names.push(ImportName { name: "default".to_string(), local, type_only: false, span });
+            if !self.eat(&Tokens::Comma) {
+                return self.parse_import_source(names, namespace, type_only);
+            }
+        }
+        if self.eat(&Tokens::Mul) {
+            self.expect_contextual("as")?;
+            namespace = Some(self.expect_ident()?.0);
+        } else if self.eat(&Tokens::LCurly) {
+            while !self.check(&Tokens::RCurly) {
+                let (name, span) = self.expect_ident()?;
+                let local = if self.eat_contextual("as") { self.expect_ident()?.0 } else { name.clone() };
+                names.push(ImportName { name, local, type_only: false, span });
+                if !self.eat(&Tokens::Comma) {
+                    break;
+                }
+            }
+            self.expect(Tokens::RCurly)?;
+        }
+        self.parse_import_source(names, namespace, type_only)
+    }
+
+    fn parse_import_source(&mut self, names: Vec<ImportName>, namespace: Option<String>, type_only: bool) -> ParseResult<AstNodes> {
+        self.expect_contextual("from")?;
+        match self.peek_kind() {
+            Some(Tokens::String(module)) => {
+                let module = module.clone();
+                self.bump();
+                Ok(AstNodes::Import(ImportNode { module, names, namespace, type_only }))
+            }
+            _ => Err(self.expected(Tokens::String(String::new()))),
+        }
+    }
+
+    /// `export default value`, `export const a = 1` or `export` before a declaration.
+    /// An exported `function f()` is local to the file.
+    fn parse_export(&mut self) -> ParseResult<AstNodes> {
+        let start = self.bump().span.start;
+        if self.eat_contextual("default") {
+            return Ok(AstNodes::ExportDefault(self.parse_expression()?));
+        }
+        if self.eat(&Tokens::Const) {
+            let mut names = self.parse_att_name_list()?;
+            for name in &mut names {
+                name.attrib = Some(Attrib::Const);
+            }
+            self.expect(Tokens::Assign)?;
+            let values = self.parse_expression_list()?;
+            let declaration = AstNode { kind: AstNodes::LocalAssign { names, values }, span: self.span_from(start), comments: Vec::new() };
+            return Ok(AstNodes::Export(Box::new(declaration)));
+        }
+        let comments = self.take_comments();
+        let mut declaration = self.parse_statement(comments)?;
+        declaration.kind = match declaration.kind {
+            AstNodes::Function(func) if func.path.is_empty() => AstNodes::LocalFunction(func),
+            kind @ (AstNodes::LocalFunction(_) | AstNodes::LocalAssign { .. } | AstNodes::Class(_)
+                | AstNodes::Interface(_) | AstNodes::TypeAlias { .. }) => kind,
+            _ => return Err(self.error_at(ParseErrors::InvalidExport, declaration.span)),
+        };
+        Ok(AstNodes::Export(Box::new(declaration)))
+    }
+
+    fn parse_local(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        if self.check(&Tokens::Function) {
+            let start = self.bump().span.start;
+            let (name, _) = self.expect_ident()?;
+            let mut func = self.parse_function_body(start)?;
+            func.name = name;
+            return Ok(AstNodes::LocalFunction(func));
+        }
+        let names = self.parse_att_name_list()?;
+        let values = if self.eat(&Tokens::Assign) {
+            self.parse_expression_list()?
+        } else {
+            Vec::new()
+        };
+        Ok(AstNodes::LocalAssign { names, values })
+    }
+
+    fn parse_att_name_list(&mut self) -> ParseResult<Vec<NamedTokenWithTypeInfo>> {
+        let mut names = Vec::new();
+        loop {
+            let mut name = self.parse_typed_name()?;
+            if self.check(&Tokens::LT) {
+                self.bump();
+                name.attrib = Some(match self.peek_kind() {
+                    Some(Tokens::Const) => Attrib::Const,
+                    Some(Tokens::Ident(attrib)) if attrib == "close" => Attrib::Close,
+                    _ => return Err(self.unexpected())
+                });
+                self.bump();
+                self.expect(Tokens::GT)?;
+            }
+            names.push(name);
+            if !self.eat(&Tokens::Comma) {
+                break;
+            }
+        }
+        Ok(names)
+    }
+
+    /// `name[: Type]`
+    fn parse_typed_name(&mut self) -> ParseResult<NamedTokenWithTypeInfo> {
+        let (name, span) = self.expect_ident()?;
+        let typ = if self.eat(&Tokens::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        Ok(NamedTokenWithTypeInfo { name, typ, attrib: None, span })
+    }
+
+    // ---- types ----
+
+    /// `type Name<T extends C = D> = T`
+    fn parse_type_alias(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let (name, _) = self.expect_ident()?;
+        let generics = self.parse_generic_params()?;
+        self.expect(Tokens::Assign)?;
+        let typ = self.parse_type()?;
+        Ok(AstNodes::TypeAlias { name, generics, typ })
+    }
+
+    /// Optional `<T extends C = D, U>` list.
+    fn parse_generic_params(&mut self) -> ParseResult<Vec<GenericParam>> {
+        let mut generics = Vec::new();
+        if !self.eat(&Tokens::LT) {
+            return Ok(generics);
+        }
+        loop {
+            let (name, span) = self.expect_ident()?;
+            let constraint = if self.eat(&Tokens::Extends) {
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            let default = if self.eat(&Tokens::Assign) {
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            generics.push(GenericParam { name, constraint, default, span });
+            if !self.eat(&Tokens::Comma) {
+                break;
+            }
+        }
+        self.expect_type_close()?;
+        Ok(generics)
+    }
+
+    /// A type, optionally conditional: `Check extends Extends ? Then : Otherwise`.
+    fn parse_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_start();
+        let check = self.parse_union_type()?;
+        if !self.eat(&Tokens::Extends) {
+            return Ok(check);
+        }
+        let outer = std::mem::replace(&mut self.in_extends_clause, true);
+        let extends = self.parse_union_type();
+        self.in_extends_clause = outer;
+        let extends = extends?;
+        self.expect(Tokens::Qmark)?;
+        let then = self.parse_type()?;
+        self.expect(Tokens::Colon)?;
+        let otherwise = self.parse_type()?;
+        Ok(Type {
+            kind: Types::Conditional { check: Box::new(check), extends: Box::new(extends), then: Box::new(then), otherwise: Box::new(otherwise) },
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses a type nested inside delimiters, where `?` is always the optional suffix.
+    /// The returns of a function, a type or `...T` for any number of values.
+    fn parse_return_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_start();
+        if self.eat(&Tokens::Dots) {
+            let inner = self.parse_nested_type()?;
+            return Ok(Type { kind: Types::Variadic(Box::new(inner)), span: self.span_from(start) });
+        }
+        self.parse_nested_type()
+    }
+
+    fn parse_nested_type(&mut self) -> ParseResult<Type> {
+        let outer = std::mem::replace(&mut self.in_extends_clause, false);
+        let typ = self.parse_type();
+        self.in_extends_clause = outer;
+        typ
+    }
+
+    /// `A | B`, the loosest binding type operator.
+    fn parse_union_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_start();
+        self.eat(&Tokens::BitOr);
+        let first = self.parse_intersection_type()?;
+        if !self.check(&Tokens::BitOr) {
+            return Ok(first);
+        }
+        let mut types = vec![first];
+        while self.eat(&Tokens::BitOr) {
+            types.push(self.parse_intersection_type()?);
+        }
+        Ok(Type { kind: Types::Union(types), span: self.span_from(start) })
+    }
+
+    fn parse_intersection_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_start();
+        let first = self.parse_postfix_type()?;
+        if !self.check(&Tokens::BitAnd) {
+            return Ok(first);
+        }
+        let mut types = vec![first];
+        while self.eat(&Tokens::BitAnd) {
+            types.push(self.parse_postfix_type()?);
+        }
+        Ok(Type { kind: Types::Intersection(types), span: self.span_from(start) })
+    }
+
+    /// Primary type followed by any number of `?` and `[]`.
+    fn parse_postfix_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_start();
+        let mut typ = self.parse_primary_type()?;
+        loop {
+            let kind = if !self.in_extends_clause && self.eat(&Tokens::Qmark) {
+                Types::Optional(Box::new(typ))
+            } else if self.check(&Tokens::LBracket) && self.peek_nth_kind(1) == Some(&Tokens::RBracket) {
+                self.cursor += 2;
+                Types::Array(Box::new(typ))
+            } else {
+                return Ok(typ);
+            };
+            typ = Type { kind, span: self.span_from(start) };
+        }
+    }
+
+    fn parse_primary_type(&mut self) -> ParseResult<Type> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected())
+        };
+        let kind = match token.kind {
+            Tokens::Nil => Types::Nil,
+            Tokens::True => Types::Literal(LiteralType::Boolean(true)),
+            Tokens::False => Types::Literal(LiteralType::Boolean(false)),
+            Tokens::String(s) => Types::Literal(LiteralType::String(s)),
+            Tokens::Number(num) | Tokens::Float(num) => Types::Literal(LiteralType::Number(num)),
+            Tokens::Minus => {
+                self.bump();
+                return match self.peek_kind() {
+                    Some(Tokens::Number(num) | Tokens::Float(num)) => {
+                        let num = -*num;
+                        self.bump();
+                        Ok(Type { kind: Types::Literal(LiteralType::Number(num)), span: self.span_from(token.span.start) })
+                    }
+                    _ => Err(self.expected(Tokens::Number(0.0)))
+                };
+            }
+            Tokens::Ident(_) => return self.parse_named_type(),
+            Tokens::LCurly => return self.parse_table_type(),
+            Tokens::LParen => return self.parse_parenthesized_type(),
+            other => return Err(self.error_at(ParseErrors::ExpectedType(other), token.span))
+        };
+        self.bump();
+        Ok(Type { kind, span: token.span })
+    }
+
+    /// `name`, `mod.name` and generic applications `name<A, B>`.
+    fn parse_named_type(&mut self) -> ParseResult<Type> {
+        let (mut name, span) = self.expect_ident()?;
+        while self.check(&Tokens::Period) {
+            self.bump();
+            name.push('.');
+            name.push_str(&self.expect_ident()?.0);
+        }
+        let mut args = Vec::new();
+        if self.eat(&Tokens::LT) {
+            loop {
+                args.push(self.parse_nested_type()?);
+                if !self.eat(&Tokens::Comma) {
+                    break;
+                }
+            }
+            self.expect_type_close()?;
+        }
+        Ok(Type { kind: Types::Named { name, args }, span: self.span_from(span.start) })
+    }
+
+    /// Closes a type argument list, splitting `>>` and `>=` that the lexer joined.
+    fn expect_type_close(&mut self) -> ParseResult<()> {
+        let rest = match self.peek_kind() {
+            Some(Tokens::SHR) => Tokens::GT,
+            Some(Tokens::GTE) => Tokens::Assign,
+            _ => return self.expect(Tokens::GT).map(|_| ())
+        };
+        let token = &mut self.tokens[self.cursor];
+        token.kind = rest;
+        token.span.start += 1;
+        Ok(())
+    }
+
+    /// `{ name: T, optional?: T, method(a: A): R, [K]: V }` or the array shorthand `{ T }`.
+    fn parse_table_type(&mut self) -> ParseResult<Type> {
+        let start = self.expect(Tokens::LCurly)?.span.start;
+        let mut table = TableType::default();
+        let mut element = None;
+        while !self.check(&Tokens::RCurly) {
+            if !self.parse_type_member(&mut table)? {
+                element = Some(self.parse_nested_type()?);
             }
+            if !self.eat(&Tokens::Comma) && !self.eat(&Tokens::SemiColon) {
+                break;
+            }
+        }
+        self.expect(Tokens::RCurly)?;
+        let kind = match element {
+            Some(element) if table.fields.is_empty() && table.indexers.is_empty() => Types::Array(Box::new(element)),
+            _ => Types::Table(table)
+        };
+        Ok(Type { kind, span: self.span_from(start) })
+    }
+
+    /// One member of a table type or interface body: an index signature `[K]: V`, a property
+    /// `name?: T` or a method signature `name(a: A): R`. Returns false if no member starts here.
+    fn parse_type_member(&mut self, table: &mut TableType) -> ParseResult<bool> {
+        if self.eat(&Tokens::LBracket) {
+            let key = self.parse_nested_type()?;
+            self.expect(Tokens::RBracket)?;
+            self.expect(Tokens::Colon)?;
+            table.indexers.push((key, self.parse_nested_type()?));
+            return Ok(true);
+        }
+        let is_method = self.at_member_name() && matches!(
+            (self.peek_nth_kind(1), self.peek_nth_kind(2)),
+            (Some(Tokens::LParen), _) | (Some(Tokens::Qmark), Some(Tokens::LParen))
+        );
+        if is_method {
+            let start = self.current_start();
+            let name = self.expect_member_name()?;
+            let optional = self.eat(&Tokens::Qmark);
+            let (params, varargs, _) = self.parse_function_type_params()?;
+            let returns = if self.eat(&Tokens::Colon) {
+                self.parse_return_type()?
+            } else {
+                Type { kind: Types::Tuple(Vec::new()), span: self.span_from(start) }
+            };
+            let typ = Type { kind: Types::Function(FunctionType { params, varargs, returns: Box::new(returns) }), span: self.span_from(start) };
+            table.fields.push(TableTypeField { name, typ, optional, is_method: true });
+            return Ok(true);
+        }
+        if self.at_type_field() {
+            table.fields.push(self.parse_table_type_field()?);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn at_type_field(&self) -> bool {
+        self.at_member_name() && matches!(
+            (self.peek_nth_kind(1), self.peek_nth_kind(2)),
+            (Some(Tokens::Colon), _) | (Some(Tokens::Qmark), Some(Tokens::Colon))
+        )
+    }
+
+    /// Fields and methods may be named `type`, as `math.type` is.
+    fn at_member_name(&self) -> bool {
+        matches!(self.peek_kind(), Some(Tokens::Ident(_) | Tokens::Type))
+    }
+
+    fn expect_member_name(&mut self) -> ParseResult<String> {
+        if self.eat(&Tokens::Type) {
+            return Ok("type".to_string());
         }
+        Ok(self.expect_ident()?.0)
     }
-    fn parse_class(&mut self, ix: usize, start: &Token) {
-        let next = self.iter.peek();
-        if next.is_some() {
-            let (_, next) = next.unwrap();
-            if let Tokens::Ident(ident) = &next.kind {
 
+    /// `name: T` or `name?: T`
+    fn parse_table_type_field(&mut self) -> ParseResult<TableTypeField> {
+        let name = self.expect_member_name()?;
+        let optional = self.eat(&Tokens::Qmark);
+        self.expect(Tokens::Colon)?;
+        Ok(TableTypeField { name, typ: self.parse_nested_type()?, optional, is_method: false })
+    }
+
+    /// `(a: A, B, ...C)`, returning the parameters, the varargs type and whether a trailing comma was seen.
+    fn parse_function_type_params(&mut self) -> ParseResult<(Vec<FunctionTypeParam>, Option<Box<Type>>, bool)> {
+        self.expect(Tokens::LParen)?;
+        let mut params = Vec::new();
+        let mut varargs = None;
+        let mut trailing_comma = false;
+        while !self.check(&Tokens::RParen) {
+            trailing_comma = false;
+            if self.eat(&Tokens::Dots) {
+                self.eat(&Tokens::Colon);
+                varargs = Some(Box::new(self.parse_nested_type()?));
+                break;
+            }
+            let param = if self.at_type_field() {
+                let field = self.parse_table_type_field()?;
+                FunctionTypeParam { name: Some(field.name), typ: field.typ, optional: field.optional }
             } else {
-                self.err_client.raise_parse_err(ParseErrors::ExpectedAfterButReceived(Tokens::Ident("".to_string()), start.kind.clone(), next.kind.clone()), &start.span, &next.span, true);
+                FunctionTypeParam { name: None, typ: self.parse_nested_type()?, optional: false }
+            };
+            params.push(param);
+            if !self.eat(&Tokens::Comma) {
+                break;
             }
+            trailing_comma = true;
+        }
+        self.expect(Tokens::RParen)?;
+        Ok((params, varargs, trailing_comma))
+    }
+
+    /// A function type `(a: A, B, ...C) -> R`, a tuple `(A, B)` or a parenthesized type `(A)`.
+    fn parse_parenthesized_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_start();
+        let (mut params, varargs, trailing_comma) = self.parse_function_type_params()?;
+        if self.eat(&Tokens::ThinArrow) {
+            let returns = Box::new(self.parse_return_type()?);
+            return Ok(Type { kind: Types::Function(FunctionType { params, varargs, returns }), span: self.span_from(start) });
+        }
+        let is_grouping = params.len() == 1 && params[0].name.is_none() && varargs.is_none() && !trailing_comma;
+        if is_grouping {
+            return Ok(params.remove(0).typ);
+        }
+        if params.iter().any(|param| param.name.is_some()) {
+            return Err(self.expected(Tokens::ThinArrow));
+        }
+        let mut types: Vec<Type> = params.into_iter().map(|param| param.typ).collect();
+        // `(A, ...B)`, returned values
+        if let Some(varargs) = varargs {
+            let span = varargs.span.clone();
+            types.push(Type { kind: Types::Variadic(varargs), span });
+        }
+        Ok(Type { kind: Types::Tuple(types), span: self.span_from(start) })
+    }
+
+    /// `interface Name<T> extends A, B<T> members... end`
+    fn parse_interface(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let (name, _) = self.expect_ident()?;
+        let generics = self.parse_generic_params()?;
+        let mut extends = Vec::new();
+        if self.eat(&Tokens::Extends) {
+            loop {
+                extends.push(self.parse_named_type()?);
+                if !self.eat(&Tokens::Comma) {
+                    break;
+                }
+            }
+        }
+        let mut body = TableType::default();
+        while !self.eat(&Tokens::End) {
+            if self.eat(&Tokens::Comma) || self.eat(&Tokens::SemiColon) {
+                continue;
+            }
+            if !self.parse_type_member(&mut body)? {
+                return Err(self.unexpected());
+            }
+        }
+        Ok(AstNodes::Interface(InterfaceNode { name, generics, extends, body }))
+    }
+
+    fn parse_function_stat(&mut self) -> ParseResult<AstNodes> {
+        let start = self.bump().span.start;
+        let (mut name, _) = self.expect_ident()?;
+        let mut path = Vec::new();
+        let mut is_method = false;
+        while self.eat(&Tokens::Period) {
+            path.push(name);
+            name = self.expect_member_name()?;
+        }
+        if self.eat(&Tokens::Colon) {
+            path.push(name);
+            name = self.expect_member_name()?;
+            is_method = true;
+        }
+        let mut func = self.parse_function_body(start)?;
+        func.name = name;
+        func.path = path;
+        func.is_method = is_method;
+        Ok(AstNodes::Function(func))
+    }
+
+    /// Parameter list, optional return annotation and body of a function, up to and including `end`.
+    fn parse_function_body(&mut self, start: usize) -> ParseResult<FunctionDecl> {
+        self.expect(Tokens::LParen)?;
+        let mut args = Vec::new();
+        let mut varargs = false;
+        let mut varargs_typ = None;
+        if !self.check(&Tokens::RParen) {
+            loop {
+                if self.eat(&Tokens::Dots) {
+                    varargs = true;
+                    if self.eat(&Tokens::Colon) {
+                        varargs_typ = Some(self.parse_type()?);
+                    }
+                    break;
+                }
+                let arg_start = self.current_start();
+                match self.parse_typed_name() {
+                    Ok(arg) => args.push(arg),
+                    // a parameter that does not parse is left out
+                    Err(error) => {
+                        self.recover_item(error, arg_start, &[Tokens::Comma, Tokens::RParen])?;
+                    }
+                }
+                if !self.eat(&Tokens::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect(Tokens::RParen)?;
+        let returns = if self.eat(&Tokens::Colon) {
+            Some(self.parse_return_type()?)
         } else {
-            //todo! raise compiler error
+            None
+        };
+        // a function in a case of a switch, `case` is a name again in its body
+        let in_switch = std::mem::replace(&mut self.in_switch, false);
+        let body = self.parse_block();
+        self.in_switch = in_switch;
+        self.expect_delimiter(Tokens::End);
+        Ok(FunctionDecl {
+            name: String::new(),
+            path: Vec::new(),
+            is_method: false,
+            args,
+            varargs,
+            varargs_typ,
+            returns,
+            body,
+            is_class: false,
+            is_arrow: false,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_if(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let condition = self.parse_expression_before(&[Tokens::Then])?;
+        self.expect_delimiter(Tokens::Then);
+        let body = self.parse_block();
+        let mut else_ifs = Vec::new();
+        let mut else_body = None;
+        loop {
+            if self.eat(&Tokens::ElseIf) {
+                let cond = self.parse_expression_before(&[Tokens::Then])?;
+                self.expect_delimiter(Tokens::Then);
+                else_ifs.push((cond, self.parse_block()));
+            } else if self.eat(&Tokens::Else) {
+                else_body = Some(self.parse_block());
+                self.expect_delimiter(Tokens::End);
+                break;
+            } else {
+                self.expect_delimiter(Tokens::End);
+                break;
+            }
         }
+        Ok(AstNodes::If { condition, body, else_ifs, else_body })
+    }
+
+    fn parse_while(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let condition = self.parse_expression_before(&[Tokens::Do])?;
+        self.expect_delimiter(Tokens::Do);
+        let body = self.parse_block();
+        self.expect_delimiter(Tokens::End);
+        Ok(AstNodes::While { condition, body })
+    }
+
+    /// `switch subject case a, b then ... case c then ... else ... end`
+    fn parse_switch(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let subject = self.parse_expression()?;
+        let mut cases = Vec::new();
+        let outer = std::mem::replace(&mut self.in_switch, true);
+        while self.eat_contextual("case") {
+            let values = self.parse_expression_list()?;
+            self.expect_delimiter(Tokens::Then);
+            cases.push((values, self.parse_block()));
+        }
+        self.in_switch = outer;
+        let default = if self.eat(&Tokens::Else) { Some(self.parse_block()) } else { None };
+        self.expect_delimiter(Tokens::End);
+        Ok(AstNodes::Switch { subject, cases, default })
+    }
+
+    fn parse_repeat(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let body = self.parse_block();
+        self.expect_delimiter(Tokens::Until);
+        let condition = self.parse_expression()?;
+        Ok(AstNodes::Repeat { body, condition })
+    }
+
+    fn parse_for(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let first = self.parse_typed_name()?;
+        if self.eat(&Tokens::Assign) {
+            let start = self.parse_expression()?;
+            self.expect(Tokens::Comma)?;
+            let limit = self.parse_expression()?;
+            let step = if self.eat(&Tokens::Comma) {
+                Some(self.parse_expression()?)
+            } else {
+                None
+            };
+            self.expect_delimiter(Tokens::Do);
+            let body = self.parse_block();
+            self.expect_delimiter(Tokens::End);
+            return Ok(AstNodes::NumericFor { var: first, start, limit, step, body });
+        }
+        let mut names = vec![first];
+        while self.eat(&Tokens::Comma) {
+            names.push(self.parse_typed_name()?);
+        }
+        self.expect(Tokens::In)?;
+        let exprs = self.parse_expression_list()?;
+        self.expect_delimiter(Tokens::Do);
+        let body = self.parse_block();
+        self.expect_delimiter(Tokens::End);
+        Ok(AstNodes::GenericFor { names, exprs, body })
+    }
+
+    fn parse_return(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        if self.block_follows() || self.check(&Tokens::SemiColon) {
+            return Ok(AstNodes::Return(Vec::new()));
+        }
+        Ok(AstNodes::Return(self.parse_expression_list()?))
+    }
+
+    /// `class Name [extends Base] [implements A, B] members... end`
+    fn parse_class(&mut self) -> ParseResult<AstNodes> {
+        self.bump();
+        let (name, _) = self.expect_ident()?;
+        let extends = if self.eat(&Tokens::Extends) {
+            Some(self.expect_ident()?.0)
+        } else {
+            None
+        };
+        let mut implements = Vec::new();
+        if self.eat(&Tokens::Implements) {
+            loop {
+                implements.push(self.expect_ident()?.0);
+                if !self.eat(&Tokens::Comma) {
+                    break;
+                }
+            }
+        }
+        let mut class = ClassNode { name, extends, implements, constructor: None, methods: Vec::new(), fields: Vec::new() };
+        while !self.eat(&Tokens::End) {
+            if self.eat(&Tokens::SemiColon) {
+                continue;
+            }
+            self.parse_class_member(&mut class)?;
+        }
+        Ok(AstNodes::Class(class))
+    }
+
+    /// `[public|private|protected] [static] name[: T] [= value]` or a method with the same modifiers.
+    fn parse_class_member(&mut self, class: &mut ClassNode) -> ParseResult<()> {
+        let start = self.current_start();
+        let visibility = if self.eat(&Tokens::Private) {
+            Visibility::Private
+        } else if self.eat_modifier("protected") {
+            Visibility::Protected
+        } else {
+            self.eat(&Tokens::Public);
+            Visibility::Public
+        };
+        let is_static = self.eat_modifier("static");
+        if !matches!(self.peek_kind(), Some(Tokens::Ident(_))) {
+            return Err(self.expected(Tokens::Ident(String::new())));
+        }
+        if self.peek_nth_kind(1) == Some(&Tokens::LParen) {
+            let (name, span) = self.expect_ident()?;
+            let mut func = self.parse_function_body(start)?;
+            func.name = name;
+            func.path = vec![class.name.clone()];
+            func.is_method = !is_static;
+            func.is_class = true;
+            if func.name == "constructor" {
+                if is_static || class.constructor.is_some() {
+                    return Err(self.error_at(ParseErrors::InvalidConstructor, span));
+                }
+                class.constructor = Some(func);
+            } else {
+                class.methods.push(ClassMethod { visibility, is_static, func });
+            }
+            return Ok(());
+        }
+        let field = self.parse_typed_name()?;
+        let value = if self.eat(&Tokens::Assign) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        class.fields.push(ClassField { visibility, is_static, field, value });
+        Ok(())
+    }
+
+    /// Assignment or call statement, both start with a suffixed expression.
+    fn parse_expr_stat(&mut self) -> ParseResult<AstNodes> {
+        let start = self.current_start();
+        let first = self.parse_suffixed_expression()?;
+        if self.check(&Tokens::Assign) || self.check(&Tokens::Comma) {
+            let mut targets = vec![first];
+            while self.eat(&Tokens::Comma) {
+                targets.push(self.parse_suffixed_expression()?);
+            }
+            for target in &targets {
+                if !matches!(target.kind, Expressions::Ident(_) | Expressions::Field { .. } | Expressions::Index { .. }) {
+                    return Err(self.error_at(ParseErrors::InvalidAssignmentTarget, target.span.clone()));
+                }
+            }
+            self.expect(Tokens::Assign)?;
+            let values = self.parse_expression_list()?;
+            return Ok(AstNodes::Assign { targets, values });
+        }
+        if let Some(op) = self.compound_operator() {
+            return Ok(self.parse_compound_assignment(first, op));
+        }
+        match first.kind {
+            Expressions::Call { .. } | Expressions::MethodCall { .. } => Ok(AstNodes::Call(first)),
+            _ => Err(self.error_at(ParseErrors::NotAStatement, self.span_from(start)))
+        }
+    }
+
+    /// The operator of `op=` at the cursor, such as `+` of `+=`.
+    fn compound_operator(&self) -> Option<BinaryOps> {
+        let (op, assign) = (self.peek()?, self.tokens.get(self.cursor + 1)?);
+        let op_kind = BinaryOps::from_token(&op.kind).filter(BinaryOps::is_compound)?;
+        (assign.kind == Tokens::Assign && op.span.end == assign.span.start).then_some(op_kind)
+    }
+
+    /// `target op= value`, which Lua lacks. It is reported and parsed as `target = target op value`, the fix
+    /// spelling that out when the target is a name or a field path.
+    fn parse_compound_assignment(&mut self, target: Expression, op: BinaryOps) -> AstNodes {
+        let start = self.bump().span.start;
+        let end = self.bump().span.end;
+        let operator = Span { start, end };
+        let value = match self.parse_expression() {
+            Ok(value) => value,
+            Err(error) => {
+                self.report(*error);
+                Expression { kind: Expressions::Error, span: self.span_from(end) }
+            }
+        };
+        if !self.target.has_compound_assignment() {
+            self.report_compound_assignment(&target, op, operator, &value);
+        }
+        let span = value.span.clone();
+        let value = Expression { kind: Expressions::Binary { op, lhs: Box::new(target.clone()), rhs: Box::new(value) }, span: Span { start: target.span.start, end: span.end } };
+        AstNodes::Assign { targets: vec![target], values: vec![value] }
+    }
+
+    /// Reports a compound assignment on a target lacking them, with a fix repeating the assigned variable.
+    fn report_compound_assignment(&mut self, target: &Expression, op: BinaryOps, operator: Span, value: &Expression) {
+        let mut error = *self.error_at(ParseErrors::CompoundAssignment(op, self.target), operator.clone());
+        if let (Some(text), false) = (reference_text(target), matches!(value.kind, Expressions::Error)) {
+            let mut edits = vec![Edit { span: operator, replacement: format!("= {} {}", text, op) }];
+            // `a *= b + 1` multiplies by the whole of `b + 1`
+            if matches!(&value.kind, Expressions::Binary { op: inner, .. } if inner.priority().0 <= op.priority().1) {
+                edits.push(Edit { span: Span { start: value.span.start, end: value.span.start }, replacement: "(".to_string() });
+                edits.push(Edit { span: Span { start: value.span.end, end: value.span.end }, replacement: ")".to_string() });
+            }
+            error = error.fix(&format!("write `{0} = {0} {1} ...`", text, op), edits);
+        }
+        // reported after the errors of the value, in source order
+        self.report(error);
+    }
+
+    // ---- expressions ----
+
+    fn parse_expression_list(&mut self) -> ParseResult<Vec<Expression>> {
+        let mut exprs = vec![self.parse_expression()?];
+        while self.eat(&Tokens::Comma) {
+            exprs.push(self.parse_expression()?);
+        }
+        Ok(exprs)
+    }
+
+    fn parse_expression(&mut self) -> ParseResult<Expression> {
+        self.parse_sub_expression(0)
+    }
+
+    /// Precedence climbing: parses operands joined by binary operators whose left priority exceeds `limit`.
+    fn parse_sub_expression(&mut self, limit: u8) -> ParseResult<Expression> {
+        let start = self.current_start();
+        let mut lhs = match self.peek_kind().and_then(UnaryOps::from_token) {
+            Some(op) => {
+                self.bump();
+                let expr = self.parse_sub_expression(UNARY_PRIORITY)?;
+                Expression { kind: Expressions::Unary { op, expr: Box::new(expr) }, span: self.span_from(start) }
+            }
+            None => self.parse_simple_expression()?
+        };
+        let binary_op = |parser: &Parser| match parser.not_equal_operator() {
+            Some(_) => Some(BinaryOps::NEQ),
+            None => parser.peek_kind().and_then(BinaryOps::from_token),
+        };
+        while let Some(op) = binary_op(self) {
+            let (left, right) = op.priority();
+            if left <= limit {
+                break;
+            }
+            if let Some(span) = self.not_equal_operator() {
+                let error = self.error_at(ParseErrors::NotEqualOperator, span.clone())
+                    .fix("replace `!=` with `~=`", vec![Edit { span, replacement: "~=".to_string() }]);
+                self.report(error);
+                self.bump();
+            }
+            self.bump();
+            let rhs = self.parse_sub_expression(right)?;
+            lhs = Expression { kind: Expressions::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span: self.span_from(start) };
+        }
+        Ok(lhs)
+    }
+
+    /// The span of `!=` at the cursor, lexed as an unknown `!` followed by `=`.
+    fn not_equal_operator(&self) -> Option<Span> {
+        let (bang, assign) = (self.peek()?, self.tokens.get(self.cursor + 1)?);
+        match (&bang.kind, &assign.kind) {
+            (Tokens::Unknown(c), Tokens::Assign) if c == "!" && bang.span.end == assign.span.start => Some(Span { start: bang.span.start, end: assign.span.end }),
+            _ => None,
+        }
+    }
+
+    fn parse_simple_expression(&mut self) -> ParseResult<Expression> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected())
+        };
+        let kind = match token.kind {
+            Tokens::Nil => Expressions::Nil,
+            Tokens::True => Expressions::True,
+            Tokens::False => Expressions::False,
+            Tokens::Number(num) => Expressions::Number(num),
+            Tokens::Float(num) => Expressions::Float(num),
+            Tokens::String(s) => Expressions::String(s),
+            Tokens::Dots => Expressions::Dots,
+            Tokens::LCurly => return self.parse_table(),
+            Tokens::Function => {
+                self.bump();
+                let func = self.parse_function_body(token.span.start)?;
+                return Ok(Expression { span: func.span.clone(), kind: Expressions::Function(Box::new(func)) });
+            }
+            Tokens::LParen if self.is_arrow_function() => return self.parse_arrow_function(),
+            Tokens::Ident(_) if self.peek_nth_kind(1) == Some(&Tokens::Arrow) => return self.parse_arrow_function(),
+            _ => return self.parse_suffixed_expression()
+        };
+        self.bump();
+        Ok(Expression { kind, span: token.span })
+    }
+
+    /// Whether the parenthesis at the cursor opens an arrow function parameter list rather than an expression.
+    fn is_arrow_function(&self) -> bool {
+        let mut depth = 0usize;
+        let mut ix = self.cursor;
+        while let Some(token) = self.tokens.get(ix) {
+            match token.kind {
+                Tokens::LParen => depth += 1,
+                Tokens::RParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return matches!(self.tokens.get(ix + 1), Some(Token { kind: Tokens::Arrow, .. }));
+                    }
+                }
+                _ => {}
+            }
+            ix += 1;
+        }
+        false
+    }
+
+    /// `(a, b: T) => expr` or `a => expr`
+    fn parse_arrow_function(&mut self) -> ParseResult<Expression> {
+        let start = self.current_start();
+        let mut args = Vec::new();
+        let mut varargs = false;
+        if self.eat(&Tokens::LParen) {
+            while !self.check(&Tokens::RParen) {
+                if self.eat(&Tokens::Dots) {
+                    varargs = true;
+                    break;
+                }
+                args.push(self.parse_typed_name()?);
+                if !self.eat(&Tokens::Comma) {
+                    break;
+                }
+            }
+            self.expect(Tokens::RParen)?;
+        } else {
+            args.push(self.parse_typed_name()?);
+        }
+        self.expect(Tokens::Arrow)?;
+        let body_start = self.current_start();
+        let value = self.parse_expression()?;
+        let ret = AstNode { kind: AstNodes::Return(vec![value]), span: self.span_from(body_start), comments: Vec::new() };
+        let func = FunctionDecl {
+            name: String::new(),
+            path: Vec::new(),
+            is_method: false,
+            args,
+            varargs,
+            varargs_typ: None,
+            returns: None,
+            body: vec![ret],
+            is_class: false,
+            is_arrow: true,
+            span: self.span_from(start),
+        };
+        Ok(Expression { span: func.span.clone(), kind: Expressions::Function(Box::new(func)) })
+    }
+
+    /// `{ value, name = value, [key] = value }`, fields separated by `,` or `;`.
+    fn parse_table(&mut self) -> ParseResult<Expression> {
+        let start = self.expect(Tokens::LCurly)?.span.start;
+        let mut fields = Vec::new();
+        while !self.check(&Tokens::RCurly) {
+            let field_start = self.current_start();
+            let field = match self.parse_table_field() {
+                Ok(field) => field,
+                Err(error) => TableField::Positional(self.recover_item(error, field_start, &[Tokens::Comma, Tokens::SemiColon, Tokens::RCurly])?),
+            };
+            fields.push(field);
+            if !self.eat(&Tokens::Comma) && !self.eat(&Tokens::SemiColon) {
+                break;
+            }
+        }
+        self.expect(Tokens::RCurly)?;
+        Ok(Expression { kind: Expressions::Table(fields), span: self.span_from(start) })
+    }
+
+    /// `value`, `name = value` or `[key] = value`
+    fn parse_table_field(&mut self) -> ParseResult<TableField> {
+        Ok(match (self.peek_kind(), self.peek_nth_kind(1)) {
+            (Some(Tokens::LBracket), _) => {
+                self.bump();
+                let key = self.parse_expression()?;
+                self.expect(Tokens::RBracket)?;
+                self.expect(Tokens::Assign)?;
+                TableField::Keyed(key, self.parse_expression()?)
+            }
+            (Some(Tokens::Ident(_) | Tokens::Type), Some(Tokens::Assign)) => {
+                let name = self.expect_member_name()?;
+                self.bump();
+                TableField::Named(name, self.parse_expression()?)
+            }
+            _ => TableField::Positional(self.parse_expression()?)
+        })
+    }
+
+    fn parse_primary_expression(&mut self) -> ParseResult<Expression> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.unexpected())
+        };
+        match token.kind {
+            Tokens::Ident(name) => {
+                self.bump();
+                Ok(Expression { kind: Expressions::Ident(name), span: token.span })
+            }
+            // `type` is only a keyword at the start of an alias declaration
+            Tokens::Type => {
+                self.bump();
+                Ok(Expression { kind: Expressions::Ident("type".to_string()), span: token.span })
+            }
+            Tokens::LParen => {
+                self.bump();
+                let inner = self.parse_expression()?;
+                self.expect(Tokens::RParen)?;
+                Ok(Expression { kind: Expressions::Paren(Box::new(inner)), span: self.span_from(token.span.start) })
+            }
+            other => Err(self.error_at(ParseErrors::ExpectedExpression(other), token.span))
+        }
+    }
+
+    /// Primary expression followed by any number of field accesses, indexes and calls.
+    fn parse_suffixed_expression(&mut self) -> ParseResult<Expression> {
+        let start = self.current_start();
+        let mut expr = self.parse_primary_expression()?;
+        loop {
+            let kind = match self.peek_kind() {
+                Some(Tokens::Period) => {
+                    self.bump();
+                    let name = self.expect_member_name()?;
+                    Expressions::Field { target: Box::new(expr), name }
+                }
+                Some(Tokens::LBracket) => {
+                    self.bump();
+                    let key = self.parse_expression()?;
+                    self.expect(Tokens::RBracket)?;
+                    Expressions::Index { target: Box::new(expr), key: Box::new(key) }
+                }
+                Some(Tokens::Colon) => {
+                    self.bump();
+                    let method = self.expect_member_name()?;
+                    let args = self.parse_call_args()?;
+                    Expressions::MethodCall { target: Box::new(expr), method, args }
+                }
+                Some(Tokens::LParen | Tokens::String(_) | Tokens::LCurly) => {
+                    let args = self.parse_call_args()?;
+                    Expressions::Call { target: Box::new(expr), args }
+                }
+                _ => return Ok(expr)
+            };
+            expr = Expression { kind, span: self.span_from(start) };
+        }
+    }
+
+    /// `(args)`, or a single string or table argument for `f"x"` and `f{...}`.
+    fn parse_call_args(&mut self) -> ParseResult<Vec<Expression>> {
+        match self.peek() {
+            Some(Token { kind: Tokens::String(s), span }) => {
+                let arg = Expression { kind: Expressions::String(s.clone()), span: span.clone() };
+                self.bump();
+                return Ok(vec![arg]);
+            }
+            Some(Token { kind: Tokens::LCurly, .. }) => return Ok(vec![self.parse_table()?]),
+            _ => {}
+        }
+        self.expect(Tokens::LParen)?;
+        let mut args = Vec::new();
+        if self.eat(&Tokens::RParen) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.parse_expression_before(&[Tokens::Comma, Tokens::RParen])?);
+            if !self.eat(&Tokens::Comma) {
+                break;
+            }
+        }
+        self.expect(Tokens::RParen)?;
+        Ok(args)
+    }
+}
+
+/// Parses the tokens of a file, the diagnostics of the errors found otherwise.
+pub fn parse(tokens: Vec<Token>) -> Result<Block, Vec<Diagnostic>> {
+    match parse_recovering(tokens) {
+        (block, errors) if errors.is_empty() => Ok(block),
+        (_, errors) => Err(errors),
+    }
+}
+
+/// Parses the tokens of a file as far as possible, with the diagnostics of every error found.
+/// The statements and expressions that failed to parse are `Error` nodes of the block.
+pub fn parse_recovering(tokens: Vec<Token>) -> (Block, Vec<Diagnostic>) {
+    parse_for(tokens, Target::default())
+}
+
+/// [`parse_recovering`] for a dialect, the syntax it has, such as Luau's compound assignments, being accepted.
+pub fn parse_for(tokens: Vec<Token>, target: Target) -> (Block, Vec<Diagnostic>) {
+    let mut parser = Parser::new(tokens, target);
+    let block = parser.parse();
+    (block, parser.errors.into_iter().map(Diagnostic::from).collect())
+}
+
+
+/// The source text of a name or a field path, `a.b.c`.
+fn reference_text(expr: &Expression) -> Option<String> {
+    match &expr.kind {
+        Expressions::Ident(name) => Some(name.clone()),
+        Expressions::Field { target, name } => Some(format!("{}.{}", reference_text(target)?, name)),
+        _ => None,
+    }
+}
+
+#[test]
+fn parse_statements() {
+    use crate::parser::Lexer;
+    let inp = r#"
+        local a, b <const> = x, y
+        local function f(n: number, ...)
+            return n
+        end
+        function obj.field:method(self2) obj.count = obj.count end
+        if a then print(a) elseif b then print(b) else do end end
+        for i = 1, 10, 2 do break end
+        for k, v in pairs(t) do goto continue ::continue:: end
+        while a do a = nil end
+        repeat f(a) until b
+        print(type(a))
+        return a, b;
+    "#;
+    let block = parse(Lexer::new(inp).collect()).unwrap();
+    let kinds: Vec<_> = block.iter().map(|node| format!("{:?}", node.kind).split([' ', '(', '{']).next().unwrap().to_string()).collect();
+    assert_eq!(kinds, ["LocalAssign", "LocalFunction", "Function", "If", "NumericFor", "GenericFor", "While", "Repeat", "Call", "Return"]);
+    match &block[2].kind {
+        AstNodes::Function(func) => {
+            assert_eq!(func.path, ["obj", "field"]);
+            assert_eq!(func.name, "method");
+            assert!(func.is_method);
+        }
+        other => panic!("expected a function declaration, got {:?}", other)
+    }
+    match &block[0].kind {
+        AstNodes::LocalAssign { names, values } => {
+            assert_eq!(names[1].attrib, Some(Attrib::Const));
+            assert_eq!(values.len(), 2);
+        }
+        other => panic!("expected a local assignment, got {:?}", other)
     }
 }
 
-pub fn parse<'a>(src: String, tokens: Vec<Token>) {
-    let err_client = ErrorClient::new(&src);
-    let mut iter: TokenIter = tokens.iter().enumerate().peekable();
-    let mut parser = Parser {
-        err_client,
-        iter: &mut iter,
-        source: &src
-    };
-    parser.parse();
-}
\ No newline at end of file
+#[test]
+fn parse_expression_precedence() {
+    use crate::parser::Lexer;
+    fn show(expr: &Expression) -> String {
+        match &expr.kind {
+            Expressions::Binary { op, lhs, rhs } => format!("({:?} {} {})", op, show(lhs), show(rhs)),
+            Expressions::Unary { op, expr } => format!("({:?} {})", op, show(expr)),
+            Expressions::Ident(name) => name.clone(),
+            Expressions::Number(num) | Expressions::Float(num) => num.to_string(),
+            Expressions::String(s) => format!("{:?}", s),
+            Expressions::Call { target, args } => format!("{}[{}]", show(target), args.iter().map(show).collect::<Vec<_>>().join(" ")),
+            Expressions::MethodCall { target, method, args } => format!("{}:{}[{}]", show(target), method, args.iter().map(show).collect::<Vec<_>>().join(" ")),
+            Expressions::Table(fields) => format!("{{{}}}", fields.len()),
+            Expressions::Function(func) => format!("fn/{}", func.args.len()),
+            other => format!("{:?}", other)
+        }
+    }
+    let inp = r#"
+        local a = 1 + 2 * 3 - x ^ 2 ^ -y
+        local b = "a" .. "b" .. c == -#t and not f"x" or obj:m{1, k = 2, [3] = 4}
+        local c = a < b | c ~ d & e << 1 // 2
+        local d = (x, y: number) => x + y
+        local e = z => z
+    "#;
+    let block = parse(Lexer::new(inp).collect()).unwrap();
+    let shown: Vec<_> = block.iter().map(|node| match &node.kind {
+        AstNodes::LocalAssign { values, .. } => show(&values[0]),
+        other => panic!("expected a local assignment, got {:?}", other)
+    }).collect();
+    assert_eq!(shown, [
+        "(Minus (Plus 1 (Mul 2 3)) (Pow x (Pow 2 (Neg y))))",
+        r#"(Or (And (EQ (Concat "a" (Concat "b" c)) (Neg (Len t))) (Not f["x"])) obj:m[{3}])"#,
+        "(LT a (BitOr b (BitXor c (BitAnd d (SHL e (IntDiv 1 2))))))",
+        "fn/2",
+        "fn/1",
+    ]);
+}
+
+#[test]
+fn parse_type_annotations() {
+    use crate::parser::Lexer;
+    let inp = r#"
+        local a: number | string & Tagged
+        local b: (a: number, ...string) -> (boolean, string?)
+        local c: { x: number, y?: Map<string, Array<number>>, [string]: any }
+        local d: "yes" | -1 | true | nil
+        local e: number[] | { string } | (number)
+        function f(x: Point?, ...: string): (number, string)
+        end
+    "#;
+    let block = parse(Lexer::new(inp).collect()).unwrap();
+    let shown: Vec<_> = block.iter().map(|node| match &node.kind {
+        AstNodes::LocalAssign { names, .. } => names[0].typ.as_ref().unwrap().to_string(),
+        AstNodes::Function(func) => format!("{} ...{} : {}", func.args[0].typ.as_ref().unwrap(), func.varargs_typ.as_ref().unwrap(), func.returns.as_ref().unwrap()),
+        other => panic!("unexpected statement {:?}", other)
+    }).collect();
+    assert_eq!(shown, [
+        "number | string & Tagged",
+        "(a: number, ...string) -> (boolean, string?)",
+        "{ x: number, y?: Map<string, Array<number>>, [string]: any }",
+        r#""yes" | -1 | true | nil"#,
+        "number[] | string[] | number",
+        "Point? ...string : (number, string)",
+    ]);
+}
+
+
+#[test]
+fn recover_from_errors() {
+    use crate::parser::Lexer;
+    let inp = r#"
+        local a = = 1
+        print(a
+        if a == then
+            print(f(1, , 2))
+        end
+        local t = { 1, = , 3 }
+        function g(x, 1, y) return x end
+        while true
+            break
+        end
+        local ok = 2
+        end
+    "#;
+    let (block, errors) = parse_recovering(Lexer::new(inp).collect());
+    let errors: Vec<(&str, &str)> = errors.iter().map(|error| (error.code, &inp[error.primary.span.start..error.primary.span.end])).collect();
+    assert_eq!(errors, [("TL1004", "="), ("TL1001", "if"), ("TL1004", "then"), ("TL1004", ","), ("TL1004", "="), ("TL1001", "1"), ("TL1001", "break"), ("TL1003", "end")]);
+    let statements: Vec<&str> = block.iter().map(|node| match &node.kind {
+        AstNodes::Error => "error",
+        AstNodes::If { condition: Expression { kind: Expressions::Error, .. }, body, .. } if body.len() == 1 => "if",
+        AstNodes::LocalAssign { values, .. } if matches!(&values[0].kind, Expressions::Table(fields) if fields.len() == 3) => "local table",
+        AstNodes::Function(func) if func.args.len() == 2 => "function",
+        AstNodes::While { .. } => "while",
+        AstNodes::LocalAssign { .. } => "local",
+        _ => "other",
+    }).collect();
+    assert_eq!(statements, ["error", "error", "if", "local table", "function", "while", "local"]);
+}
+
+#[test]
+fn parse_contextual_keywords() {
+    use crate::parser::Lexer;
+    let inp = r#"
+        local static, protected, case = 1, 2, 3
+        import = require("import")
+        export.value = import { "a" }
+        import { a, b as c } from "./mod"
+        export default static
+        class Point
+            protected static count = 0
+            static: boolean
+            protected(): number return case end
+        end
+        switch case
+            case 1 then
+                local f = function() case = 2 end
+            case 2, 3 then
+        end
+    "#;
+    let block = parse(Lexer::new(inp).collect()).unwrap();
+    let kinds: Vec<_> = block.iter().map(|node| format!("{:?}", node.kind).split([' ', '(', '{']).next().unwrap().to_string()).collect();
+    assert_eq!(kinds, ["LocalAssign", "Assign", "Assign", "Import", "ExportDefault", "Class", "Switch"]);
+    let AstNodes::Class(class) = &block[5].kind else { unreachable!() };
+    let fields: Vec<_> = class.fields.iter().map(|field| (field.field.name.as_str(), field.visibility, field.is_static)).collect();
+    assert_eq!(fields, [("count", Visibility::Protected, true), ("static", Visibility::Public, false)]);
+    assert_eq!(class.methods[0].func.name, "protected");
+    let AstNodes::Switch { cases, .. } = &block[6].kind else { unreachable!() };
+    assert_eq!(cases.len(), 2);
+}
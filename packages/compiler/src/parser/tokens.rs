@@ -1,4 +1,4 @@
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -9,6 +9,7 @@ pub struct Token {
     pub kind: Tokens,
     pub span: Span,
 }
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Debug, Clone)]
 pub enum Tokens {
     And,
@@ -23,10 +24,10 @@ pub enum Tokens {
     Interface,
     Public,
     Private,
-    Protected,
     Extends,
     Implements,
     Arrow,
+    ThinArrow,
     Switch,
     Qmark,
     For,
@@ -81,6 +82,7 @@ pub enum Tokens {
     Colon,
     String(String),
     Number(f64),
+    Float(f64),
     Ident(String),
     Comment(Comment),
     Unknown(String),
@@ -90,4 +92,21 @@ pub enum Tokens {
 pub enum Comment {
     SingleLine(String),
     MultiLine(String),
-}
\ No newline at end of file
+}
+/// Start of the private use characters standing for the bytes above `0x7f` of `\xNN` and `\ddd` escapes, which
+/// are no characters on their own and would otherwise be encoded as two UTF-8 bytes.
+const RAW_BYTES: u32 = 0x10ff00;
+
+/// The character of a string literal holding an escaped byte.
+pub fn byte_char(byte: u8) -> char {
+    if byte.is_ascii() {
+        char::from(byte)
+    } else {
+        char::from_u32(RAW_BYTES + u32::from(byte)).expect("a private use character")
+    }
+}
+
+/// The byte a character of a string literal stands for, if it was escaped as one.
+pub fn raw_byte(c: char) -> Option<u8> {
+    u32::from(c).checked_sub(RAW_BYTES).and_then(|byte| u8::try_from(byte).ok()).filter(|byte| !byte.is_ascii())
+}
@@ -0,0 +1,228 @@
+use crate::parser::ast::{AstNode, AstNodes, Block, Expression, Expressions, TableField, TableType, Type, Types};
+
+/// Whether control never reaches the end of a block.
+pub fn terminates(block: &[AstNode]) -> bool {
+    match block.last().map(|node| &node.kind) {
+        Some(AstNodes::Return(_) | AstNodes::Break | AstNodes::Goto(_)) => true,
+        Some(AstNodes::Call(expr)) => matches!(&expr.kind, Expressions::Call { target, .. } if matches!(&target.kind, Expressions::Ident(name) if name == "error")),
+        Some(AstNodes::Do(body)) => terminates(body),
+        Some(AstNodes::If { body, else_ifs, else_body: Some(else_body), .. }) => {
+            terminates(body) && else_ifs.iter().all(|(_, body)| terminates(body)) && terminates(else_body)
+        }
+        _ => false,
+    }
+}
+
+/// Calls `f` on every expression of a block, nested function bodies included, children before parents.
+pub fn walk_block_mut(block: &mut Block, f: &mut dyn FnMut(&mut Expression)) {
+    for node in block {
+        walk_node_mut(node, f);
+    }
+}
+
+pub fn walk_node_mut(node: &mut AstNode, f: &mut dyn FnMut(&mut Expression)) {
+    match &mut node.kind {
+        AstNodes::LocalAssign { values, .. } => walk_all(values, f),
+        AstNodes::Assign { targets, values } => {
+            walk_all(targets, f);
+            walk_all(values, f);
+        }
+        AstNodes::Call(expr) => walk_expression_mut(expr, f),
+        AstNodes::Do(body) => walk_block_mut(body, f),
+        AstNodes::While { condition, body } | AstNodes::Repeat { body, condition } => {
+            walk_expression_mut(condition, f);
+            walk_block_mut(body, f);
+        }
+        AstNodes::If { condition, body, else_ifs, else_body } => {
+            walk_expression_mut(condition, f);
+            walk_block_mut(body, f);
+            for (cond, body) in else_ifs {
+                walk_expression_mut(cond, f);
+                walk_block_mut(body, f);
+            }
+            if let Some(body) = else_body {
+                walk_block_mut(body, f);
+            }
+        }
+        AstNodes::Switch { subject, cases, default } => {
+            walk_expression_mut(subject, f);
+            for (values, body) in cases {
+                walk_all(values, f);
+                walk_block_mut(body, f);
+            }
+            if let Some(body) = default {
+                walk_block_mut(body, f);
+            }
+        }
+        AstNodes::NumericFor { start, limit, step, body, .. } => {
+            walk_expression_mut(start, f);
+            walk_expression_mut(limit, f);
+            if let Some(step) = step {
+                walk_expression_mut(step, f);
+            }
+            walk_block_mut(body, f);
+        }
+        AstNodes::GenericFor { exprs, body, .. } => {
+            walk_all(exprs, f);
+            walk_block_mut(body, f);
+        }
+        AstNodes::Function(func) | AstNodes::LocalFunction(func) => walk_block_mut(&mut func.body, f),
+        AstNodes::Return(values) => walk_all(values, f),
+        AstNodes::Class(class) => {
+            for field in &mut class.fields {
+                if let Some(value) = &mut field.value {
+                    walk_expression_mut(value, f);
+                }
+            }
+            let methods = class.methods.iter_mut().map(|method| &mut method.func);
+            for func in class.constructor.iter_mut().chain(methods) {
+                walk_block_mut(&mut func.body, f);
+            }
+        }
+        AstNodes::Export(inner) => walk_node_mut(inner, f),
+        AstNodes::ExportDefault(value) => walk_expression_mut(value, f),
+        AstNodes::Break | AstNodes::Goto(_) | AstNodes::Label(_) | AstNodes::TypeAlias { .. } | AstNodes::Interface(_) | AstNodes::Error
+            | AstNodes::Import(_) => {}
+    }
+}
+
+/// Calls `f` on every statement of a block, those of nested blocks and function bodies included.
+pub fn walk_statements_mut(block: &mut Block, f: &mut dyn FnMut(&mut AstNode)) {
+    for node in block.iter_mut() {
+        walk_statement_mut(node, f);
+    }
+    // bodies of function expressions, the statements above cover everything else
+    walk_block_mut(block, &mut |expr| {
+        if let Expressions::Function(func) = &mut expr.kind {
+            for node in &mut func.body {
+                walk_statement_mut(node, f);
+            }
+        }
+    });
+}
+
+/// Calls `f` on a statement and the statements nested in it, without entering function expressions.
+fn walk_statement_mut(node: &mut AstNode, f: &mut dyn FnMut(&mut AstNode)) {
+    f(node);
+    let mut nested = |block: &mut Block| {
+        for node in block {
+            walk_statement_mut(node, f);
+        }
+    };
+    match &mut node.kind {
+        AstNodes::Do(body) | AstNodes::While { body, .. } | AstNodes::Repeat { body, .. }
+            | AstNodes::NumericFor { body, .. } | AstNodes::GenericFor { body, .. } => nested(body),
+        AstNodes::If { body, else_ifs, else_body, .. } => {
+            nested(body);
+            for (_, body) in else_ifs {
+                nested(body);
+            }
+            if let Some(body) = else_body {
+                nested(body);
+            }
+        }
+        AstNodes::Switch { cases, default, .. } => {
+            for (_, body) in cases {
+                nested(body);
+            }
+            if let Some(body) = default {
+                nested(body);
+            }
+        }
+        AstNodes::Function(func) | AstNodes::LocalFunction(func) => nested(&mut func.body),
+        AstNodes::Class(class) => {
+            let methods = class.methods.iter_mut().map(|method| &mut method.func);
+            for func in class.constructor.iter_mut().chain(methods) {
+                nested(&mut func.body);
+            }
+        }
+        AstNodes::Export(inner) => walk_statement_mut(inner, f),
+        _ => {}
+    }
+}
+
+fn walk_all(exprs: &mut [Expression], f: &mut dyn FnMut(&mut Expression)) {
+    for expr in exprs {
+        walk_expression_mut(expr, f);
+    }
+}
+
+pub fn walk_expression_mut(expr: &mut Expression, f: &mut dyn FnMut(&mut Expression)) {
+    match &mut expr.kind {
+        Expressions::Function(func) => walk_block_mut(&mut func.body, f),
+        Expressions::Paren(inner) => walk_expression_mut(inner, f),
+        Expressions::Field { target, .. } => walk_expression_mut(target, f),
+        Expressions::Index { target, key } => {
+            walk_expression_mut(target, f);
+            walk_expression_mut(key, f);
+        }
+        Expressions::Call { target, args } | Expressions::MethodCall { target, args, .. } => {
+            walk_expression_mut(target, f);
+            walk_all(args, f);
+        }
+        Expressions::Binary { lhs, rhs, .. } => {
+            walk_expression_mut(lhs, f);
+            walk_expression_mut(rhs, f);
+        }
+        Expressions::Unary { expr, .. } => walk_expression_mut(expr, f),
+        Expressions::Table(fields) => {
+            for field in fields {
+                match field {
+                    TableField::Positional(value) | TableField::Named(_, value) => walk_expression_mut(value, f),
+                    TableField::Keyed(key, value) => {
+                        walk_expression_mut(key, f);
+                        walk_expression_mut(value, f);
+                    }
+                }
+            }
+        }
+        Expressions::Nil | Expressions::True | Expressions::False | Expressions::Number(_) | Expressions::Float(_)
+        | Expressions::String(_) | Expressions::Dots | Expressions::Ident(_) | Expressions::Error => {}
+    }
+    f(expr);
+}
+
+/// Calls `f` on the name of every named type of an annotation, `Map` and `geo.Point` in `Map<string, geo.Point>`.
+pub fn walk_type_names(typ: &Type, f: &mut dyn FnMut(&str)) {
+    match &typ.kind {
+        Types::Named { name, args } => {
+            f(name);
+            walk_types(args, f);
+        }
+        Types::Union(types) | Types::Intersection(types) | Types::Tuple(types) => walk_types(types, f),
+        Types::Optional(inner) | Types::Array(inner) | Types::Variadic(inner) => walk_type_names(inner, f),
+        Types::Function(func) => {
+            for param in &func.params {
+                walk_type_names(&param.typ, f);
+            }
+            if let Some(varargs) = &func.varargs {
+                walk_type_names(varargs, f);
+            }
+            walk_type_names(&func.returns, f);
+        }
+        Types::Table(table) => walk_table_type_names(table, f),
+        Types::Conditional { check, extends, then, otherwise } => {
+            for typ in [check, extends, then, otherwise] {
+                walk_type_names(typ, f);
+            }
+        }
+        Types::Nil | Types::Literal(_) => {}
+    }
+}
+
+/// [`walk_type_names`] for the fields and index signatures of a table type or an interface body.
+pub fn walk_table_type_names(table: &TableType, f: &mut dyn FnMut(&str)) {
+    for field in &table.fields {
+        walk_type_names(&field.typ, f);
+    }
+    for (key, value) in &table.indexers {
+        walk_type_names(key, f);
+        walk_type_names(value, f);
+    }
+}
+
+fn walk_types(types: &[Type], f: &mut dyn FnMut(&str)) {
+    for typ in types {
+        walk_type_names(typ, f);
+    }
+}
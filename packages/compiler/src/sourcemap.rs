@@ -0,0 +1,241 @@
+use line_col::LineColLookup;
+use crate::json;
+use crate::traceback::Traceback;
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Where the compiler puts the source map of a generated file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceMaps {
+    #[default]
+    None,
+    /// `file.lua.map` next to the generated file, linked from a trailing comment.
+    File,
+    /// Embedded in a trailing comment of the generated file.
+    Inline,
+}
+
+/// A position of the generated code, 0-based, and the byte offset in the source of the statement printed there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+/// A generated position mapped to a position of one of the sources, all 0-based.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub generated_line: usize,
+    pub generated_column: usize,
+    /// Index into [`SourceMap::sources`].
+    pub source: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A Source Map v3 from a generated Lua file back to the tlua files it was compiled from.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// The generated file, as named in the `file` field.
+    pub file: String,
+    /// The `sourceRoot` field, the directory the sources are relative to, from the map itself. Empty when absent.
+    pub source_root: String,
+    pub sources: Vec<String>,
+    /// Sorted by generated position.
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn new(file: &str) -> SourceMap {
+        SourceMap { file: file.to_string(), ..SourceMap::default() }
+    }
+
+    /// Adds the segments printed from `source`, found at `path`, whose code starts `line_offset` lines into
+    /// the generated file and is indented by `column_offset`, as when bundled.
+    pub fn add_source(&mut self, path: &str, source: &str, segments: &[Segment], line_offset: usize, column_offset: usize) {
+        let index = self.sources.len();
+        self.sources.push(path.to_string());
+        let lookup = LineColLookup::new(source);
+        self.mappings.extend(segments.iter().map(|segment| {
+            let (line, column) = lookup.get(segment.offset.min(source.len()));
+            Mapping {
+                generated_line: segment.line + line_offset,
+                generated_column: segment.column + column_offset,
+                source: index,
+                line: line - 1,
+                column: column - 1,
+            }
+        }));
+        self.mappings.sort_by_key(|mapping| (mapping.generated_line, mapping.generated_column));
+    }
+
+    /// The source file, 1-based line and 1-based column a 1-based line of the generated file was printed from.
+    /// A `column` picks among the statements of a line the last one starting at or before it, 0 picks the first.
+    /// A line without statements of its own, such as the `end` of a function, maps as the line before it.
+    pub fn original_position(&self, line: usize, column: usize) -> Option<(&str, usize, usize)> {
+        let line = line.checked_sub(1)?;
+        let on_line = || self.mappings.iter().filter(|mapping| mapping.generated_line == line);
+        let found = on_line().take_while(|mapping| mapping.generated_column < column).last()
+            .or_else(|| on_line().next())
+            .or_else(|| self.mappings.iter().rev().find(|mapping| mapping.generated_line < line));
+        found.map(|mapping| (self.sources[mapping.source].as_str(), mapping.line + 1, mapping.column + 1))
+    }
+
+    /// Rewrites the `file.lua:12:` positions of a Lua error or traceback that point into the generated file
+    /// to `file.tlua:3:5:`. Positions in other files are left as they are, see [`Traceback`] to also name
+    /// the functions of the frames.
+    pub fn translate_traceback(&self, traceback: &str) -> String {
+        Traceback::new().map(self.clone()).rewrite(traceback)
+    }
+
+    /// The map as Source Map v3 JSON.
+    pub fn to_json(&self) -> String {
+        let sources: Vec<String> = self.sources.iter().map(|source| json::string(source)).collect();
+        let source_root = if self.source_root.is_empty() { String::new() } else { format!(", \"sourceRoot\": {}", json::string(&self.source_root)) };
+        format!("{{\"version\": 3, \"file\": {}{}, \"sources\": [{}], \"names\": [], \"mappings\": {}}}",
+            json::string(&self.file), source_root, sources.join(", "), json::string(&self.encode_mappings()))
+    }
+
+    /// A trailing comment linking the generated code to its map, written next to it as `file.lua.map`.
+    pub fn comment(&self) -> String {
+        format!("--# sourceMappingURL={}.map", self.file.rsplit(['/', '\\']).next().unwrap_or(&self.file))
+    }
+
+    /// A trailing comment embedding the whole map in the generated code.
+    pub fn inline_comment(&self) -> String {
+        format!("--# sourceMappingURL=data:application/json;base64,{}", base64(self.to_json().as_bytes()))
+    }
+
+    /// Reads a map written by [`SourceMap::to_json`], or any Source Map v3 with a single section.
+    pub fn parse(json: &str) -> Option<SourceMap> {
+        let mut map = SourceMap { file: json::field(json, "file").and_then(|value| json::strings(value).into_iter().next()).unwrap_or_default(), ..SourceMap::default() };
+        map.source_root = json::field(json, "sourceRoot").and_then(|value| json::strings(value).into_iter().next()).unwrap_or_default();
+        map.sources = json::strings(json::field(json, "sources")?);
+        let mappings = json::strings(json::field(json, "mappings")?).into_iter().next()?;
+        map.decode_mappings(&mappings)?;
+        Some(map)
+    }
+
+    fn encode_mappings(&self) -> String {
+        let mut out = String::new();
+        let mut line = 0;
+        // every field but the generated column is relative to the previous segment of the file
+        let (mut source, mut source_line, mut source_column) = (0, 0, 0);
+        let mut column = 0;
+        for (ix, mapping) in self.mappings.iter().enumerate() {
+            if mapping.generated_line > line || ix == 0 {
+                for _ in line..mapping.generated_line {
+                    out.push(';');
+                }
+                line = mapping.generated_line;
+                column = 0;
+            } else {
+                out.push(',');
+            }
+            for value in [
+                mapping.generated_column as i64 - column as i64,
+                mapping.source as i64 - source as i64,
+                mapping.line as i64 - source_line as i64,
+                mapping.column as i64 - source_column as i64,
+            ] {
+                vlq_encode(&mut out, value);
+            }
+            column = mapping.generated_column;
+            (source, source_line, source_column) = (mapping.source, mapping.line, mapping.column);
+        }
+        out
+    }
+
+    fn decode_mappings(&mut self, mappings: &str) -> Option<()> {
+        let (mut source, mut source_line, mut source_column) = (0i64, 0i64, 0i64);
+        for (line, segments) in mappings.split(';').enumerate() {
+            let mut column = 0i64;
+            for segment in segments.split(',').filter(|segment| !segment.is_empty()) {
+                let values = vlq_decode(segment)?;
+                column += values[0];
+                // a segment of one field maps to nothing
+                if let [_, source_delta, line_delta, column_delta, ..] = values[..] {
+                    source += source_delta;
+                    source_line += line_delta;
+                    source_column += column_delta;
+                    self.mappings.push(Mapping {
+                        generated_line: line,
+                        generated_column: column as usize,
+                        source: source as usize,
+                        line: source_line as usize,
+                        column: source_column as usize,
+                    });
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+/// Appends a signed base64 VLQ, the sign in the lowest bit and five bits per digit.
+fn vlq_encode(out: &mut String, value: i64) {
+    let mut rest = if value < 0 { ((-value) << 1) | 1 } else { value << 1 } as u64;
+    loop {
+        let mut digit = (rest & 0b11111) as usize;
+        rest >>= 5;
+        if rest > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit] as char);
+        if rest == 0 {
+            break;
+        }
+    }
+}
+
+fn vlq_decode(segment: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0u64, 0);
+    for c in segment.bytes() {
+        let digit = BASE64.iter().position(|&b| b == c)? as u64;
+        value |= (digit & 0b11111) << shift;
+        if digit & 0b100000 == 0 {
+            let magnitude = (value >> 1) as i64;
+            values.push(if value & 1 == 1 { -magnitude } else { magnitude });
+            (value, shift) = (0, 0);
+        } else {
+            shift += 5;
+        }
+    }
+    Some(values)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (ix, &b)| n | (b as u32) << (16 - 8 * ix));
+        for ix in 0..4 {
+            if ix <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * ix) & 0b111111) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+
+#[test]
+fn source_map_round_trip() {
+    use crate::codegen::emit_mapped;
+    use crate::parser::{parse, Lexer};
+    use crate::target::Target;
+    let src = "local x: number = 1\n\nlocal function f(a: number)\n    return a + x\nend\nlocal g = function()\n    error(\"boom\")\nend\n";
+    let (code, segments) = emit_mapped(parse(Lexer::new(src).collect()).unwrap(), Target::Lua54, false);
+    assert_eq!(code, "local x = 1\nlocal function f(a)\n    return a + x\nend\nlocal g = function()\n    error(\"boom\")\nend\n");
+    let mut map = SourceMap::new("out/main.lua");
+    map.add_source("main.tlua", src, &segments, 0, 0);
+    assert_eq!(map.original_position(3, 0), Some(("main.tlua", 4, 5)));
+    assert_eq!(map.original_position(6, 0), Some(("main.tlua", 7, 5)));
+    let parsed = SourceMap::parse(&map.to_json()).unwrap();
+    assert_eq!((parsed.file.as_str(), &parsed.sources, &parsed.mappings), ("out/main.lua", &map.sources, &map.mappings));
+    let traceback = "lua: out/main.lua:6: boom\nstack traceback:\n\t[C]: in function 'error'\n\tout/main.lua:6: in function 'g'\n\t[C]: in ?";
+    assert_eq!(map.translate_traceback(traceback), "lua: main.tlua:7:5: boom\nstack traceback:\n\t[C]: in function 'error'\n\tmain.tlua:7:5: in function 'g'\n\t[C]: in ?");
+}
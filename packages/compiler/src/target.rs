@@ -0,0 +1,117 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::parser::ast::{AstNodes, Block, BinaryOps, UnaryOps};
+
+/// The Lua dialect the generated code runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+    Lua51,
+    Lua52,
+    Lua53,
+    #[default]
+    Lua54,
+    LuaJIT,
+    /// Roblox's typed Lua 5.1 derivative, printed with its native type annotations.
+    Luau,
+}
+
+impl Target {
+    /// Native bitwise operators, added in 5.3.
+    pub fn has_bitwise_operators(&self) -> bool {
+        matches!(self, Target::Lua53 | Target::Lua54)
+    }
+
+    /// Native floor division `//`, added in 5.3 and to Luau.
+    pub fn has_floor_division(&self) -> bool {
+        matches!(self, Target::Lua53 | Target::Lua54 | Target::Luau)
+    }
+
+    /// The global library providing bitwise functions on dialects without the operators.
+    pub fn bit_library(&self) -> Option<&'static str> {
+        match self {
+            Target::LuaJIT => Some("bit"),
+            Target::Lua52 | Target::Luau => Some("bit32"),
+            _ => None,
+        }
+    }
+
+    /// Whether bitwise operations can be expressed, natively or through the bit library.
+    pub fn has_bitwise(&self) -> bool {
+        self.has_bitwise_operators() || self.bit_library().is_some()
+    }
+
+    /// `goto` and labels, available from 5.2 and in LuaJIT. Luau only has `continue`,
+    /// written as a `goto` to a label ending the loop body.
+    pub fn has_goto(&self) -> bool {
+        !matches!(self, Target::Lua51 | Target::Luau)
+    }
+
+    /// Compound assignments such as `a += 1`, only in Luau.
+    pub fn has_compound_assignment(&self) -> bool {
+        matches!(self, Target::Luau)
+    }
+
+    /// `<const>` and `<close>` local attributes, added in 5.4.
+    pub fn has_attributes(&self) -> bool {
+        matches!(self, Target::Lua54)
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Lua51 => write!(f, "Lua 5.1"),
+            Target::Lua52 => write!(f, "Lua 5.2"),
+            Target::Lua53 => write!(f, "Lua 5.3"),
+            Target::Lua54 => write!(f, "Lua 5.4"),
+            Target::LuaJIT => write!(f, "LuaJIT"),
+            Target::Luau => write!(f, "Luau"),
+        }
+    }
+}
+
+/// Parses the name of a target as given on the command line: `lua51`, `lua52`, `lua53`, `lua54`, `luajit`
+/// or `luau`, in any case.
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Target, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "lua51" => Ok(Target::Lua51),
+            "lua52" => Ok(Target::Lua52),
+            "lua53" => Ok(Target::Lua53),
+            "lua54" => Ok(Target::Lua54),
+            "luajit" => Ok(Target::LuaJIT),
+            "luau" => Ok(Target::Luau),
+            _ => Err(format!("unknown target '{}', expected lua51, lua52, lua53, lua54, luajit or luau", name)),
+        }
+    }
+}
+
+/// Name of the bit library function implementing a bitwise binary operator.
+pub fn bit_function(op: BinaryOps) -> Option<&'static str> {
+    match op {
+        BinaryOps::BitAnd => Some("band"),
+        BinaryOps::BitOr => Some("bor"),
+        BinaryOps::BitXor => Some("bxor"),
+        BinaryOps::SHL => Some("lshift"),
+        BinaryOps::SHR => Some("rshift"),
+        _ => None,
+    }
+}
+
+/// Name of the bit library function implementing a bitwise unary operator.
+pub fn bit_unary_function(op: UnaryOps) -> Option<&'static str> {
+    match op {
+        UnaryOps::BitNot => Some("bnot"),
+        _ => None,
+    }
+}
+
+/// The label ending a loop body, which a `goto` jumps to in order to continue the loop.
+pub fn continue_label(body: &Block) -> Option<&str> {
+    match body.last().map(|node| &node.kind) {
+        Some(AstNodes::Label(label)) => Some(label),
+        _ => None,
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::path::Path;
+use line_col::LineColLookup;
+use crate::parser::ast::{AstNode, AstNodes, Block, Expression, Expressions, FunctionDecl, TableField};
+use crate::parser::{parse, Lexer};
+use crate::sourcemap::SourceMap;
+
+/// Rewrites Lua errors and `debug.traceback` output of generated files to the tlua sources they were
+/// compiled from, using their source maps:
+///
+/// ```text
+/// main.lua:24: boom                  =>    main.tlua:4:5: boom
+/// stack traceback:                         stack traceback:
+///     main.lua:28: in local 'a'                main.tlua:7:9: in function 'run'
+/// ```
+///
+/// Frames are named after the function declared around their original position, once its source is added.
+#[derive(Default)]
+pub struct Traceback {
+    /// The maps, with the normalized path of the generated file each maps.
+    maps: Vec<(String, SourceMap)>,
+    /// The functions of each added source, by path.
+    functions: HashMap<String, Vec<Function>>,
+}
+
+/// A function of a source file with its 1-based first and last positions, `None` when anonymous.
+struct Function {
+    name: Option<String>,
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+impl Traceback {
+    pub fn new() -> Traceback {
+        Traceback::default()
+    }
+
+    /// The map of a generated file whose positions are rewritten, the file being at the path the map names
+    /// and its sources at the paths the map names.
+    pub fn map(mut self, map: SourceMap) -> Traceback {
+        self.maps.push((normalize(&map.file), map));
+        self
+    }
+
+    /// The source of a file named by the maps, parsed to name the functions of its frames.
+    /// A source that does not parse names none.
+    pub fn source(mut self, path: &str, source: &str) -> Traceback {
+        let block = parse(Lexer::new(source).collect()).unwrap_or_default();
+        let mut collector = Collector { lookup: LineColLookup::new(source), functions: Vec::new() };
+        collector.block(&block);
+        self.functions.insert(normalize(path), collector.functions);
+        self
+    }
+
+    /// Reads a `.lua.map` file and the sources it names that can be read. The generated file is the map's path
+    /// without `.map`, and the sources are resolved from the directory of the map and its `sourceRoot`.
+    pub fn load(mut self, map_path: &Path) -> std::io::Result<Traceback> {
+        let json = std::fs::read_to_string(map_path)?;
+        let mut map = SourceMap::parse(&json).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "not a Source Map v3"))?;
+        let directory = map_path.parent().unwrap_or(Path::new(""));
+        let root = directory.join(&map.source_root);
+        map.sources = map.sources.iter().map(|source| normalize(&root.join(source).to_string_lossy())).collect();
+        map.source_root.clear();
+        let generated = match map_path.to_string_lossy().strip_suffix(".map") {
+            Some(generated) => generated.to_string(),
+            None => directory.join(&map.file).to_string_lossy().into_owned(),
+        };
+        let sources: Vec<(String, String)> = map.sources.iter()
+            .filter_map(|source| Some((source.clone(), std::fs::read_to_string(source).ok()?)))
+            .collect();
+        self.maps.push((normalize(&generated), map));
+        for (path, source) in sources {
+            self = self.source(&path, &source);
+        }
+        Ok(self)
+    }
+
+    /// Rewrites every `file.lua:12:` position of a mapped file to `file.tlua:3:5:` and names the function
+    /// of each frame as in the source. Other positions and lines are left as they are.
+    pub fn rewrite(&self, traceback: &str) -> String {
+        traceback.split_inclusive('\n').map(|line| self.rewrite_line(line)).collect()
+    }
+
+    fn rewrite_line(&self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        let mut rest = line;
+        let mut frame = None;
+        while let Some((start, end, map, generated)) = self.find_position(rest) {
+            match map.original_position(generated, 0) {
+                Some((source, line, column)) => {
+                    out.push_str(&rest[..start]);
+                    out.push_str(&format!("{}:{}:{}:", source, line, column));
+                    frame.get_or_insert((source, line, column));
+                }
+                None => out.push_str(&rest[..end]),
+            }
+            rest = &rest[end..];
+        }
+        // `in function 'a'`, `in local 'a'`, `in method 'a'`, `in upvalue 'a'`, `in function <main.lua:12>`
+        let description = rest.find(" in ").filter(|ix| !rest[ix + 4..].starts_with("main chunk"));
+        let name = frame.and_then(|(source, line, column)| self.function_at(source, (line, column)));
+        if let (Some(ix), Some(name)) = (description, name) {
+            out.push_str(&rest[..ix]);
+            out.push_str(&format!(" in function '{}'", name));
+            if rest.ends_with('\n') {
+                out.push('\n');
+            }
+            return out;
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The first `path:12:` of `text` whose path names a mapped file, with its range, map and line. Paths are
+    /// compared whole, once normalized, except for the end left of a path Lua shortened to `...end/of/path`.
+    fn find_position(&self, text: &str) -> Option<(usize, usize, &SourceMap, usize)> {
+        let mut searched = 0;
+        loop {
+            let colon = searched + text[searched..].find(':')?;
+            searched = colon + 1;
+            let after = &text[colon + 1..];
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 || !after[digits..].starts_with(':') {
+                continue;
+            }
+            let start = text[..colon].rfind(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<')).map_or(0, |ix| ix + 1);
+            let path = normalize(&text[start..colon]);
+            let mapped = |generated: &String| match path.strip_prefix("...") {
+                Some(end) => generated.ends_with(end),
+                None => *generated == path,
+            };
+            let Some((_, map)) = self.maps.iter().find(|(generated, _)| mapped(generated)) else {
+                continue;
+            };
+            if let Ok(generated) = after[..digits].parse() {
+                return Some((start, colon + digits + 2, map, generated));
+            }
+        }
+    }
+
+    /// The name of the innermost function around a position of a source.
+    fn function_at(&self, path: &str, position: (usize, usize)) -> Option<&str> {
+        let functions = self.functions.get(&normalize(path))?;
+        // functions are collected outermost first
+        functions.iter().rev()
+            .find(|function| function.start <= position && position <= function.end)?
+            .name.as_deref()
+    }
+}
+
+/// A path with `/` separators, without `.` segments and with `..` segments applied where they can be.
+fn normalize(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for (ix, segment) in path.split(['/', '\\']).enumerate() {
+        match segment {
+            // a leading empty segment is the root of an absolute path
+            "" if ix > 0 => {}
+            "." => {}
+            ".." if segments.last().is_some_and(|last| !matches!(*last, "" | "..")) => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Finds the functions of a file, outer functions before the ones they contain, named as they are declared
+/// or assigned: `f`, `M.f`, `Class:method`.
+struct Collector<'a> {
+    lookup: LineColLookup<'a>,
+    functions: Vec<Function>,
+}
+
+impl Collector<'_> {
+    fn block(&mut self, block: &Block) {
+        for node in block {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &AstNode) {
+        match &node.kind {
+            AstNodes::LocalAssign { names, values } => {
+                for (ix, value) in values.iter().enumerate() {
+                    self.expression(value, names.get(ix).map(|name| name.name.clone()));
+                }
+            }
+            AstNodes::Assign { targets, values } => {
+                for (ix, value) in values.iter().enumerate() {
+                    self.expression(value, targets.get(ix).and_then(path));
+                }
+            }
+            AstNodes::Call(expr) => self.expression(expr, None),
+            AstNodes::Do(body) => self.block(body),
+            AstNodes::While { condition, body } | AstNodes::Repeat { body, condition } => {
+                self.expression(condition, None);
+                self.block(body);
+            }
+            AstNodes::If { condition, body, else_ifs, else_body } => {
+                self.expression(condition, None);
+                self.block(body);
+                for (condition, body) in else_ifs {
+                    self.expression(condition, None);
+                    self.block(body);
+                }
+                if let Some(body) = else_body {
+                    self.block(body);
+                }
+            }
+            AstNodes::Switch { subject, cases, default } => {
+                self.expression(subject, None);
+                for (values, body) in cases {
+                    self.expressions(values);
+                    self.block(body);
+                }
+                if let Some(body) = default {
+                    self.block(body);
+                }
+            }
+            AstNodes::NumericFor { start, limit, step, body, .. } => {
+                self.expressions([start, limit].into_iter().chain(step));
+                self.block(body);
+            }
+            AstNodes::GenericFor { exprs, body, .. } => {
+                self.expressions(exprs);
+                self.block(body);
+            }
+            AstNodes::Function(func) => {
+                let mut name = func.path.join(".");
+                if !func.path.is_empty() {
+                    name.push(if func.is_method { ':' } else { '.' });
+                }
+                name.push_str(&func.name);
+                self.function(func, Some(name));
+            }
+            AstNodes::LocalFunction(func) => self.function(func, Some(func.name.clone())),
+            AstNodes::Return(values) => self.expressions(values),
+            AstNodes::Class(class) => {
+                for field in &class.fields {
+                    if let Some(value) = &field.value {
+                        self.expression(value, Some(format!("{}.{}", class.name, field.field.name)));
+                    }
+                }
+                if let Some(constructor) = &class.constructor {
+                    self.function(constructor, Some(format!("{}.new", class.name)));
+                }
+                for method in &class.methods {
+                    let separator = if method.is_static { '.' } else { ':' };
+                    self.function(&method.func, Some(format!("{}{}{}", class.name, separator, method.func.name)));
+                }
+            }
+            AstNodes::Export(declaration) => self.node(declaration),
+            AstNodes::ExportDefault(value) => self.expression(value, Some("default".to_string())),
+            AstNodes::Break | AstNodes::Goto(_) | AstNodes::Label(_) | AstNodes::TypeAlias { .. }
+                | AstNodes::Interface(_) | AstNodes::Import(_) | AstNodes::Error => {}
+        }
+    }
+
+    fn function(&mut self, func: &FunctionDecl, name: Option<String>) {
+        let start = self.lookup.get(func.span.start);
+        let end = self.lookup.get(func.span.end);
+        self.functions.push(Function { name, start, end });
+        self.block(&func.body);
+    }
+
+    fn expressions<'e>(&mut self, exprs: impl IntoIterator<Item = &'e Expression>) {
+        for expr in exprs {
+            self.expression(expr, None);
+        }
+    }
+
+    /// `name` is the variable or field the expression is assigned to.
+    fn expression(&mut self, expr: &Expression, name: Option<String>) {
+        match &expr.kind {
+            Expressions::Function(func) => self.function(func, name),
+            Expressions::Paren(inner) => self.expression(inner, name),
+            Expressions::Field { target, .. } | Expressions::Unary { expr: target, .. } => self.expression(target, None),
+            Expressions::Index { target, key } => self.expressions([target.as_ref(), key.as_ref()]),
+            Expressions::Call { target, args } | Expressions::MethodCall { target, args, .. } => {
+                self.expression(target, None);
+                self.expressions(args);
+            }
+            Expressions::Binary { lhs, rhs, .. } => self.expressions([lhs.as_ref(), rhs.as_ref()]),
+            Expressions::Table(fields) => {
+                for field in fields {
+                    match field {
+                        TableField::Named(field, value) => self.expression(value, Some(match &name {
+                            Some(table) => format!("{}.{}", table, field),
+                            None => field.clone(),
+                        })),
+                        TableField::Positional(value) => self.expression(value, None),
+                        TableField::Keyed(key, value) => self.expressions([key, value]),
+                    }
+                }
+            }
+            Expressions::Nil | Expressions::True | Expressions::False | Expressions::Number(_) | Expressions::Float(_)
+                | Expressions::String(_) | Expressions::Dots | Expressions::Ident(_) | Expressions::Error => {}
+        }
+    }
+}
+
+/// `a.b.c` for an assignment target made of names.
+fn path(target: &Expression) -> Option<String> {
+    match &target.kind {
+        Expressions::Ident(name) => Some(name.clone()),
+        Expressions::Field { target, name } => path(target).map(|path| format!("{}.{}", path, name)),
+        _ => None,
+    }
+}
+
+
+#[test]
+fn rewrite_tracebacks() {
+    use crate::codegen::emit_mapped;
+    use crate::target::Target;
+    let src = "local Stack = {}\nfunction Stack.push(item: any)\n    local check = function()\n        error(\"full\")\n    end\n    check()\nend\nlocal function run()\n    Stack.push(1)\nend\nrun()\n";
+    let (code, segments) = emit_mapped(parse(Lexer::new(src).collect()).unwrap(), Target::Lua54, false);
+    let mut map = SourceMap::new("main.lua");
+    map.add_source("src/main.tlua", src, &segments, 0, 0);
+    assert_eq!(code.lines().nth(3), Some("        error(\"full\")"));
+    let traceback = Traceback::new().map(map).source("src/main.tlua", src);
+    let lua = "lua: ./main.lua:4: full\nstack traceback:\n\t[C]: in function 'error'\n\t./main.lua:4: in local 'check'\n\t./main.lua:6: in field 'push'\n\t./main.lua:9: in local 'run'\n\t./main.lua:11: in main chunk\n\tother.lua:3: in ?\n";
+    assert_eq!(traceback.rewrite(lua), "lua: src/main.tlua:4:9: full\nstack traceback:\n\t[C]: in function 'error'\n\tsrc/main.tlua:4:9: in function 'check'\n\tsrc/main.tlua:6:5: in function 'Stack.push'\n\tsrc/main.tlua:9:5: in function 'run'\n\tsrc/main.tlua:11:1: in main chunk\n\tother.lua:3: in ?\n");
+}
+
+#[test]
+fn load_maps_by_generated_path() {
+    use crate::codegen::emit_mapped;
+    use crate::target::Target;
+    let a = "local function fail()\n    error(\"a\")\nend\nfail()\n";
+    let b = "\nerror(\"b\")\n";
+    let map_json = |src: &str, source: &str, source_root: &str| {
+        let (_, segments) = emit_mapped(parse(Lexer::new(src).collect()).unwrap(), Target::Lua54, false);
+        let mut map = SourceMap::new("init.lua");
+        map.source_root = source_root.to_string();
+        map.add_source(source, src, &segments, 0, 0);
+        map.to_json()
+    };
+    let dir = crate::TestDir::new("traceback", &[
+        ("src/a/init.tlua", a),
+        ("src/b/init.tlua", b),
+        ("out/a/init.lua.map", &map_json(a, "a/init.tlua", "../../src")),
+        ("out/b/init.lua.map", &map_json(b, "../../src/b/init.tlua", "")),
+    ]);
+    let traceback = Traceback::new().load(Path::new(&dir.path("out/a/init.lua.map"))).unwrap().load(Path::new(&dir.path("out/b/init.lua.map"))).unwrap();
+    let root = normalize(&dir.path(""));
+    let lua = format!("{root}/out/b/init.lua:2: b\n\t{root}/out/./a/init.lua:2: in local 'fail'\n\t...b/init.lua:2: in main chunk\n\tinit.lua:2: in ?\n");
+    assert_eq!(traceback.rewrite(&lua), format!("{root}/src/b/init.tlua:2:1: b\n\t{root}/src/a/init.tlua:2:5: in function 'fail'\n\t{root}/src/b/init.tlua:2:1: in main chunk\n\tinit.lua:2: in ?\n"));
+}
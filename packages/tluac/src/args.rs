@@ -0,0 +1,147 @@
+use compiler::diagnostics::DiagnosticFormat;
+use compiler::lint::{Level, Lint, LintLevels};
+use compiler::sourcemap::SourceMaps;
+use compiler::target::Target;
+
+pub const USAGE: &str = "\
+Usage: tluac <command> [options]
+
+Commands:
+    build <files...>        Check the files and write their Lua code
+    check <files...>        Type check the files without writing anything
+    emit-tokens <file>      Print the tokens of a file
+    emit-ast <file>         Print the syntax tree of a file
+    explain <code>          Explain a diagnostic code, such as TL2008
+    traceback <maps...>     Rewrite a Lua traceback read from stdin to the tlua sources of the maps
+
+Options of build and check:
+    --strict                Strict nil-safety, type errors fail compilation
+    --mangle                Shorten local names, writing a name map next to each file
+    --bundle                Compile the files and every module they require into a single file
+    --out-dir <dir>         Write the generated files under <dir> instead of next to their sources
+    --target <target>       lua51, lua52, lua53, lua54 (default), luajit or luau
+    --source-map <kind>     Write a source map to a `.lua.map` file or inline
+    --preserve-comments     Keep the comments of the sources
+    --format <format>       Print diagnostics as human (default), json or sarif
+    --fix                   Apply the suggested fixes to the sources first
+    --allow <lint>          Do not report a lint, `all` for every lint
+    --warn <lint>           Report a lint as a warning
+    --deny <lint>           Report a lint as an error, failing compilation
+
+Exit codes:
+    0   success, warnings aside
+    1   compilation failed
+    2   invalid command line
+    3   a file could not be read or written";
+
+#[derive(Debug)]
+pub enum Command {
+    Build(Options),
+    Check(Options),
+    EmitTokens(String),
+    EmitAst(String),
+    Explain(String),
+    Traceback(Vec<String>),
+    Help,
+}
+
+/// The options of `build` and `check`.
+#[derive(Debug, Default)]
+pub struct Options {
+    pub files: Vec<String>,
+    pub strict: bool,
+    pub mangle: bool,
+    pub bundle: bool,
+    pub out_dir: Option<String>,
+    pub target: Target,
+    pub source_maps: SourceMaps,
+    pub preserve_comments: bool,
+    pub format: DiagnosticFormat,
+    pub fix: bool,
+    pub lint_levels: LintLevels,
+}
+
+/// Parses the arguments that follow the program name, returning the message of an invalid command line.
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let Some((command, rest)) = args.split_first() else {
+        return Ok(Command::Help);
+    };
+    match command.as_str() {
+        "build" => Ok(Command::Build(parse_options(rest)?)),
+        "check" => Ok(Command::Check(parse_options(rest)?)),
+        "emit-tokens" => Ok(Command::EmitTokens(single(command, rest)?)),
+        "emit-ast" => Ok(Command::EmitAst(single(command, rest)?)),
+        "explain" => Ok(Command::Explain(single(command, rest)?)),
+        "traceback" if !rest.is_empty() => Ok(Command::Traceback(rest.to_vec())),
+        "traceback" => Err("traceback expects the source maps of the generated files".to_string()),
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        other => Err(format!("unknown command '{}'", other)),
+    }
+}
+
+fn single(command: &str, rest: &[String]) -> Result<String, String> {
+    match rest {
+        [arg] => Ok(arg.clone()),
+        _ => Err(format!("{} expects a single argument", command)),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} expects a value", arg));
+        match arg.as_str() {
+            "--strict" => options.strict = true,
+            "--mangle" => options.mangle = true,
+            "--bundle" => options.bundle = true,
+            "--preserve-comments" => options.preserve_comments = true,
+            "--fix" => options.fix = true,
+            "--out-dir" => options.out_dir = Some(value()?),
+            "--target" => options.target = value()?.parse()?,
+            "--source-map" => options.source_maps = match value()?.as_str() {
+                "file" => SourceMaps::File,
+                "inline" => SourceMaps::Inline,
+                other => return Err(format!("unknown source map kind '{}', expected file or inline", other)),
+            },
+            "--format" => options.format = match value()?.as_str() {
+                "human" => DiagnosticFormat::Human,
+                "json" => DiagnosticFormat::JsonLines,
+                "sarif" => DiagnosticFormat::Sarif,
+                other => return Err(format!("unknown format '{}', expected human, json or sarif", other)),
+            },
+            "--allow" | "--warn" | "--deny" => {
+                let level = Level::from_name(&arg[2..]).expect("a level option");
+                match value()?.as_str() {
+                    "all" => options.lint_levels.set_all(level),
+                    name => options.lint_levels.set(Lint::from_name(name).ok_or_else(|| format!("unknown lint '{}'", name))?, level),
+                }
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag)),
+            file => options.files.push(file.to_string()),
+        }
+    }
+    if options.files.is_empty() {
+        return Err("no input files".to_string());
+    }
+    Ok(options)
+}
+
+
+#[test]
+fn parse_command_lines() {
+    let args = |line: &str| parse_args(&line.split_whitespace().map(String::from).collect::<Vec<_>>());
+    let Ok(Command::Build(options)) = args("build src/main.tlua --strict --bundle --out-dir out --target luau --format sarif --allow all --deny unused-local") else {
+        panic!("a build command line");
+    };
+    assert_eq!(options.files, ["src/main.tlua"]);
+    assert!(options.strict && options.bundle && !options.mangle);
+    assert_eq!((options.out_dir.as_deref(), options.target, options.format), (Some("out"), Target::Luau, DiagnosticFormat::Sarif));
+    assert_eq!((options.lint_levels.level(Lint::Shadowing), options.lint_levels.level(Lint::UnusedLocal)), (Level::Allow, Level::Deny));
+    assert!(matches!(args("explain TL1001"), Ok(Command::Explain(code)) if code == "TL1001"));
+    assert!(matches!(args(""), Ok(Command::Help)));
+    assert_eq!(args("check").unwrap_err(), "no input files");
+    assert_eq!(args("check a.tlua --target lua6").unwrap_err(), "unknown target 'lua6', expected lua51, lua52, lua53, lua54, luajit or luau");
+    assert_eq!(args("build a.tlua --out-dir").unwrap_err(), "--out-dir expects a value");
+    assert_eq!(args("run a.tlua").unwrap_err(), "unknown command 'run'");
+}
@@ -0,0 +1,180 @@
+mod args;
+
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use compiler::diagnostics::{explain, CompileError, DiagnosticFormat, Diagnostics};
+use compiler::parser::{parse_recovering, Lexer};
+use compiler::traceback::Traceback;
+use compiler::Compiler;
+use crate::args::{parse_args, Command, Options, USAGE};
+
+/// Exit code of a compilation that reported errors.
+const FAILED: u8 = 1;
+/// Exit code of an invalid command line.
+const INVALID_USAGE: u8 = 2;
+/// Exit code of a file that could not be read or written.
+const IO_ERROR: u8 = 3;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(INVALID_USAGE);
+        }
+    };
+    match run(command) {
+        Ok(code) => code,
+        // the reader of the output is gone, as with `tluac emit-ast main.tlua | head`
+        Err(error) if error.kind() == ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(IO_ERROR)
+        }
+    }
+}
+
+fn run(command: Command) -> std::io::Result<ExitCode> {
+    match command {
+        Command::Build(options) => build(&options),
+        Command::Check(options) => check(&options),
+        Command::EmitTokens(path) => {
+            let source = std::fs::read_to_string(path)?;
+            let tokens: String = Lexer::new(&source).map(|token| format!("{}..{} {:?}\n", token.span.start, token.span.end, token.kind)).collect();
+            output(&tokens)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::EmitAst(path) => {
+            let source = std::fs::read_to_string(&path)?;
+            let (block, errors) = parse_recovering(Lexer::new(&source).collect());
+            if !errors.is_empty() {
+                let mut diagnostics = Diagnostics::new(&path, &source);
+                errors.into_iter().for_each(|error| diagnostics.push(error));
+                eprint!("{}", diagnostics.render());
+                return Ok(ExitCode::from(FAILED));
+            }
+            output(&format!("{:#?}\n", block))?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Explain(code) => match explain(&code) {
+            Some(explanation) => {
+                output(explanation)?;
+                Ok(ExitCode::SUCCESS)
+            }
+            None => {
+                eprintln!("error: no explanation for '{}'", code);
+                Ok(ExitCode::from(INVALID_USAGE))
+            }
+        },
+        Command::Traceback(maps) => {
+            let mut traceback = Traceback::new();
+            for map in maps {
+                traceback = traceback.load(Path::new(&map))?;
+            }
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            output(&traceback.rewrite(&input))?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Help => {
+            output(&format!("{}\n", USAGE))?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn compiler(options: &Options) -> Compiler {
+    let compiler = Compiler::new(options.files.clone(), options.strict, options.mangle, options.bundle)
+        .target(options.target)
+        .source_maps(options.source_maps)
+        .preserve_comments(options.preserve_comments)
+        .lint_levels(options.lint_levels.clone());
+    match &options.out_dir {
+        Some(dir) => compiler.out_dir(dir),
+        None => compiler,
+    }
+}
+
+fn check(options: &Options) -> std::io::Result<ExitCode> {
+    let compiler = compiler(options);
+    if let Some(failed) = fix(&compiler, options)? {
+        return Ok(failed);
+    }
+    match compiler.check() {
+        Ok(files) => {
+            report(options.format, &files.into_iter().map(|file| file.diagnostics).collect::<Vec<_>>())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(error) => failed(error, options.format),
+    }
+}
+
+fn build(options: &Options) -> std::io::Result<ExitCode> {
+    let compiler = compiler(options);
+    if let Some(failed) = fix(&compiler, options)? {
+        return Ok(failed);
+    }
+    match compiler.compile() {
+        Ok(files) => {
+            report(options.format, &files.iter().flat_map(|file| file.diagnostics.iter().cloned()).collect::<Vec<_>>())?;
+            for file in &files {
+                file.write()?;
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(error) => failed(error, options.format),
+    }
+}
+
+/// Applies the suggested fixes with `--fix`, returning the exit code when the files cannot be checked at all.
+fn fix(compiler: &Compiler, options: &Options) -> std::io::Result<Option<ExitCode>> {
+    if !options.fix {
+        return Ok(None);
+    }
+    match compiler.fix() {
+        Ok(files) => {
+            for file in files {
+                eprintln!("{}: applied {} fix(es)", file.path, file.applied);
+                if !file.skipped.is_empty() {
+                    eprintln!("{}: left {} overlapping fix(es), run again to apply them", file.path, file.skipped.len());
+                }
+            }
+            Ok(None)
+        }
+        Err(error) => failed(error, options.format).map(Some),
+    }
+}
+
+fn failed(error: CompileError, format: DiagnosticFormat) -> std::io::Result<ExitCode> {
+    let summary = error.to_string();
+    match error {
+        CompileError::Io(error) => Err(error),
+        CompileError::Failed(files) => {
+            report(format, &files)?;
+            if format == DiagnosticFormat::Human {
+                eprintln!("{}", summary);
+            }
+            Ok(ExitCode::from(FAILED))
+        }
+    }
+}
+
+/// Prints diagnostics for a terminal to stderr, and the machine-readable formats to stdout for the tools
+/// reading them. A SARIF log is printed even without results.
+fn report(format: DiagnosticFormat, files: &[Diagnostics]) -> std::io::Result<()> {
+    let files: Vec<Diagnostics> = files.iter().filter(|file| !file.is_empty()).cloned().collect();
+    match format {
+        DiagnosticFormat::Human => eprint!("{}", format.render(&files)),
+        DiagnosticFormat::JsonLines | DiagnosticFormat::Sarif => output(&format.render(&files))?,
+    }
+    Ok(())
+}
+
+/// Writes to stdout, returning the error `print!` would panic with.
+fn output(text: &str) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()
+}